@@ -0,0 +1,163 @@
+use std::{
+    fs::File,
+    io::Read,
+    path::{ Path, PathBuf },
+};
+
+use source_demo_tool::demo_file::DemoFile;
+
+use crate::document::DemoDocument;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Container {
+    Plain,
+    Gzip,
+    Bzip2,
+    Zip,
+}
+
+impl Container {
+    pub fn detect(path: &Path) -> Result<Self, String> {
+        let mut file = match File::open(path) {
+            Ok(f) => f,
+            Err(e) => return Err(format!("couldn't open '{}': {}", path.display(), e))
+        };
+
+        let mut magic = [0u8; 4];
+        let mut read = 0;
+        while read < magic.len() {
+            match file.read(&mut magic[read..]) {
+                Ok(0) => break,
+                Ok(n) => read += n,
+                Err(e) => return Err(format!("couldn't read '{}': {}", path.display(), e))
+            }
+        }
+
+        Ok(Self::from_magic(&magic[..read]))
+    }
+
+    pub fn from_magic(magic: &[u8]) -> Self {
+        if magic.starts_with(&[0x1f, 0x8b]) {
+            Container::Gzip
+        } else if magic.starts_with(b"BZh") {
+            Container::Bzip2
+        } else if magic.starts_with(b"PK\x03\x04") || magic.starts_with(b"PK\x05\x06") {
+            Container::Zip
+        } else {
+            Container::Plain
+        }
+    }
+}
+
+// names of all .dem entries in a zip archive, sorted by name.
+pub fn list_zip_demos(path: &Path) -> Result<Vec<String>, String> {
+    let archive = open_zip(path)?;
+    let mut names: Vec<String> = archive.file_names()
+        .filter(|name| name.to_lowercase().ends_with(".dem"))
+        .map(|name| name.to_owned())
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+fn open_zip(path: &Path) -> Result<zip::ZipArchive<File>, String> {
+    let file = match File::open(path) {
+        Ok(f) => f,
+        Err(e) => return Err(format!("couldn't open '{}': {}", path.display(), e))
+    };
+    match zip::ZipArchive::new(file) {
+        Ok(a) => Ok(a),
+        Err(e) => Err(format!("couldn't read zip archive '{}': {}", path.display(), e))
+    }
+}
+
+// reads the (decompressed) bytes of a demo, zip_entry selects which demo to
+// read from a zip archive and is required when it holds more than one.
+pub fn read_demo_bytes(path: &Path, zip_entry: Option<&str>) -> Result<Vec<u8>, String> {
+    let container = Container::detect(path)?;
+    let mut data = Vec::new();
+    let res = match container {
+        Container::Plain => File::open(path).and_then(|mut f| f.read_to_end(&mut data)),
+        Container::Gzip => File::open(path).and_then(|f| {
+            flate2::read::MultiGzDecoder::new(f).read_to_end(&mut data)
+        }),
+        Container::Bzip2 => File::open(path).and_then(|f| {
+            bzip2::read::MultiBzDecoder::new(f).read_to_end(&mut data)
+        }),
+        Container::Zip => {
+            let entry_name = match zip_entry {
+                Some(name) => name.to_owned(),
+                None => {
+                    let mut names = list_zip_demos(path)?;
+                    match names.len() {
+                        0 => return Err(format!("'{}' contains no .dem files", path.display())),
+                        1 => names.remove(0),
+                        _ => return Err(format!(
+                            "'{}' contains multiple demos, pick one of: {}",
+                            path.display(),
+                            names.join(", ")
+                        ))
+                    }
+                }
+            };
+
+            let mut archive = open_zip(path)?;
+            let mut entry = match archive.by_name(&entry_name) {
+                Ok(entry) => entry,
+                Err(e) => return Err(format!("couldn't find '{}' in '{}': {}", entry_name, path.display(), e))
+            };
+            entry.read_to_end(&mut data)
+        }
+    };
+
+    match res {
+        Ok(_) => Ok(data),
+        Err(e) => Err(format!("couldn't decompress '{}': {}", path.display(), e))
+    }
+}
+
+// where a demo was read from, zip_entry is set for demos inside zip archives.
+#[derive(Debug, Clone)]
+pub struct DemoSource {
+    pub path: PathBuf,
+    pub zip_entry: Option<String>,
+}
+
+impl DemoSource {
+    pub fn read_bytes(&self) -> Result<Vec<u8>, String> {
+        read_demo_bytes(&self.path, self.zip_entry.as_deref())
+    }
+
+    // the path shown for the demo, zip entries are joined onto the archive path.
+    pub fn display_path(&self) -> PathBuf {
+        match &self.zip_entry {
+            Some(entry) => self.path.join(entry),
+            None => self.path.clone()
+        }
+    }
+
+    pub fn open_document(&self) -> Result<DemoDocument, String> {
+        DemoDocument::from_bytes(&self.read_bytes()?)
+    }
+}
+
+// opens a demo that may be compressed, returns its document and the DemoFile built from it.
+// the DemoFile's path points at the archive (joined with the entry name for zip archives).
+pub fn open_demo(path: &Path, zip_entry: Option<&str>) -> Result<(DemoDocument, DemoFile), String> {
+    let source = DemoSource {
+        path: path.to_path_buf(),
+        zip_entry: zip_entry.map(|e| e.to_owned()),
+    };
+    let document = source.open_document()?;
+    let demo_file = document.to_demo_file(source.display_path())?;
+    Ok((document, demo_file))
+}
+
+// whether two paths are the same file once relative parts and symlinks are resolved,
+// paths that don't exist are never the same file.
+pub fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false
+    }
+}
\ No newline at end of file
@@ -0,0 +1,445 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{ self, BufWriter, Write },
+    path::{ Path, PathBuf },
+};
+
+use clap::{ Parser, Subcommand, ValueEnum };
+use source_demo_tool::{
+    demo_file::{
+        DemoFile,
+        frame::{ Command, Frame },
+        packet::netmessage::NetMessage,
+    },
+    protobuf_message::ProtobufMessageEnumTraits,
+};
+
+use crate::{
+    archive::{ self, DemoSource },
+    document::{ DemoDocument, entities::EntityTimeline, player_info::UserIdLookup },
+    edit::patch::{ self, PatchOp, PatchSyntax },
+    export::{ self, flatten_protobuf_fields, json::JsonFormat, protobuf_value_to_string, tick_to_time_string },
+};
+
+pub mod exit_code {
+    pub const OK: i32 = 0;
+    pub const INVALID: i32 = 1;
+    pub const FAILED: i32 = 2;
+}
+
+/// Browse source engine demo files, launches the gui when no command is given.
+#[derive(Parser)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
+pub struct Args {
+    /// Demo file to open in the gui.
+    pub demo: Option<PathBuf>,
+    #[command(subcommand)]
+    pub command: Option<CliCommand>,
+}
+
+#[derive(Subcommand)]
+pub enum CliCommand {
+    /// Print the header and server info of a demo.
+    Info {
+        demo: PathBuf,
+    },
+    /// Export the frames, user messages, game events and data tables of a demo as csv or json files.
+    Export {
+        demo: PathBuf,
+        /// Directory the files are written to.
+        #[arg(short, long, default_value = ".")]
+        output: PathBuf,
+        /// Only export game events with this name, adds a column per event key (csv only).
+        #[arg(long)]
+        event: Option<String>,
+        /// Only export game events whose userid, attacker or assister is this player name or user id (csv only).
+        #[arg(long)]
+        player: Option<String>,
+        #[arg(short, long, value_enum, default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+    },
+    /// Check a demo for parse errors, exits with 1 if any were found.
+    Validate {
+        demo: PathBuf,
+        /// Also treat warnings as failures.
+        #[arg(long)]
+        strict: bool,
+    },
+    /// Apply the edits in a patch file (toml or json) to demos and save them as new demos.
+    Patch {
+        #[arg(required = true)]
+        demos: Vec<PathBuf>,
+        /// The patch file, the format is picked by its extension.
+        #[arg(long)]
+        spec: PathBuf,
+        /// The demo to write, or a directory when patching several demos.
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// One csv file per table.
+    Csv,
+    /// A single json document.
+    Json,
+    /// One json object per line.
+    Ndjson,
+}
+
+pub fn run(command: CliCommand) -> i32 {
+    let demo_path = match &command {
+        CliCommand::Info { demo } |
+        CliCommand::Export { demo, .. } |
+        CliCommand::Validate { demo, .. } => demo.clone(),
+        // patch works on documents instead of DemoFiles
+        CliCommand::Patch { demos, spec, output } => return run_patch(demos, spec, output)
+    };
+
+    // the json exports write the whole demo, the filters only apply to the csv game events table
+    if let CliCommand::Export { event, player, format, .. } = &command {
+        if *format != ExportFormat::Csv && (event.is_some() || player.is_some()) {
+            let format = format.to_possible_value().map(|v| v.get_name().to_owned()).unwrap_or_default();
+            eprintln!("--event and --player only apply to csv exports, they can't be used with --format {}", format);
+            return exit_code::FAILED
+        }
+    }
+
+    let (document, demo_file) = match archive::open_demo(&demo_path, None) {
+        Ok(opened) => opened,
+        Err(e) => {
+            eprintln!("Failed to open file: {}", e);
+            return exit_code::FAILED
+        }
+    };
+
+    match command {
+        CliCommand::Info { .. } => run_info(&demo_file),
+        CliCommand::Export { output, event, player, format: ExportFormat::Csv, .. } => {
+            run_export(&demo_file, &user_ids(&document), &output, event, player)
+        },
+        CliCommand::Export { output, format, .. } => run_export_json(&demo_file, &user_ids(&document), &output, format),
+        CliCommand::Validate { strict, .. } => run_validate(&demo_file, &document, strict),
+        CliCommand::Patch { .. } => unreachable!(),
+    }
+}
+
+// the players user ids in game events are resolved against, demos whose userinfo table
+// can't be decoded are exported with bare user ids.
+fn user_ids(document: &DemoDocument) -> UserIdLookup {
+    match UserIdLookup::from_document(document) {
+        Ok(user_ids) => user_ids,
+        Err(e) => {
+            eprintln!("Player names couldn't be read, user ids are exported as they are: {}", e);
+            UserIdLookup::default()
+        }
+    }
+}
+
+fn get_tick_interval(demo_file: &DemoFile) -> f32 {
+    match demo_file.get_server_info() {
+        Some(si) => si.tick_interval.unwrap_or(0.0),
+        None => 0.0
+    }
+}
+
+fn run_info(demo_file: &DemoFile) -> i32 {
+    let header = &demo_file.header;
+    println!("Header");
+    println!("  Client Name: {}", header.client_name);
+    println!("  Server Name: {}", header.server_name);
+    println!("  Map Name: {}", header.map_name);
+    println!("  Game Directory: {}", header.game_directory);
+    println!("  Playback Time: {}", header.playback_time);
+    println!("  Ticks: {}", header.ticks);
+    println!("  Frames: {}", header.frames);
+    println!("  Demo Protocol: {}", header.demo_protocol);
+    println!("  Network Protocol: {}", header.network_protocol);
+    println!("  Sign On Length: {}", header.sign_on_length);
+
+    println!("Server Info");
+    match demo_file.get_server_info() {
+        Some(si) => {
+            let fields = flatten_protobuf_fields(
+                NetMessage::ServerInfo(si.clone()).to_vec(),
+                true
+            );
+            for (name, value) in fields {
+                println!("  {}: {}", name, protobuf_value_to_string(&value));
+            }
+        },
+        None => println!("  No Server Info was found in the demo file.")
+    }
+
+    let tick_interval = get_tick_interval(demo_file);
+    let last_tick = match demo_file.frames.last() {
+        Some(f) => f.tick,
+        None => 0
+    };
+    println!("Contents");
+    println!("  Frames: {}", demo_file.frames.len());
+    println!("  Sign On Frames: {}", demo_file.sign_on_frames.len());
+    println!("  Last Tick: {} ({})", last_tick, tick_to_time_string(tick_interval, last_tick));
+
+    exit_code::OK
+}
+
+fn create_csv_file(dir: &Path, stem: &str, table: &str) -> io::Result<(PathBuf, BufWriter<File>)> {
+    let path = dir.join(format!("{}-{}.csv", stem, table));
+    let file = File::create(&path)?;
+    Ok((path, BufWriter::new(file)))
+}
+
+fn run_export(
+    demo_file: &DemoFile,
+    user_ids: &UserIdLookup,
+    output: &Path,
+    event: Option<String>,
+    player: Option<String>
+) -> i32 {
+    let stem = match demo_file.path.file_stem() {
+        Some(s) => s.to_string_lossy().to_string(),
+        None => "demo".to_owned()
+    };
+    let tick_interval = get_tick_interval(demo_file);
+
+    let res = (|| -> io::Result<()> {
+        std::fs::create_dir_all(output)?;
+
+        let write_table = |table: &str, write_fn: &dyn Fn(&mut BufWriter<File>) -> io::Result<()>| -> io::Result<()> {
+            let (path, mut writer) = create_csv_file(output, &stem, table)?;
+            write_fn(&mut writer)?;
+            writer.flush()?;
+            println!("Wrote {}", path.display());
+            Ok(())
+        };
+
+        write_table("frames", &|w| export::write_frames_csv(
+            w, demo_file.frames.iter().enumerate(), tick_interval
+        ))?;
+        write_table("sign_on_frames", &|w| export::write_frames_csv(
+            w, demo_file.sign_on_frames.iter().enumerate(), tick_interval
+        ))?;
+
+        let user_messages = demo_file.get_user_messages();
+        write_table("user_messages", &|w| export::write_messages_csv(
+            w, export::user_message_rows(&user_messages).into_iter(), Some(tick_interval)
+        ))?;
+
+        let game_events = demo_file.get_full_game_events();
+        let game_events = game_events
+            .iter()
+            .enumerate()
+            .filter(|(_, ev)| event.as_ref().is_none_or(|name| &ev.event_name == name))
+            .filter(|(_, ev)| player.as_ref().is_none_or(|player| ev.event_keys.iter().any(|k| {
+                user_ids.find_key(k, ev.event_tick).is_some_and(|(_, p)| {
+                    p.name_at(ev.event_tick) == player || &p.info.user_id.to_string() == player
+                })
+            })));
+        write_table("game_events", &|w| export::write_game_events_csv(
+            w, game_events.clone(), tick_interval, event.is_some(), user_ids
+        ))?;
+
+        if let Some(dt) = demo_file.get_data_tables().first() {
+            write_table("class_descriptions", &|w| export::write_class_descriptions_csv(w, dt))?;
+            write_table("send_tables", &|w| export::write_send_tables_csv(w, dt))?;
+        }
+        Ok(())
+    })();
+
+    match res {
+        Ok(()) => exit_code::OK,
+        Err(e) => {
+            eprintln!("Failed to export tables: {}", e);
+            exit_code::FAILED
+        }
+    }
+}
+
+fn run_export_json(demo_file: &DemoFile, user_ids: &UserIdLookup, output: &Path, format: ExportFormat) -> i32 {
+    let (extension, json_format) = match format {
+        ExportFormat::Ndjson => ("ndjson", JsonFormat::Lines),
+        _ => ("json", JsonFormat::Document),
+    };
+    let stem = match demo_file.path.file_stem() {
+        Some(s) => s.to_string_lossy().to_string(),
+        None => "demo".to_owned()
+    };
+    let path = output.join(format!("{}.{}", stem, extension));
+
+    let res = (|| -> io::Result<()> {
+        std::fs::create_dir_all(output)?;
+        let mut writer = BufWriter::new(File::create(&path)?);
+        export::json::write_demo_json(&mut writer, demo_file, user_ids, json_format)?;
+        writer.flush()
+    })();
+
+    match res {
+        Ok(()) => {
+            println!("Wrote {}", path.display());
+            exit_code::OK
+        },
+        Err(e) => {
+            eprintln!("Failed to export json: {}", e);
+            exit_code::FAILED
+        }
+    }
+}
+
+fn run_validate(demo_file: &DemoFile, document: &DemoDocument, strict: bool) -> i32 {
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+
+    if let Some(e) = &demo_file.last_index_error {
+        errors.push(format!("frames could not be fully indexed: {}", e));
+    }
+
+    match demo_file.frames.last() {
+        Some(Frame { command: Command::Stop, .. }) => {},
+        Some(_) => errors.push("last frame is not a Stop frame, the demo may be truncated".into()),
+        None => errors.push("demo contains no frames".into()),
+    }
+
+    let header = &demo_file.header;
+    if header.ticks == 0 && header.frames == 0 && header.playback_time == 0.0 {
+        warnings.push("header ticks/frames/playback time are zero, the recording may be unfinished".into());
+    }
+
+    if demo_file.get_server_info().is_none() {
+        errors.push("no Server Info was found".into());
+    }
+    if demo_file.get_game_event_list().is_none() {
+        warnings.push("no Game Event List was found".into());
+    }
+
+    // name -> (errors, warnings)
+    let mut message_issues: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+    let all_frames = demo_file.sign_on_frames.iter().chain(demo_file.frames.iter());
+    for frame in all_frames {
+        if let Command::Packet(pd) | Command::SignOn(pd) = &frame.command {
+            for nmsg_ret in &pd.network_messages {
+                let name = match &nmsg_ret.message {
+                    Some(nmsg) => nmsg.to_str().to_owned(),
+                    None => "????".to_owned()
+                };
+                if nmsg_ret.err.is_some() {
+                    message_issues.entry(name.clone()).or_insert((0, 0)).0 += 1;
+                }
+                if let Some(warns) = &nmsg_ret.warnings {
+                    if warns.has_warnings() {
+                        message_issues.entry(name).or_insert((0, 0)).1 += 1;
+                    }
+                }
+            }
+        }
+    }
+    for (name, (errs, warns)) in message_issues {
+        if errs > 0 {
+            errors.push(format!("{} NetMessage(s) of type {} failed to parse", errs, name));
+        }
+        if warns > 0 {
+            warnings.push(format!("{} NetMessage(s) of type {} parsed with warnings", warns, name));
+        }
+    }
+
+    // the entity decoder is this tool's own, so problems with it are only warnings
+    match EntityTimeline::from_document(document) {
+        Ok(timeline) => if let Some(e) = timeline.decode_error {
+            warnings.push(format!("entity data stopped decoding at {}", e));
+        },
+        Err(e) => warnings.push(format!("entities couldn't be decoded: {}", e)),
+    }
+
+    for e in &errors {
+        println!("error: {}", e);
+    }
+    for w in &warnings {
+        println!("warning: {}", w);
+    }
+
+    if !errors.is_empty() || (strict && !warnings.is_empty()) {
+        println!("{}: INVALID ({} errors, {} warnings)", demo_file.path.display(), errors.len(), warnings.len());
+        exit_code::INVALID
+    } else {
+        println!("{}: OK ({} warnings)", demo_file.path.display(), warnings.len());
+        exit_code::OK
+    }
+}
+
+fn run_patch(demos: &[PathBuf], spec: &Path, output: &Path) -> i32 {
+    let syntax = PatchSyntax::from_extension(
+        &spec.extension().unwrap_or_default().to_string_lossy()
+    );
+    let ops = match std::fs::read_to_string(spec).map_err(|e| e.to_string()).and_then(|text| patch::parse_patch(&text, syntax)) {
+        Ok(ops) => ops,
+        Err(e) => {
+            eprintln!("Failed to read patch {}: {}", spec.display(), e);
+            return exit_code::INVALID
+        }
+    };
+
+    // several demos are written into the output directory under their own names
+    let output_is_dir = demos.len() > 1 || output.is_dir();
+    let out_paths: Vec<PathBuf> = demos.iter().map(|demo| match output_is_dir {
+        true => output.join(Path::new(demo.file_stem().unwrap_or_default()).with_extension("dem")),
+        false => output.to_path_buf()
+    }).collect();
+    // demos with the same name in different directories (or compressed) would overwrite each other
+    for (i, out_path) in out_paths.iter().enumerate() {
+        if let Some(j) = out_paths[..i].iter().position(|p| p == out_path) {
+            eprintln!(
+                "{} and {} would both be written to {}, patch them separately.",
+                demos[j].display(), demos[i].display(), out_path.display()
+            );
+            return exit_code::FAILED
+        }
+    }
+
+    if output_is_dir {
+        if let Err(e) = std::fs::create_dir_all(output) {
+            eprintln!("Failed to create {}: {}", output.display(), e);
+            return exit_code::FAILED
+        }
+    }
+
+    let mut failed = 0;
+    for (demo, out_path) in demos.iter().zip(&out_paths) {
+        println!("Patching {}", demo.display());
+        match patch_demo(demo, &ops, out_path) {
+            Ok(()) => println!("  Wrote {}", out_path.display()),
+            Err(e) => {
+                eprintln!("  error: {}", e);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        eprintln!("{} of {} demos failed, nothing was written for them.", failed, demos.len());
+        return exit_code::FAILED
+    }
+    exit_code::OK
+}
+
+// applies every op before writing, so a demo is only written when all of them matched.
+fn patch_demo(demo: &Path, ops: &[PatchOp], out_path: &Path) -> Result<(), String> {
+    if archive::same_file(demo, out_path) {
+        return Err("the output would overwrite the demo".into())
+    }
+    let source = DemoSource { path: demo.to_path_buf(), zip_entry: None };
+    let mut document = source.open_document()?;
+
+    for (i, op) in ops.iter().enumerate() {
+        let summary = op.apply(&mut document).map_err(|e| format!("edit {}: {}", i + 1, e))?;
+        println!("  {}. {}", i + 1, summary);
+    }
+
+    let res = File::create(out_path).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        document.write(&mut writer)?;
+        writer.flush()
+    });
+    res.map_err(|e| format!("failed to write {}: {}", out_path.display(), e))
+}
\ No newline at end of file
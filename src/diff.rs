@@ -0,0 +1,402 @@
+use std::collections::{ BTreeMap, HashMap };
+
+use source_demo_tool::{
+    demo_file::{
+        DemoFile,
+        FullGameEvent,
+        frame::{ Command, DataTablesData },
+        packet::netmessage::NetMessage,
+    },
+    protobuf_message::ProtobufMessageEnumTraits,
+};
+
+use crate::export::{ flatten_protobuf_fields, game_event_key_value_string, protobuf_value_to_string };
+
+// edit distance past which game event streams are no longer aligned,
+// keeps memory bounded for unrelated demos.
+const MAX_STREAM_EDIT_DISTANCE: usize = 4000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffKind {
+    Same,
+    Added,
+    Removed,
+    Changed,
+}
+
+#[derive(Debug, Clone)]
+pub struct DiffRow {
+    pub name: String,
+    pub left: Option<String>,
+    pub right: Option<String>,
+    pub kind: DiffKind,
+}
+
+#[derive(Debug, Clone)]
+pub struct DiffSection {
+    pub name: &'static str,
+    pub rows: Vec<DiffRow>,
+    pub note: Option<String>,
+}
+
+impl DiffSection {
+    pub fn count(&self, kind: DiffKind) -> usize {
+        self.rows.iter().filter(|r| r.kind == kind).count()
+    }
+
+    pub fn has_differences(&self) -> bool {
+        self.rows.iter().any(|r| r.kind != DiffKind::Same)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DemoDiff {
+    pub left_name: String,
+    pub right_name: String,
+    pub sections: Vec<DiffSection>,
+}
+
+impl DemoDiff {
+    pub fn new(left: &DemoFile, right: &DemoFile) -> Self {
+        let left_events = left.get_full_game_events();
+        let right_events = right.get_full_game_events();
+        let (event_rows, event_note) = diff_game_event_streams(&left_events, &right_events);
+
+        let sections = vec![
+            DiffSection {
+                name: "Header",
+                rows: diff_fields(header_fields(left), header_fields(right)),
+                note: None,
+            },
+            DiffSection {
+                name: "Server Info",
+                rows: diff_fields(server_info_fields(left), server_info_fields(right)),
+                note: None,
+            },
+            DiffSection {
+                name: "Class Descriptions",
+                rows: diff_fields(
+                    class_description_fields(left.get_data_tables().first().copied()),
+                    class_description_fields(right.get_data_tables().first().copied())
+                ),
+                note: None,
+            },
+            DiffSection {
+                name: "Send Tables",
+                rows: diff_fields(
+                    send_table_fields(left.get_data_tables().first().copied()),
+                    send_table_fields(right.get_data_tables().first().copied())
+                ),
+                note: None,
+            },
+            DiffSection {
+                name: "NetMessage Counts",
+                rows: diff_fields(netmessage_counts(left), netmessage_counts(right)),
+                note: None,
+            },
+            DiffSection {
+                name: "Game Event Counts",
+                rows: diff_fields(game_event_counts(&left_events), game_event_counts(&right_events)),
+                note: None,
+            },
+            DiffSection {
+                name: "Game Events",
+                rows: event_rows,
+                note: event_note,
+            },
+        ];
+
+        Self {
+            left_name: demo_name(left),
+            right_name: demo_name(right),
+            sections,
+        }
+    }
+}
+
+fn demo_name(demo_file: &DemoFile) -> String {
+    demo_file.path.file_name().unwrap_or_default().to_string_lossy().to_string()
+}
+
+// compares two ordered name -> value lists, rows keep the order of the left
+// side with right only names appended.
+fn diff_fields(left: Vec<(String, String)>, right: Vec<(String, String)>) -> Vec<DiffRow> {
+    let mut right_map: HashMap<String, String> = HashMap::new();
+    let mut right_order = Vec::new();
+    for (name, value) in right {
+        if !right_map.contains_key(&name) {
+            right_order.push(name.clone());
+        }
+        right_map.insert(name, value);
+    }
+
+    let mut rows = Vec::new();
+    for (name, left_value) in left {
+        match right_map.remove(&name) {
+            Some(right_value) => {
+                let kind = if left_value == right_value {
+                    DiffKind::Same
+                } else {
+                    DiffKind::Changed
+                };
+                rows.push(DiffRow { name, left: Some(left_value), right: Some(right_value), kind });
+            },
+            None => rows.push(DiffRow { name, left: Some(left_value), right: None, kind: DiffKind::Removed }),
+        }
+    }
+    for name in right_order {
+        if let Some(right_value) = right_map.remove(&name) {
+            rows.push(DiffRow { name, left: None, right: Some(right_value), kind: DiffKind::Added });
+        }
+    }
+    rows
+}
+
+fn header_fields(demo_file: &DemoFile) -> Vec<(String, String)> {
+    let header = &demo_file.header;
+    vec![
+        ("Demo Protocol".into(), header.demo_protocol.to_string()),
+        ("Network Protocol".into(), header.network_protocol.to_string()),
+        ("Server Name".into(), header.server_name.clone()),
+        ("Client Name".into(), header.client_name.clone()),
+        ("Map Name".into(), header.map_name.clone()),
+        ("Game Directory".into(), header.game_directory.clone()),
+        ("Playback Time".into(), header.playback_time.to_string()),
+        ("Ticks".into(), header.ticks.to_string()),
+        ("Frames".into(), header.frames.to_string()),
+        ("Sign On Length".into(), header.sign_on_length.to_string()),
+    ]
+}
+
+fn flatten_to_strings(message: NetMessage) -> Vec<(String, String)> {
+    flatten_protobuf_fields(message.to_vec(), false)
+        .into_iter()
+        .map(|(name, value)| (name, protobuf_value_to_string(&value)))
+        .collect()
+}
+
+fn server_info_fields(demo_file: &DemoFile) -> Vec<(String, String)> {
+    match demo_file.get_server_info() {
+        Some(si) => flatten_to_strings(NetMessage::ServerInfo(si.clone())),
+        None => Vec::new()
+    }
+}
+
+fn class_description_fields(data_tables: Option<&DataTablesData>) -> Vec<(String, String)> {
+    let mut fields = Vec::new();
+    if let Some(dt) = data_tables {
+        for cd in &dt.class_descriptions {
+            fields.push((
+                cd.network_name.clone(),
+                format!("Class ID: {}, Table: {}", cd.class_id, cd.table_name)
+            ));
+        }
+    }
+    fields
+}
+
+// send props are keyed by their var_name rather than their index, so an
+// inserted prop doesn't show every following prop as changed.
+fn send_table_fields(data_tables: Option<&DataTablesData>) -> Vec<(String, String)> {
+    let mut fields = Vec::new();
+    if let Some(dt) = data_tables {
+        for st in &dt.send_tables {
+            let table_name = st.net_table_name.clone().unwrap_or_else(|| "????".into());
+
+            let mut name_counts: HashMap<String, usize> = HashMap::new();
+            let prop_names: Vec<String> = st.SendProp.iter().map(|sp| {
+                let name = sp.var_name.clone().unwrap_or_else(|| "????".into());
+                let count = name_counts.entry(name.clone()).or_insert(0);
+                *count += 1;
+                if *count > 1 {
+                    format!("{}#{}", name, count)
+                } else {
+                    name
+                }
+            }).collect();
+
+            for (name, value) in flatten_to_strings(NetMessage::SendTable(st.clone())) {
+                let name = match name.strip_prefix("SendProp[") {
+                    Some(rest) => {
+                        let (index, field) = rest.split_once(']').unwrap_or((rest, ""));
+                        match index.parse::<usize>().ok().and_then(|i| prop_names.get(i)) {
+                            Some(prop_name) => format!("SendProp[{}]{}", prop_name, field),
+                            None => name.clone()
+                        }
+                    },
+                    None => name
+                };
+                fields.push((format!("{}.{}", table_name, name), value));
+            }
+        }
+    }
+    fields
+}
+
+fn netmessage_counts(demo_file: &DemoFile) -> Vec<(String, String)> {
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    let all_frames = demo_file.sign_on_frames.iter().chain(demo_file.frames.iter());
+    for frame in all_frames {
+        if let Command::Packet(pd) | Command::SignOn(pd) = &frame.command {
+            for nmsg_ret in &pd.network_messages {
+                let name = match &nmsg_ret.message {
+                    Some(nmsg) => nmsg.to_str().to_owned(),
+                    None => "????".to_owned()
+                };
+                *counts.entry(name).or_insert(0) += 1;
+            }
+        }
+    }
+    counts.into_iter().map(|(name, count)| (name, count.to_string())).collect()
+}
+
+fn game_event_counts(events: &[FullGameEvent]) -> Vec<(String, String)> {
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for ev in events {
+        *counts.entry(ev.event_name.clone()).or_insert(0) += 1;
+    }
+    counts.into_iter().map(|(name, count)| (name, count.to_string())).collect()
+}
+
+fn game_event_keys_string(event: &FullGameEvent) -> String {
+    let keys: Vec<String> = event.event_keys.iter()
+        .map(|k| format!("{}={}", k.key_name, game_event_key_value_string(k)))
+        .collect();
+    keys.join(", ")
+}
+
+// aligns the two event streams ignoring ticks, which differ between recordings
+// of the same match (e.g. gotv delay).
+fn diff_game_event_streams(left: &[FullGameEvent], right: &[FullGameEvent]) -> (Vec<DiffRow>, Option<String>) {
+    let left_sigs: Vec<String> = left.iter()
+        .map(|ev| format!("{} {}", ev.event_name, game_event_keys_string(ev)))
+        .collect();
+    let right_sigs: Vec<String> = right.iter()
+        .map(|ev| format!("{} {}", ev.event_name, game_event_keys_string(ev)))
+        .collect();
+
+    let event_string = |ev: &FullGameEvent| {
+        format!("[{}] {}", ev.event_tick, game_event_keys_string(ev))
+    };
+
+    let (ops, note) = match diff_sequences(&left_sigs, &right_sigs, MAX_STREAM_EDIT_DISTANCE) {
+        Some(ops) => (ops, None),
+        None => {
+            let mut ops: Vec<SeqOp> = (0..left.len()).map(SeqOp::Removed).collect();
+            ops.extend((0..right.len()).map(SeqOp::Added));
+            (ops, Some(format!(
+                "The event streams differ in more than {} places and could not be aligned.",
+                MAX_STREAM_EDIT_DISTANCE
+            )))
+        }
+    };
+
+    let rows = ops.into_iter().map(|op| match op {
+        SeqOp::Same(l, r) => DiffRow {
+            name: left[l].event_name.clone(),
+            left: Some(event_string(&left[l])),
+            right: Some(event_string(&right[r])),
+            kind: DiffKind::Same,
+        },
+        SeqOp::Removed(l) => DiffRow {
+            name: left[l].event_name.clone(),
+            left: Some(event_string(&left[l])),
+            right: None,
+            kind: DiffKind::Removed,
+        },
+        SeqOp::Added(r) => DiffRow {
+            name: right[r].event_name.clone(),
+            left: None,
+            right: Some(event_string(&right[r])),
+            kind: DiffKind::Added,
+        },
+    }).collect();
+
+    (rows, note)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SeqOp {
+    Same(usize, usize),
+    Removed(usize),
+    Added(usize),
+}
+
+// myers' diff, returns None when the edit distance exceeds max_distance.
+fn diff_sequences<T: PartialEq>(a: &[T], b: &[T], max_distance: usize) -> Option<Vec<SeqOp>> {
+    let n = a.len() as isize;
+    let m = b.len() as isize;
+    let max_d = (n + m).min(max_distance as isize);
+    let offset = max_d + 1;
+
+    // v[k + offset] = furthest x reached on diagonal k
+    let mut v = vec![0isize; (2 * max_d + 3) as usize];
+    // trace[d] = v before round d, restricted to diagonals -d-1..=d+1
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    let mut found = false;
+    'rounds: for d in 0..=max_d {
+        trace.push(v[(offset - d - 1) as usize..=(offset + d + 1) as usize].to_vec());
+
+        let mut k = -d;
+        while k <= d {
+            let mut x = if k == -d || (k != d && v[(offset + k - 1) as usize] < v[(offset + k + 1) as usize]) {
+                v[(offset + k + 1) as usize]
+            } else {
+                v[(offset + k - 1) as usize] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[(offset + k) as usize] = x;
+
+            if x >= n && y >= m {
+                found = true;
+                break 'rounds
+            }
+            k += 2;
+        }
+    }
+
+    if !found {
+        return None
+    }
+
+    // walk back through the trace
+    let mut ops = Vec::new();
+    let mut x = n;
+    let mut y = m;
+    for d in (0..trace.len() as isize).rev() {
+        let round_v = &trace[d as usize];
+        let get = |k: isize| round_v[(k + d + 1) as usize];
+
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && get(k - 1) < get(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = get(prev_k);
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            ops.push(SeqOp::Same(x as usize, y as usize));
+        }
+        if d > 0 {
+            if x == prev_x {
+                ops.push(SeqOp::Added(prev_y as usize));
+            } else {
+                ops.push(SeqOp::Removed(prev_x as usize));
+            }
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+
+    ops.reverse();
+    Some(ops)
+}
\ No newline at end of file
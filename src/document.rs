@@ -0,0 +1,809 @@
+use std::{
+    io::{ self, Write },
+    path::PathBuf,
+    thread,
+};
+
+use source_demo_tool::{
+    demo_file::{
+        DemoFile,
+        frame::{ CommandIndex, DataTablesIndex, Frame, FrameIndex },
+        header::DemoHeader,
+        packet::{ Header, PacketIndex, netmessage::NetMessage },
+    },
+    engine_types::Vector3F64,
+    protobuf_message::ProtobufMessage,
+};
+
+mod bit_reader;
+pub mod entities;
+pub mod player_info;
+pub mod protobuf;
+pub mod string_table;
+
+pub const HEADER_LENGTH: usize = 1072;
+const HEADER_MAGIC: &[u8] = b"HL2DEMO\0";
+const HEADER_STRING_LENGTH: usize = 260;
+// longest string that fits one of the header's string fields with its nul
+pub const MAX_HEADER_STRING_LENGTH: usize = HEADER_STRING_LENGTH - 1;
+const PACKET_HEADER_LENGTH: usize = 8 + 6 * 24 + 12;
+
+pub mod command_id {
+    pub const       SIGN_ON: u8 = 1;
+    pub const        PACKET: u8 = 2;
+    pub const     SYNC_TICK: u8 = 3;
+    pub const   CONSOLE_CMD: u8 = 4;
+    pub const      USER_CMD: u8 = 5;
+    pub const   DATA_TABLES: u8 = 6;
+    pub const          STOP: u8 = 7;
+    pub const STRING_TABLES: u8 = 8;
+    pub const   CUSTOM_DATA: u8 = 9;
+}
+
+pub mod net_message_id {
+    pub const         SET_CON_VAR: u64 = 6;
+    pub const         SERVER_INFO: u64 = 8;
+    pub const CREATE_STRING_TABLE: u64 = 12;
+    pub const UPDATE_STRING_TABLE: u64 = 13;
+    pub const        USER_MESSAGE: u64 = 23;
+    pub const          GAME_EVENT: u64 = 25;
+    pub const     PACKET_ENTITIES: u64 = 26;
+    pub const     GAME_EVENT_LIST: u64 = 30;
+}
+
+// a demo as it is laid out on disk. unlike DemoFile this keeps every frame in
+// file order (including the commands DemoFile can't parse) and the raw bytes of
+// every netmessage, so writing it back out gives the exact same file.
+#[derive(Debug, Clone)]
+pub struct DemoDocument {
+    pub header: DemoHeader,
+    // the header as read, fields that weren't changed are written back from here
+    header_raw: Vec<u8>,
+    pub sign_on_frames: Vec<DocumentFrame>,
+    pub frames: Vec<DocumentFrame>,
+    // bytes after the last frame that could be read, only truncated or corrupt demos have these
+    pub trailing_data: Vec<u8>,
+    pub index_error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DocumentFrame {
+    pub tick: i32,
+    pub player_slot: u8,
+    pub body: FrameBody,
+}
+
+#[derive(Debug, Clone)]
+pub enum FrameBody {
+    SignOn(DocumentPacket),
+    Packet(DocumentPacket),
+    SyncTick,
+    ConsoleCmd(Vec<u8>),
+    UserCmd { outgoing_sequence: i32, data: Vec<u8> },
+    DataTables(Vec<u8>),
+    Stop,
+    StringTables(Vec<u8>),
+    CustomData { callback_index: i32, data: Vec<u8> },
+}
+
+#[derive(Debug, Clone)]
+pub struct DocumentPacket {
+    // data_length is recomputed from the messages when written
+    pub header: Header,
+    pub messages: Vec<RawMessage>,
+    // bytes at the end of the packet that don't form a complete message
+    pub trailing_data: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RawMessage {
+    pub id: u64,
+    pub data: Vec<u8>,
+}
+
+// where a netmessage is in a document, frame indices are document (file order) indices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageLocation {
+    pub sign_on: bool,
+    pub frame_index: usize,
+    pub message_index: usize,
+}
+
+impl MessageLocation {
+    pub fn describe(&self) -> String {
+        format!(
+            "{} {}, Message {}",
+            if self.sign_on { "Sign On Frame" } else { "Frame" },
+            self.frame_index + 1,
+            self.message_index + 1
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RoundTripReport {
+    pub original_length: usize,
+    pub written_length: usize,
+    pub messages_reencoded: usize,
+    // messages that couldn't be decoded and were written back as read
+    pub messages_copied: usize,
+    pub first_difference: Option<usize>,
+}
+
+struct ByteReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    // file offset of data, for error messages
+    base: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(data: &'a [u8], base: usize) -> Self {
+        Self { data, pos: 0, base }
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], String> {
+        if length > self.remaining() {
+            return Err(format!(
+                "unexpected end of data at {}, wanted {} bytes but only {} remain",
+                self.base + self.pos, length, self.remaining()
+            ))
+        }
+        let bytes = &self.data[self.pos..self.pos + length];
+        self.pos += length;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn length_prefixed(&mut self) -> Result<Vec<u8>, String> {
+        let length = self.i32()?;
+        if length < 0 {
+            return Err(format!("unexpected negative data length {} at {}", length, self.base + self.pos - 4))
+        }
+        Ok(self.take(length as usize)?.to_vec())
+    }
+}
+
+impl DemoDocument {
+    pub fn from_bytes(data: &[u8]) -> Result<Self, String> {
+        if data.len() < HEADER_LENGTH {
+            return Err("couldn't parse header: file is too short".into())
+        }
+        let header_raw = data[..HEADER_LENGTH].to_vec();
+        let header = match DemoHeader::from_readable(header_raw.as_slice()) {
+            Ok(h) => h,
+            Err(e) => return Err(format!("couldn't parse header: {e}"))
+        };
+
+        let sign_on_end = HEADER_LENGTH + header.sign_on_length as usize;
+        if data.len() < sign_on_end {
+            return Err("couldn't read sign_on_data".into())
+        }
+
+        let mut sign_on_frames = Vec::new();
+        let mut sod_reader = ByteReader::new(&data[HEADER_LENGTH..sign_on_end], HEADER_LENGTH);
+        while sod_reader.remaining() > 0 {
+            match read_frame(&mut sod_reader) {
+                Ok(frame) => sign_on_frames.push(frame),
+                Err(e) => return Err(format!("invalid or corrupt sign_on_data: {}", e))
+            }
+        }
+
+        let mut frames = Vec::new();
+        let mut index_error = None;
+        let mut reader = ByteReader::new(&data[sign_on_end..], sign_on_end);
+        while reader.remaining() > 0 {
+            let frame_start = reader.pos;
+            match read_frame(&mut reader) {
+                Ok(frame) => frames.push(frame),
+                Err(e) => {
+                    // keep what couldn't be read so the demo is still written back whole
+                    reader.pos = frame_start;
+                    index_error = Some(format!("error occured parsing frame {}: {}", frames.len(), e));
+                    break
+                }
+            }
+        }
+        let trailing_data = reader.take(reader.remaining())?.to_vec();
+
+        Ok(Self {
+            header,
+            header_raw,
+            sign_on_frames,
+            frames,
+            trailing_data,
+            index_error,
+        })
+    }
+
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        self.write(&mut out)?;
+        Ok(out)
+    }
+
+    // writes the demo, the header's sign_on_length and every packet's data_length
+    // are recomputed, everything else is written as stored.
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut sign_on_data = Vec::new();
+        for frame in &self.sign_on_frames {
+            frame.write(&mut sign_on_data);
+        }
+        let sign_on_length = match i32::try_from(sign_on_data.len()) {
+            Ok(n) => n,
+            Err(_) => return Err(io::Error::new(io::ErrorKind::InvalidData, "sign on data is too long"))
+        };
+
+        writer.write_all(&self.header_bytes(sign_on_length)?)?;
+        writer.write_all(&sign_on_data)?;
+
+        let mut buffer = Vec::new();
+        for frame in &self.frames {
+            buffer.clear();
+            frame.write(&mut buffer);
+            writer.write_all(&buffer)?;
+        }
+        writer.write_all(&self.trailing_data)
+    }
+
+    fn header_bytes(&self, sign_on_length: i32) -> io::Result<Vec<u8>> {
+        let header = &self.header;
+        let raw = &self.header_raw;
+        let mut out = Vec::with_capacity(HEADER_LENGTH);
+        out.extend_from_slice(HEADER_MAGIC);
+        out.extend_from_slice(&header.demo_protocol.to_le_bytes());
+        out.extend_from_slice(&header.network_protocol.to_le_bytes());
+
+        let strings = [
+            ("server name", &header.server_name),
+            ("client name", &header.client_name),
+            ("map name", &header.map_name),
+            ("game directory", &header.game_directory),
+        ];
+        for (i, (name, value)) in strings.into_iter().enumerate() {
+            let start = 16 + i * HEADER_STRING_LENGTH;
+            let raw_field = &raw[start..start + HEADER_STRING_LENGTH];
+            out.extend_from_slice(&fixed_width_string(name, raw_field, value)?);
+        }
+
+        out.extend_from_slice(&header.playback_time.to_le_bytes());
+        out.extend_from_slice(&header.ticks.to_le_bytes());
+        out.extend_from_slice(&header.frames.to_le_bytes());
+        out.extend_from_slice(&sign_on_length.to_le_bytes());
+        Ok(out)
+    }
+
+    // rewrites the header's ticks, frames and playback time to match the frames.
+    // tick_interval is the seconds per tick, when it's 0 the header's own is used.
+    pub fn update_header_counts(&mut self, tick_interval: f32) {
+        let first_tick = self.frames.iter().map(|f| f.tick).min().unwrap_or(0);
+        let last_tick = self.frames.iter().map(|f| f.tick).max().unwrap_or(0);
+
+        let header = &mut self.header;
+        let seconds_per_tick = if tick_interval > 0.0 {
+            tick_interval
+        } else if header.ticks > 0 {
+            header.playback_time / header.ticks as f32
+        } else {
+            0.0
+        };
+        header.ticks = last_tick - first_tick;
+        header.frames = self.frames.len() as i32;
+        header.playback_time = header.ticks as f32 * seconds_per_tick;
+    }
+
+    // seconds per tick from the first ServerInfo, 0 when there is none.
+    pub fn tick_interval(&self) -> f32 {
+        let location = self.message_locations(net_message_id::SERVER_INFO).first().copied();
+        match location.and_then(|l| self.message(l)).map(|m| protobuf::parse_net_message(m.id, &m.data)) {
+            Some(Ok(NetMessage::ServerInfo(si))) => si.tick_interval.unwrap_or(0.0),
+            _ => 0.0
+        }
+    }
+
+    // the document index of every frame the DemoFile built by to_demo_file has, in its order.
+    pub fn demo_file_frame_indices(&self, sign_on: bool) -> Vec<usize> {
+        let frames = if sign_on { &self.sign_on_frames } else { &self.frames };
+        frames
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| f.is_in_demo_file())
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    // converts to a DemoFile for the viewer, frames keep their file order. frames with
+    // commands DemoFile doesn't know (ConsoleCmd, UserCmd, StringTables, CustomData)
+    // are left out.
+    pub fn to_demo_file(&self, path: PathBuf) -> Result<DemoFile, String> {
+        let sign_on_frames = parse_frames(&self.sign_on_frames)?;
+        let frames = parse_frames(&self.frames)?;
+        Ok(DemoFile {
+            path,
+            header: self.header.clone(),
+            frames,
+            sign_on_frames,
+            last_index_error: self.index_error.clone(),
+        })
+    }
+
+    // locations of all netmessages with the given id, sign on frames first.
+    pub fn message_locations(&self, message_id: u64) -> Vec<MessageLocation> {
+        let mut locations = Vec::new();
+        for (sign_on, frames) in [(true, &self.sign_on_frames), (false, &self.frames)] {
+            for (frame_index, frame) in frames.iter().enumerate() {
+                if let FrameBody::SignOn(packet) | FrameBody::Packet(packet) = &frame.body {
+                    for (message_index, message) in packet.messages.iter().enumerate() {
+                        if message.id == message_id {
+                            locations.push(MessageLocation { sign_on, frame_index, message_index });
+                        }
+                    }
+                }
+            }
+        }
+        locations
+    }
+
+    pub fn message(&self, location: MessageLocation) -> Option<&RawMessage> {
+        let frames = if location.sign_on { &self.sign_on_frames } else { &self.frames };
+        match &frames.get(location.frame_index)?.body {
+            FrameBody::SignOn(packet) | FrameBody::Packet(packet) => packet.messages.get(location.message_index),
+            _ => None
+        }
+    }
+
+    pub fn message_mut(&mut self, location: MessageLocation) -> Option<&mut RawMessage> {
+        let frames = if location.sign_on { &mut self.sign_on_frames } else { &mut self.frames };
+        match &mut frames.get_mut(location.frame_index)?.body {
+            FrameBody::SignOn(packet) | FrameBody::Packet(packet) => packet.messages.get_mut(location.message_index),
+            _ => None
+        }
+    }
+
+    // decodes every netmessage down to its protobuf fields and encodes it again,
+    // returns how many messages were re-encoded and how many couldn't be decoded.
+    pub fn reencode_messages(&mut self) -> (usize, usize) {
+        let mut reencoded = 0;
+        let mut copied = 0;
+        for frame in self.sign_on_frames.iter_mut().chain(self.frames.iter_mut()) {
+            if let FrameBody::Packet(packet) | FrameBody::SignOn(packet) = &mut frame.body {
+                for message in &mut packet.messages {
+                    match message.fields() {
+                        Ok(fields) => {
+                            message.set_fields(&fields);
+                            reencoded += 1;
+                        },
+                        Err(_) => copied += 1
+                    }
+                }
+            }
+        }
+        (reencoded, copied)
+    }
+
+    // reads a demo, re-encodes all of its messages and writes it back out, comparing the result.
+    pub fn round_trip(data: &[u8]) -> Result<RoundTripReport, String> {
+        let mut document = Self::from_bytes(data)?;
+        let (messages_reencoded, messages_copied) = document.reencode_messages();
+        let written = match document.to_bytes() {
+            Ok(w) => w,
+            Err(e) => return Err(format!("couldn't write demo: {}", e))
+        };
+
+        let first_difference = match data.iter().zip(written.iter()).position(|(a, b)| a != b) {
+            Some(i) => Some(i),
+            None if data.len() != written.len() => Some(data.len().min(written.len())),
+            None => None
+        };
+
+        Ok(RoundTripReport {
+            original_length: data.len(),
+            written_length: written.len(),
+            messages_reencoded,
+            messages_copied,
+            first_difference,
+        })
+    }
+}
+
+impl DocumentFrame {
+    pub fn command(&self) -> u8 {
+        match &self.body {
+            FrameBody::SignOn(_) => command_id::SIGN_ON,
+            FrameBody::Packet(_) => command_id::PACKET,
+            FrameBody::SyncTick => command_id::SYNC_TICK,
+            FrameBody::ConsoleCmd(_) => command_id::CONSOLE_CMD,
+            FrameBody::UserCmd { .. } => command_id::USER_CMD,
+            FrameBody::DataTables(_) => command_id::DATA_TABLES,
+            FrameBody::Stop => command_id::STOP,
+            FrameBody::StringTables(_) => command_id::STRING_TABLES,
+            FrameBody::CustomData { .. } => command_id::CUSTOM_DATA,
+        }
+    }
+
+    pub fn command_name(&self) -> &'static str {
+        match &self.body {
+            FrameBody::SignOn(_) => "SignOn",
+            FrameBody::Packet(_) => "Packet",
+            FrameBody::SyncTick => "SyncTick",
+            FrameBody::ConsoleCmd(_) => "ConsoleCmd",
+            FrameBody::UserCmd { .. } => "UserCmd",
+            FrameBody::DataTables(_) => "DataTables",
+            FrameBody::Stop => "Stop",
+            FrameBody::StringTables(_) => "StringTables",
+            FrameBody::CustomData { .. } => "CustomData",
+        }
+    }
+
+    // the command of a ConsoleCmd frame, without its nul.
+    pub fn console_command(&self) -> Option<String> {
+        match &self.body {
+            FrameBody::ConsoleCmd(data) => {
+                let end = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+                Some(String::from_utf8_lossy(&data[..end]).to_string())
+            },
+            _ => None
+        }
+    }
+
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.push(self.command());
+        out.extend_from_slice(&self.tick.to_le_bytes());
+        out.push(self.player_slot);
+
+        match &self.body {
+            FrameBody::SignOn(packet) | FrameBody::Packet(packet) => packet.write(out),
+            FrameBody::SyncTick | FrameBody::Stop => {},
+            FrameBody::ConsoleCmd(data) | FrameBody::DataTables(data) | FrameBody::StringTables(data) => {
+                write_length_prefixed(out, data);
+            },
+            FrameBody::UserCmd { outgoing_sequence, data } => {
+                out.extend_from_slice(&outgoing_sequence.to_le_bytes());
+                write_length_prefixed(out, data);
+            },
+            FrameBody::CustomData { callback_index, data } => {
+                out.extend_from_slice(&callback_index.to_le_bytes());
+                write_length_prefixed(out, data);
+            },
+        }
+    }
+
+    // whether DemoFile has this frame, see DemoDocument::to_demo_file.
+    pub fn is_in_demo_file(&self) -> bool {
+        matches!(
+            self.body,
+            FrameBody::SignOn(_) | FrameBody::Packet(_) | FrameBody::SyncTick | FrameBody::DataTables(_) | FrameBody::Stop
+        )
+    }
+
+    fn to_frame_index(&self) -> Option<FrameIndex> {
+        let command_index = match &self.body {
+            FrameBody::SignOn(packet) => CommandIndex::SignOn(packet.to_packet_index()),
+            FrameBody::Packet(packet) => CommandIndex::Packet(packet.to_packet_index()),
+            FrameBody::SyncTick => CommandIndex::SyncTick,
+            FrameBody::DataTables(data) => CommandIndex::DataTables(DataTablesIndex { data: data.clone() }),
+            FrameBody::Stop => CommandIndex::Stop,
+            _ => return None
+        };
+        Some(FrameIndex {
+            command_index,
+            tick: self.tick,
+            player_slot: self.player_slot,
+        })
+    }
+}
+
+impl DocumentPacket {
+    // the packet's message data, as it follows the packet header.
+    pub fn data(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        for message in &self.messages {
+            message.write(&mut data);
+        }
+        data.extend_from_slice(&self.trailing_data);
+        data
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        let data = self.data();
+        let info = &self.header.command_info;
+        out.extend_from_slice(&info.flags.to_le_bytes());
+        for v in [
+            &info.view_origin,
+            &info.view_angles,
+            &info.local_view_angles,
+            &info.inter_view_origin,
+            &info.inter_view_angles,
+            &info.inter_local_view_angles,
+        ] {
+            write_vector(out, v);
+        }
+        out.extend_from_slice(&self.header.in_seq.to_le_bytes());
+        out.extend_from_slice(&self.header.out_seq.to_le_bytes());
+        out.extend_from_slice(&(data.len() as i32).to_le_bytes());
+        out.extend_from_slice(&data);
+    }
+
+    fn to_packet_index(&self) -> PacketIndex {
+        let data = self.data();
+        let mut header = self.header.clone();
+        header.data_length = data.len() as i32;
+        PacketIndex { header, data }
+    }
+}
+
+impl RawMessage {
+    pub fn write(&self, out: &mut Vec<u8>) {
+        protobuf::write_varint(out, self.id);
+        protobuf::write_varint(out, self.data.len() as u64);
+        out.extend_from_slice(&self.data);
+    }
+
+    pub fn fields(&self) -> Result<Vec<ProtobufMessage>, String> {
+        protobuf::decode_fields(&self.data)
+    }
+
+    pub fn set_fields(&mut self, fields: &[ProtobufMessage]) {
+        self.data = protobuf::encode_fields(fields);
+    }
+}
+
+fn read_frame(reader: &mut ByteReader) -> Result<DocumentFrame, String> {
+    let command = reader.u8()?;
+    let tick = reader.i32()?;
+    let player_slot = reader.u8()?;
+
+    let body = match command {
+        command_id::SIGN_ON => FrameBody::SignOn(read_packet(reader)?),
+        command_id::PACKET => FrameBody::Packet(read_packet(reader)?),
+        command_id::SYNC_TICK => FrameBody::SyncTick,
+        command_id::CONSOLE_CMD => FrameBody::ConsoleCmd(reader.length_prefixed()?),
+        command_id::USER_CMD => {
+            let outgoing_sequence = reader.i32()?;
+            FrameBody::UserCmd { outgoing_sequence, data: reader.length_prefixed()? }
+        },
+        command_id::DATA_TABLES => FrameBody::DataTables(reader.length_prefixed()?),
+        command_id::STOP => FrameBody::Stop,
+        command_id::STRING_TABLES => FrameBody::StringTables(reader.length_prefixed()?),
+        command_id::CUSTOM_DATA => {
+            let callback_index = reader.i32()?;
+            FrameBody::CustomData { callback_index, data: reader.length_prefixed()? }
+        },
+        n => return Err(format!("unsupported command number: {}", n))
+    };
+
+    Ok(DocumentFrame { tick, player_slot, body })
+}
+
+fn read_packet(reader: &mut ByteReader) -> Result<DocumentPacket, String> {
+    let header = Header::from_readable(reader.take(PACKET_HEADER_LENGTH)?)?;
+    if header.data_length < 0 {
+        return Err(format!("unexpected negative packet data length {}", header.data_length))
+    }
+    let data = reader.take(header.data_length as usize)?;
+
+    let mut messages = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let message_start = pos;
+        let id = protobuf::read_varint(data, &mut pos);
+        let length = protobuf::read_varint(data, &mut pos);
+        match (id, length) {
+            (Some(id), Some(length)) if (length as usize) <= data.len() - pos => {
+                let end = pos + length as usize;
+                messages.push(RawMessage { id, data: data[pos..end].to_vec() });
+                pos = end;
+            },
+            _ => {
+                pos = message_start;
+                break
+            }
+        }
+    }
+
+    Ok(DocumentPacket {
+        header,
+        messages,
+        trailing_data: data[pos..].to_vec(),
+    })
+}
+
+fn parse_frames(frames: &[DocumentFrame]) -> Result<Vec<Frame>, String> {
+    // packet data parsing is the slow part of opening a demo, split it over all cores
+    let thread_count: usize = thread::available_parallelism()
+        .map(|n| n.into())
+        .unwrap_or(1);
+    let chunk_size = (frames.len() / thread_count).max(1);
+
+    let results: Vec<Result<Vec<Frame>, &'static str>> = thread::scope(|s| {
+        let handles: Vec<_> = frames
+            .chunks(chunk_size)
+            .map(|chunk| s.spawn(move || {
+                chunk
+                    .iter()
+                    .filter_map(|f| f.to_frame_index())
+                    .map(Frame::from_frame_index)
+                    .collect()
+            }))
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    let mut parsed = Vec::with_capacity(frames.len());
+    for res in results {
+        parsed.append(&mut res?);
+    }
+    Ok(parsed)
+}
+
+fn write_vector(out: &mut Vec<u8>, v: &Vector3F64) {
+    out.extend_from_slice(&v.x.to_le_bytes());
+    out.extend_from_slice(&v.y.to_le_bytes());
+    out.extend_from_slice(&v.z.to_le_bytes());
+}
+
+fn write_length_prefixed(out: &mut Vec<u8>, data: &[u8]) {
+    out.extend_from_slice(&(data.len() as i32).to_le_bytes());
+    out.extend_from_slice(data);
+}
+
+// keeps the field as read when the value is unchanged, so bytes after the nul survive.
+fn fixed_width_string(name: &str, raw_field: &[u8], value: &str) -> io::Result<Vec<u8>> {
+    let raw_end = raw_field.iter().position(|&c| c == 0).unwrap_or(raw_field.len());
+    if &raw_field[..raw_end] == value.as_bytes() {
+        return Ok(raw_field.to_vec())
+    }
+
+    if value.len() > MAX_HEADER_STRING_LENGTH {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is longer than {} bytes", name, MAX_HEADER_STRING_LENGTH)
+        ))
+    }
+    let mut field = value.as_bytes().to_vec();
+    field.resize(HEADER_STRING_LENGTH, 0);
+    Ok(field)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use protobuf::write_varint;
+
+    fn message(out: &mut Vec<u8>, id: u64, payload: &[u8]) {
+        write_varint(out, id);
+        write_varint(out, payload.len() as u64);
+        out.extend_from_slice(payload);
+    }
+
+    fn frame_head(out: &mut Vec<u8>, command: u8, tick: i32) {
+        out.push(command);
+        out.extend_from_slice(&tick.to_le_bytes());
+        out.push(0);
+    }
+
+    fn length_prefixed(out: &mut Vec<u8>, data: &[u8]) {
+        out.extend_from_slice(&(data.len() as i32).to_le_bytes());
+        out.extend_from_slice(data);
+    }
+
+    fn packet(out: &mut Vec<u8>, command: u8, tick: i32, data: &[u8]) {
+        frame_head(out, command, tick);
+        out.extend_from_slice(&1i64.to_le_bytes());
+        for i in 0..18 {
+            out.extend_from_slice(&(i as f64 * 1.5).to_le_bytes());
+        }
+        out.extend_from_slice(&5i32.to_le_bytes());
+        out.extend_from_slice(&6i32.to_le_bytes());
+        length_prefixed(out, data);
+    }
+
+    // a demo with every frame command, header strings with bytes after their nul
+    // and a packet with bytes that don't form a message. the map is de_dust2 and
+    // there are no players.
+    pub fn demo_bytes() -> Vec<u8> {
+        let mut sign_on = Vec::new();
+        let mut data = Vec::new();
+        // server info: protocol 13, map_name de_dust2
+        message(&mut data, net_message_id::SERVER_INFO, &[0x08, 0x0d, 0x82, 0x01, 0x08, b'd', b'e', b'_', b'd', b'u', b's', b't', b'2']);
+        message(&mut data, 4, &[0x08, 0x64]);
+        packet(&mut sign_on, command_id::SIGN_ON, 0, &data);
+        frame_head(&mut sign_on, command_id::DATA_TABLES, 0);
+        length_prefixed(&mut sign_on, &[0x09, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        frame_head(&mut sign_on, command_id::STRING_TABLES, 0);
+        // one table, userinfo, with no entries and no client side entries
+        length_prefixed(&mut sign_on, b"\x01userinfo\0\0\0\0");
+
+        let mut frames = Vec::new();
+        frame_head(&mut frames, command_id::SYNC_TICK, 0);
+        for tick in 1..5 {
+            let mut data = Vec::new();
+            message(&mut data, 4, &[0x08, tick as u8]);
+            message(&mut data, 5, b"\x0a\x06say hi");
+            data.push(0x80);
+            packet(&mut frames, command_id::PACKET, tick, &data);
+        }
+        frame_head(&mut frames, command_id::CONSOLE_CMD, 4);
+        length_prefixed(&mut frames, b"echo hi\0");
+        frame_head(&mut frames, command_id::USER_CMD, 4);
+        frames.extend_from_slice(&42i32.to_le_bytes());
+        length_prefixed(&mut frames, b"usercmd");
+        frame_head(&mut frames, command_id::CUSTOM_DATA, 4);
+        frames.extend_from_slice(&(-1i32).to_le_bytes());
+        length_prefixed(&mut frames, b"custom");
+        frame_head(&mut frames, command_id::STOP, 5);
+
+        let mut out = Vec::new();
+        out.extend_from_slice(HEADER_MAGIC);
+        out.extend_from_slice(&4i32.to_le_bytes());
+        out.extend_from_slice(&13753i32.to_le_bytes());
+        for name in [&b"server"[..], b"client", b"de_dust2", b"csgo"] {
+            let mut field = name.to_vec();
+            field.push(0);
+            field.extend_from_slice(b"garbage");
+            field.resize(HEADER_STRING_LENGTH, 0);
+            out.extend_from_slice(&field);
+        }
+        out.extend_from_slice(&0.078125f32.to_le_bytes());
+        out.extend_from_slice(&5i32.to_le_bytes());
+        out.extend_from_slice(&10i32.to_le_bytes());
+        out.extend_from_slice(&(sign_on.len() as i32).to_le_bytes());
+        assert_eq!(out.len(), HEADER_LENGTH);
+        out.extend_from_slice(&sign_on);
+        out.extend_from_slice(&frames);
+        out
+    }
+
+    #[test]
+    fn write_gives_the_same_bytes() {
+        let data = demo_bytes();
+        let document = DemoDocument::from_bytes(&data).unwrap();
+        assert_eq!(document.sign_on_frames.len(), 3);
+        assert_eq!(document.frames.len(), 9);
+        assert!(document.index_error.is_none());
+        assert!(document.trailing_data.is_empty());
+        match &document.frames[1].body {
+            FrameBody::Packet(packet) => {
+                assert_eq!(packet.messages.len(), 2);
+                assert_eq!(packet.trailing_data, vec![0x80]);
+            },
+            _ => panic!("frame 2 should be a packet")
+        }
+        assert_eq!(document.frames[5].console_command(), Some("echo hi".to_owned()));
+
+        assert_eq!(document.to_bytes().unwrap(), data);
+    }
+
+    #[test]
+    fn write_keeps_what_couldnt_be_read() {
+        let data = demo_bytes();
+        let truncated = &data[..data.len() - 10];
+        let document = DemoDocument::from_bytes(truncated).unwrap();
+        assert!(document.index_error.is_some());
+        assert!(!document.trailing_data.is_empty());
+        assert_eq!(document.to_bytes().unwrap(), truncated);
+    }
+
+    #[test]
+    fn write_recomputes_lengths() {
+        let mut document = DemoDocument::from_bytes(&demo_bytes()).unwrap();
+        let location = MessageLocation { sign_on: true, frame_index: 0, message_index: 1 };
+        document.message_mut(location).unwrap().data = vec![0x08, 0xe8, 0x07];
+
+        let written = DemoDocument::from_bytes(&document.to_bytes().unwrap()).unwrap();
+        assert_eq!(written.message(location).unwrap().data, vec![0x08, 0xe8, 0x07]);
+        assert_eq!(written.frames.len(), 9);
+        assert!(written.index_error.is_none());
+    }
+}
\ No newline at end of file
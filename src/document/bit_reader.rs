@@ -0,0 +1,223 @@
+const MAX_STRING_LENGTH: usize = 4096;
+
+// reads the engine's bit buffers, least significant bit first.
+pub struct BitReader<'a> {
+    data: &'a [u8],
+    pub pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    pub fn bits_left(&self) -> usize {
+        (self.data.len() * 8).saturating_sub(self.pos)
+    }
+
+    pub fn bit(&mut self) -> Result<bool, String> {
+        let byte = match self.data.get(self.pos / 8) {
+            Some(b) => *b,
+            None => return Err(format!("data ended at bit {}", self.pos))
+        };
+        let bit = (byte >> (self.pos % 8)) & 1;
+        self.pos += 1;
+        Ok(bit != 0)
+    }
+
+    // up to 32 bits
+    pub fn bits(&mut self, count: usize) -> Result<u32, String> {
+        if count > 32 {
+            return Err(format!("can't read {} bits at once", count))
+        }
+        if count > self.bits_left() {
+            return Err(format!("data ended reading {} bits at bit {}", count, self.pos))
+        }
+        let mut value: u64 = 0;
+        let mut read = 0;
+        while read < count {
+            let offset = self.pos % 8;
+            let take = (8 - offset).min(count - read);
+            let byte = (self.data[self.pos / 8] >> offset) as u64 & ((1 << take) - 1);
+            value |= byte << read;
+            read += take;
+            self.pos += take;
+        }
+        Ok(value as u32)
+    }
+
+    // up to 32 bits, sign extended
+    pub fn signed_bits(&mut self, count: usize) -> Result<i32, String> {
+        let value = self.bits(count)?;
+        if count == 0 || count == 32 {
+            return Ok(value as i32)
+        }
+        let shift = 32 - count;
+        Ok(((value << shift) as i32) >> shift)
+    }
+
+    pub fn f32(&mut self) -> Result<f32, String> {
+        Ok(f32::from_bits(self.bits(32)?))
+    }
+
+    // the engine's UBitVar, a 4 bit value with 0, 4, 8 or 28 more bits.
+    pub fn ubitvar(&mut self) -> Result<u32, String> {
+        let value = self.bits(6)?;
+        let extra_bits = match value & 0b11_0000 {
+            0b01_0000 => 4,
+            0b10_0000 => 8,
+            0b11_0000 => 28,
+            _ => return Ok(value)
+        };
+        Ok((value & 0b1111) | (self.bits(extra_bits)? << 4))
+    }
+
+    pub fn varint64(&mut self) -> Result<u64, String> {
+        let mut value: u64 = 0;
+        for i in 0..10 {
+            let byte = self.bits(8)?;
+            value |= ((byte & 0b0111_1111) as u64) << (7 * i);
+            if byte & 0b1000_0000 == 0 {
+                return Ok(value)
+            }
+        }
+        Err(format!("varint longer than 10 bytes at bit {}", self.pos))
+    }
+
+    pub fn varint32(&mut self) -> Result<u32, String> {
+        Ok(self.varint64()? as u32)
+    }
+
+    // zigzag encoded
+    pub fn signed_varint32(&mut self) -> Result<i32, String> {
+        let value = self.varint32()?;
+        Ok((value >> 1) as i32 ^ -((value & 1) as i32))
+    }
+
+    pub fn signed_varint64(&mut self) -> Result<i64, String> {
+        let value = self.varint64()?;
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+
+    pub fn bytes_from_bits(&mut self, bit_count: usize) -> Result<Vec<u8>, String> {
+        if bit_count > self.bits_left() {
+            return Err(format!("data ended reading {} bits at bit {}", bit_count, self.pos))
+        }
+        if self.pos.is_multiple_of(8) && bit_count.is_multiple_of(8) {
+            let start = self.pos / 8;
+            self.pos += bit_count;
+            return Ok(self.data[start..start + bit_count / 8].to_vec())
+        }
+        let mut out = vec![0; bit_count.div_ceil(8)];
+        for (i, byte) in out.iter_mut().enumerate() {
+            *byte = self.bits((bit_count - i * 8).min(8))? as u8;
+        }
+        Ok(out)
+    }
+
+    // a nul terminated string, the nul isn't returned.
+    pub fn string(&mut self) -> Result<Vec<u8>, String> {
+        let mut out = Vec::new();
+        loop {
+            let c = self.bits(8)? as u8;
+            if c == 0 {
+                return Ok(out)
+            }
+            if out.len() == MAX_STRING_LENGTH {
+                return Err(format!("string longer than {} bytes at bit {}", MAX_STRING_LENGTH, self.pos))
+            }
+            out.push(c);
+        }
+    }
+}
+
+// packs (value, bit count) pairs least significant bit first, the way BitReader reads them.
+#[cfg(test)]
+pub fn pack_bits(values: &[(u64, usize)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    for (value, count) in values {
+        for i in 0..*count {
+            if pos % 8 == 0 {
+                out.push(0);
+            }
+            if (value >> i) & 1 != 0 {
+                *out.last_mut().unwrap() |= 1 << (pos % 8);
+            }
+            pos += 1;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bits_across_bytes() {
+        let data = pack_bits(&[(0b101, 3), (0x1234, 13), (1, 1)]);
+        let mut reader = BitReader::new(&data);
+        assert_eq!(reader.bits(3), Ok(0b101));
+        assert_eq!(reader.bits(13), Ok(0x1234));
+        assert_eq!(reader.bit(), Ok(true));
+        assert_eq!(reader.pos, 17);
+        assert_eq!(reader.bits_left(), 7);
+        assert!(reader.bits(8).is_err());
+    }
+
+    #[test]
+    fn signed_bits() {
+        let data = pack_bits(&[(0b111, 3), (0b011, 3), (u32::MAX as u64, 32)]);
+        let mut reader = BitReader::new(&data);
+        assert_eq!(reader.signed_bits(3), Ok(-1));
+        assert_eq!(reader.signed_bits(3), Ok(3));
+        assert_eq!(reader.signed_bits(32), Ok(-1));
+    }
+
+    #[test]
+    fn ubitvar() {
+        // (encoded, bit count, value)
+        let cases: [(u64, usize, u32); 6] = [
+            (0b00_1111, 6, 15),
+            (0b00_0000, 6, 0),
+            (0b01_0000 | (0xf << 6) | 0x3, 10, 0xf3),
+            (0b10_0000 | (0xab << 6) | 0x5, 14, 0xab5),
+            (0b11_0000 | (0x0fff_ffff << 6) | 0xf, 34, u32::MAX),
+            (0b11_0000 | (0x123_4567 << 6) | 0x8, 34, 0x1234_5678),
+        ];
+        for (encoded, count, value) in cases {
+            let data = pack_bits(&[(1, 1), (encoded, count)]);
+            let mut reader = BitReader::new(&data);
+            reader.bit().unwrap();
+            assert_eq!(reader.ubitvar(), Ok(value), "ubitvar {:#x}", value);
+            assert_eq!(reader.pos, 1 + count);
+        }
+    }
+
+    #[test]
+    fn ubitvar_truncated() {
+        let data = pack_bits(&[(0b10_0000, 6)]);
+        assert!(BitReader::new(&data).ubitvar().is_err());
+    }
+
+    #[test]
+    fn varints() {
+        let data = pack_bits(&[(1, 1), (0xac, 8), (0x02, 8), (0x03, 8), (0x04, 8)]);
+        let mut reader = BitReader::new(&data);
+        reader.bit().unwrap();
+        assert_eq!(reader.varint32(), Ok(300));
+        // zigzag
+        assert_eq!(reader.signed_varint32(), Ok(-2));
+        assert_eq!(reader.signed_varint64(), Ok(2));
+    }
+
+    #[test]
+    fn string_and_bytes() {
+        let data = pack_bits(&[(1, 1), (b'h' as u64, 8), (b'i' as u64, 8), (0, 8), (0xbeef, 16)]);
+        let mut reader = BitReader::new(&data);
+        reader.bit().unwrap();
+        assert_eq!(reader.string(), Ok(b"hi".to_vec()));
+        assert_eq!(reader.bytes_from_bits(12), Ok(vec![0xef, 0x0e]));
+    }
+}
\ No newline at end of file
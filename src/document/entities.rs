@@ -0,0 +1,870 @@
+use std::{
+    collections::{ BTreeMap, HashMap },
+    fmt,
+};
+
+use source_demo_tool::demo_file::{
+    frame::{ DataTablesData, DataTablesIndex },
+    packet::netmessage::NetMessage,
+};
+
+use super::{
+    DemoDocument, FrameBody, MessageLocation, bit_reader::BitReader, net_message_id, protobuf,
+    string_table::{ self, StringTableLocation },
+};
+
+mod prop_flag {
+    pub const                UNSIGNED: u32 = 1 << 0;
+    pub const                   COORD: u32 = 1 << 1;
+    pub const                 NOSCALE: u32 = 1 << 2;
+    pub const                  NORMAL: u32 = 1 << 5;
+    pub const                 EXCLUDE: u32 = 1 << 6;
+    pub const             INSIDEARRAY: u32 = 1 << 8;
+    pub const             COLLAPSIBLE: u32 = 1 << 11;
+    pub const                COORD_MP: u32 = 1 << 12;
+    pub const   COORD_MP_LOWPRECISION: u32 = 1 << 13;
+    pub const       COORD_MP_INTEGRAL: u32 = 1 << 14;
+    pub const              CELL_COORD: u32 = 1 << 15;
+    pub const CELL_COORD_LOWPRECISION: u32 = 1 << 16;
+    pub const     CELL_COORD_INTEGRAL: u32 = 1 << 17;
+    pub const           CHANGES_OFTEN: u32 = 1 << 18;
+    pub const                  VARINT: u32 = 1 << 19;
+}
+
+mod prop_type {
+    pub const        INT: u64 = 0;
+    pub const      FLOAT: u64 = 1;
+    pub const     VECTOR: u64 = 2;
+    pub const  VECTOR_XY: u64 = 3;
+    pub const     STRING: u64 = 4;
+    pub const      ARRAY: u64 = 5;
+    pub const DATA_TABLE: u64 = 6;
+    pub const      INT64: u64 = 7;
+}
+
+mod coord {
+    pub const                 INTEGER_BITS: usize = 14;
+    pub const              INTEGER_BITS_MP: usize = 11;
+    pub const              FRACTIONAL_BITS: usize = 5;
+    pub const FRACTIONAL_BITS_LOWPRECISION: usize = 3;
+    pub const       NORMAL_FRACTIONAL_BITS: usize = 11;
+}
+
+const INSTANCE_BASELINE_TABLE: &str = "instancebaseline";
+const SERIAL_BITS: usize = 10;
+const STRING_LENGTH_BITS: usize = 9;
+const CHANGES_OFTEN_PRIORITY: u32 = 64;
+const END_OF_PROPS: u32 = 0xfff;
+// the decoder is kept after this many PacketEntities messages so state_at
+// only replays the messages since the closest one
+const KEYFRAME_INTERVAL: usize = 2048;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PropKind {
+    Int,
+    Float,
+    Vector,
+    VectorXY,
+    String,
+    Array,
+    Int64,
+}
+
+// a networked property of a server class, after its data tables were flattened.
+#[derive(Debug, Clone)]
+pub struct SendProp {
+    // props of nested data tables are prefixed with the table's prop name, e.g. m_Local.m_iHideHUD
+    pub name: String,
+    pub kind: PropKind,
+    pub flags: u32,
+    pub num_bits: usize,
+    pub low_value: f32,
+    pub high_value: f32,
+    pub num_elements: usize,
+    pub priority: u32,
+    // what each element of an array is decoded as
+    pub element: Option<Box<SendProp>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ServerClass {
+    pub class_id: u16,
+    pub network_name: String,
+    pub table_name: String,
+    // in the order the entity data refers to them by index
+    pub props: Vec<SendProp>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropValue {
+    Int(i64),
+    Float(f32),
+    Vector([f32; 3]),
+    VectorXY([f32; 2]),
+    String(String),
+    Array(Vec<PropValue>),
+}
+
+impl fmt::Display for PropValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PropValue::Int(v) => write!(f, "{}", v),
+            PropValue::Float(v) => write!(f, "{}", v),
+            PropValue::Vector([x, y, z]) => write!(f, "({}, {}, {})", x, y, z),
+            PropValue::VectorXY([x, y]) => write!(f, "({}, {})", x, y),
+            PropValue::String(v) => write!(f, "{}", v),
+            PropValue::Array(values) => {
+                let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+                write!(f, "[{}]", values.join(", "))
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Entity {
+    pub index: usize,
+    pub class_id: u16,
+    pub serial: u32,
+    // by the class's prop index, None for props neither the baseline nor an update has set
+    pub props: Vec<Option<PropValue>>,
+}
+
+// every entity the client knows of after the PacketEntities messages up to tick.
+#[derive(Debug, Clone, Default)]
+pub struct EntityState {
+    pub tick: i32,
+    pub entities: BTreeMap<usize, Entity>,
+}
+
+// what a PacketEntities message did to an entity. Create holds the entity with
+// its baseline applied, Update only the props that were sent.
+#[derive(Debug, Clone)]
+pub enum EntityChange {
+    Create(Entity),
+    Update { index: usize, props: Vec<(usize, PropValue)> },
+    Delete { index: usize },
+}
+
+// the value a prop has from tick on, set by the PacketEntities message at location.
+// value is None while the entity doesn't exist or the prop isn't set.
+#[derive(Debug, Clone)]
+pub struct PropChange {
+    pub tick: i32,
+    pub location: MessageLocation,
+    pub value: Option<PropValue>,
+}
+
+#[derive(Debug, Clone)]
+enum TimelineEvent {
+    Baseline { class_id: u16, data: Vec<u8> },
+    Packet { location: MessageLocation, tick: i32 },
+}
+
+// the entities of a demo at every tick. only the decoder's state every
+// KEYFRAME_INTERVAL messages is kept, the rest is decoded again from the
+// document when asked for.
+pub struct EntityTimeline {
+    // sorted by class id
+    pub classes: Vec<ServerClass>,
+    class_bits: usize,
+    // instance baseline changes and PacketEntities messages in file order
+    events: Vec<TimelineEvent>,
+    // the event index to continue from and the decoder up to it
+    keyframes: Vec<(usize, EntityDecoder)>,
+    // decoding stops at the first message that can't be decoded, events after it are dropped
+    pub decode_error: Option<String>,
+}
+
+impl EntityTimeline {
+    pub fn from_document(document: &DemoDocument) -> Result<Self, String> {
+        let mut classes = server_classes(document)?;
+        classes.sort_by_key(|c| c.class_id);
+        // Q_log2(classes) + 1
+        let class_bits = (usize::BITS - classes.len().leading_zeros()) as usize;
+
+        // both are already in file order
+        let baselines = string_table::string_table_updates(document)?
+            .into_iter()
+            .filter(|u| u.table_name == INSTANCE_BASELINE_TABLE)
+            .flat_map(|u| {
+                let order = match u.location {
+                    StringTableLocation::Message(l) => (!l.sign_on, l.frame_index, l.message_index),
+                    StringTableLocation::Frame { sign_on, frame_index } => (!sign_on, frame_index, 0),
+                };
+                u.entries.into_iter().filter_map(move |e| Some((
+                    order,
+                    TimelineEvent::Baseline { class_id: e.string.parse().ok()?, data: e.user_data? }
+                )))
+            });
+        let packets = document.message_locations(net_message_id::PACKET_ENTITIES)
+            .into_iter()
+            .map(|l| {
+                let frames = if l.sign_on { &document.sign_on_frames } else { &document.frames };
+                ((!l.sign_on, l.frame_index, l.message_index), TimelineEvent::Packet { location: l, tick: frames[l.frame_index].tick })
+            });
+        let mut events: Vec<((bool, usize, usize), TimelineEvent)> = baselines.chain(packets).collect();
+        events.sort_by_key(|(order, _)| *order);
+
+        let mut timeline = Self {
+            classes,
+            class_bits,
+            events: events.into_iter().map(|(_, e)| e).collect(),
+            keyframes: Vec::new(),
+            decode_error: None,
+        };
+
+        let mut decoder = EntityDecoder::default();
+        let mut packet_count = 0;
+        for i in 0..timeline.events.len() {
+            if let TimelineEvent::Packet { .. } = &timeline.events[i] {
+                if packet_count % KEYFRAME_INTERVAL == 0 {
+                    let mut keyframe = decoder.clone();
+                    keyframe.decoded_baselines.clear();
+                    timeline.keyframes.push((i, keyframe));
+                }
+                packet_count += 1;
+            }
+            if let Err(e) = timeline.replay_event(document, &mut decoder, i) {
+                timeline.decode_error = Some(e);
+                timeline.events.truncate(i);
+                timeline.keyframes.retain(|(k, _)| *k < i);
+                break
+            }
+        }
+        Ok(timeline)
+    }
+
+    pub fn class(&self, class_id: u16) -> Option<&ServerClass> {
+        class_by_id(&self.classes, class_id)
+    }
+
+    // the entities after every PacketEntities message up to and including tick.
+    pub fn state_at(&self, document: &DemoDocument, tick: i32) -> Result<EntityState, String> {
+        let keyframe = self.keyframes
+            .iter()
+            .rev()
+            .find(|(i, _)| matches!(&self.events[*i], TimelineEvent::Packet { tick: t, .. } if *t <= tick));
+        let (start, mut decoder) = match keyframe {
+            Some((i, decoder)) => (*i, decoder.clone()),
+            None => (0, EntityDecoder::default())
+        };
+
+        for i in start..self.events.len() {
+            if matches!(&self.events[i], TimelineEvent::Packet { tick: t, .. } if *t > tick) {
+                break
+            }
+            self.replay_event(document, &mut decoder, i)?;
+        }
+        Ok(decoder.state)
+    }
+
+    // every change to a prop of the entity at index over the whole demo. while another
+    // class's entity is at index the prop counts as unset.
+    pub fn prop_history(
+        &self,
+        document: &DemoDocument,
+        index: usize,
+        class_id: u16,
+        prop_index: usize
+    ) -> Result<Vec<PropChange>, String> {
+        let mut decoder = EntityDecoder::default();
+        let mut history: Vec<PropChange> = Vec::new();
+        for i in 0..self.events.len() {
+            let changes = self.replay_event(document, &mut decoder, i)?;
+            let (location, tick) = match &self.events[i] {
+                TimelineEvent::Packet { location, tick } => (*location, *tick),
+                TimelineEvent::Baseline { .. } => continue
+            };
+            let touched = changes.iter().any(|c| match c {
+                EntityChange::Create(entity) => entity.index == index,
+                EntityChange::Update { index: i, props } => *i == index && props.iter().any(|(p, _)| *p == prop_index),
+                EntityChange::Delete { index: i } => *i == index,
+            });
+            if !touched {
+                continue
+            }
+
+            let value = decoder.state.entities
+                .get(&index)
+                .filter(|e| e.class_id == class_id)
+                .and_then(|e| e.props.get(prop_index).cloned().flatten());
+            let previous = history.last().and_then(|c| c.value.as_ref());
+            if previous != value.as_ref() {
+                history.push(PropChange { tick, location, value });
+            }
+        }
+        Ok(history)
+    }
+
+    fn replay_event(&self, document: &DemoDocument, decoder: &mut EntityDecoder, event_index: usize) -> Result<Vec<EntityChange>, String> {
+        match &self.events[event_index] {
+            TimelineEvent::Baseline { class_id, data } => {
+                decoder.set_baseline(*class_id, data.clone());
+                Ok(Vec::new())
+            },
+            TimelineEvent::Packet { location, tick } => {
+                let message = match document.message(*location) {
+                    Some(m) => m,
+                    None => return Err(format!("{}: the message is gone, the document changed", location.describe()))
+                };
+                decoder.read_packet_entities(&self.classes, self.class_bits, &message.data, *tick)
+                    .map_err(|e| format!("{}: {}", location.describe(), e))
+            },
+        }
+    }
+}
+
+// replays PacketEntities messages, state is what the client has after each one.
+#[derive(Debug, Clone, Default)]
+struct EntityDecoder {
+    state: EntityState,
+    // raw instance baselines by class id, from the instancebaseline string table
+    baselines: BTreeMap<u16, Vec<u8>>,
+    decoded_baselines: HashMap<u16, Vec<Option<PropValue>>>,
+}
+
+impl EntityDecoder {
+    fn set_baseline(&mut self, class_id: u16, data: Vec<u8>) {
+        self.decoded_baselines.remove(&class_id);
+        self.baselines.insert(class_id, data);
+    }
+
+    fn baseline(&mut self, class: &ServerClass) -> Result<&Vec<Option<PropValue>>, String> {
+        if !self.decoded_baselines.contains_key(&class.class_id) {
+            let mut props = vec![None; class.props.len()];
+            if let Some(data) = self.baselines.get(&class.class_id) {
+                let mut reader = BitReader::new(data);
+                for (i, value) in read_props(&mut reader, class).map_err(|e| format!("baseline of {}: {}", class.network_name, e))? {
+                    props[i] = Some(value);
+                }
+            }
+            self.decoded_baselines.insert(class.class_id, props);
+        }
+        Ok(&self.decoded_baselines[&class.class_id])
+    }
+
+    fn read_packet_entities(
+        &mut self,
+        classes: &[ServerClass],
+        class_bits: usize,
+        data: &[u8],
+        tick: i32
+    ) -> Result<Vec<EntityChange>, String> {
+        let packet_entities = match protobuf::parse_net_message(net_message_id::PACKET_ENTITIES, data)? {
+            NetMessage::PacketEntity(pe) => pe,
+            _ => return Err("not a PacketEntities message".into())
+        };
+        let mut changes = Vec::new();
+        // a full update replaces every entity
+        if packet_entities.is_delta.unwrap_or(0) == 0 {
+            changes.extend(self.state.entities.keys().map(|&index| EntityChange::Delete { index }));
+            self.state.entities.clear();
+        }
+
+        let entity_data = packet_entities.entity_data.unwrap_or_default();
+        let mut reader = BitReader::new(&entity_data);
+        let mut index: i64 = -1;
+        for _ in 0..packet_entities.updated_entries.unwrap_or(0) {
+            index += 1 + reader.ubitvar()? as i64;
+            let index = index as usize;
+
+            if reader.bit()? {
+                // left the pvs, the second bit deletes it
+                if reader.bit()? && self.state.entities.remove(&index).is_some() {
+                    changes.push(EntityChange::Delete { index });
+                }
+            } else if reader.bit()? {
+                // entered the pvs, starts from the class's baseline
+                let class_id = reader.bits(class_bits)? as u16;
+                let serial = reader.bits(SERIAL_BITS)?;
+                let class = match class_by_id(classes, class_id) {
+                    Some(c) => c,
+                    None => return Err(format!("entity {} has unknown class {}", index, class_id))
+                };
+                let mut props = self.baseline(class)?.clone();
+                for (i, value) in read_props(&mut reader, class).map_err(|e| format!("entity {}: {}", index, e))? {
+                    props[i] = Some(value);
+                }
+                let entity = Entity { index, class_id, serial, props };
+                changes.push(EntityChange::Create(entity.clone()));
+                self.state.entities.insert(index, entity);
+            } else {
+                let entity = match self.state.entities.get_mut(&index) {
+                    Some(e) => e,
+                    None => return Err(format!("update for entity {} which doesn't exist", index))
+                };
+                let class = match class_by_id(classes, entity.class_id) {
+                    Some(c) => c,
+                    None => return Err(format!("entity {} has unknown class {}", index, entity.class_id))
+                };
+                let props = read_props(&mut reader, class).map_err(|e| format!("entity {}: {}", index, e))?;
+                for (i, value) in &props {
+                    entity.props[*i] = Some(value.clone());
+                }
+                changes.push(EntityChange::Update { index, props });
+            }
+        }
+        self.state.tick = tick;
+        Ok(changes)
+    }
+}
+
+fn class_by_id(classes: &[ServerClass], class_id: u16) -> Option<&ServerClass> {
+    classes.binary_search_by_key(&class_id, |c| c.class_id).ok().map(|i| &classes[i])
+}
+
+// the prop indices an entity update sends, then the value of each.
+fn read_props(reader: &mut BitReader, class: &ServerClass) -> Result<Vec<(usize, PropValue)>, String> {
+    let new_way = reader.bit()?;
+    let mut indices = Vec::new();
+    let mut index: i64 = -1;
+    loop {
+        if new_way && reader.bit()? {
+            index += 1;
+            indices.push(index as usize);
+            continue
+        }
+        let offset = if new_way && reader.bit()? {
+            reader.bits(3)?
+        } else {
+            let value = reader.bits(7)?;
+            let extra_bits = match value & 0b110_0000 {
+                0b010_0000 => 2,
+                0b100_0000 => 4,
+                0b110_0000 => 7,
+                _ => 0
+            };
+            (value & !0b110_0000) | (reader.bits(extra_bits)? << 5)
+        };
+        if offset == END_OF_PROPS {
+            break
+        }
+        index += 1 + offset as i64;
+        indices.push(index as usize);
+    }
+
+    indices.into_iter().map(|i| {
+        let prop = match class.props.get(i) {
+            Some(p) => p,
+            None => return Err(format!("{} has no prop {}", class.network_name, i))
+        };
+        match decode_prop(reader, prop) {
+            Ok(value) => Ok((i, value)),
+            Err(e) => Err(format!("{}: {}", prop.name, e))
+        }
+    }).collect()
+}
+
+fn decode_prop(reader: &mut BitReader, prop: &SendProp) -> Result<PropValue, String> {
+    let value = match prop.kind {
+        PropKind::Int => PropValue::Int(decode_int(reader, prop)?),
+        PropKind::Int64 => PropValue::Int(decode_int64(reader, prop)?),
+        PropKind::Float => PropValue::Float(decode_float(reader, prop)?),
+        PropKind::Vector => {
+            let x = decode_float(reader, prop)?;
+            let y = decode_float(reader, prop)?;
+            let z = if prop.flags & prop_flag::NORMAL != 0 {
+                // only the sign of z is sent, the vector is unit length
+                let negative = reader.bit()?;
+                let z = (1.0 - x * x - y * y).max(0.0).sqrt();
+                if negative { -z } else { z }
+            } else {
+                decode_float(reader, prop)?
+            };
+            PropValue::Vector([x, y, z])
+        },
+        PropKind::VectorXY => PropValue::VectorXY([decode_float(reader, prop)?, decode_float(reader, prop)?]),
+        PropKind::String => {
+            let length = reader.bits(STRING_LENGTH_BITS)? as usize;
+            PropValue::String(String::from_utf8_lossy(&reader.bytes_from_bits(length * 8)?).to_string())
+        },
+        PropKind::Array => {
+            let element = match &prop.element {
+                Some(e) => e,
+                None => return Err("the array has no element prop".into())
+            };
+            let count_bits = (usize::BITS - prop.num_elements.leading_zeros()) as usize;
+            let count = reader.bits(count_bits)?;
+            PropValue::Array((0..count).map(|_| decode_prop(reader, element)).collect::<Result<_, _>>()?)
+        },
+    };
+    Ok(value)
+}
+
+fn decode_int(reader: &mut BitReader, prop: &SendProp) -> Result<i64, String> {
+    let unsigned = prop.flags & prop_flag::UNSIGNED != 0;
+    let value = match (prop.flags & prop_flag::VARINT != 0, unsigned) {
+        (true, true) => reader.varint32()? as i64,
+        (true, false) => reader.signed_varint32()? as i64,
+        (false, true) => reader.bits(prop.num_bits)? as i64,
+        (false, false) => reader.signed_bits(prop.num_bits)? as i64,
+    };
+    Ok(value)
+}
+
+fn decode_int64(reader: &mut BitReader, prop: &SendProp) -> Result<i64, String> {
+    if prop.flags & prop_flag::VARINT != 0 {
+        return match prop.flags & prop_flag::UNSIGNED != 0 {
+            true => Ok(reader.varint64()? as i64),
+            false => reader.signed_varint64()
+        }
+    }
+    if prop.flags & prop_flag::UNSIGNED != 0 {
+        let low = reader.bits(32)? as u64;
+        let high = reader.bits(prop.num_bits.saturating_sub(32))? as u64;
+        return Ok((high << 32 | low) as i64)
+    }
+    let negative = reader.bit()?;
+    let low = reader.bits(32)? as u64;
+    let high = reader.bits(prop.num_bits.saturating_sub(33))? as u64;
+    let value = (high << 32 | low) as i64;
+    Ok(if negative { -value } else { value })
+}
+
+fn decode_float(reader: &mut BitReader, prop: &SendProp) -> Result<f32, String> {
+    let flags = prop.flags;
+    if flags & prop_flag::COORD != 0 {
+        return bit_coord(reader)
+    }
+    if flags & prop_flag::COORD_MP != 0 {
+        return bit_coord_mp(reader, false, false)
+    }
+    if flags & prop_flag::COORD_MP_LOWPRECISION != 0 {
+        return bit_coord_mp(reader, false, true)
+    }
+    if flags & prop_flag::COORD_MP_INTEGRAL != 0 {
+        return bit_coord_mp(reader, true, false)
+    }
+    if flags & prop_flag::NOSCALE != 0 {
+        return reader.f32()
+    }
+    if flags & prop_flag::NORMAL != 0 {
+        let negative = reader.bit()?;
+        let fraction = reader.bits(coord::NORMAL_FRACTIONAL_BITS)? as f32;
+        let value = fraction / ((1 << coord::NORMAL_FRACTIONAL_BITS) - 1) as f32;
+        return Ok(if negative { -value } else { value })
+    }
+    if flags & prop_flag::CELL_COORD != 0 {
+        return bit_cell_coord(reader, prop.num_bits, false, false)
+    }
+    if flags & prop_flag::CELL_COORD_LOWPRECISION != 0 {
+        return bit_cell_coord(reader, prop.num_bits, false, true)
+    }
+    if flags & prop_flag::CELL_COORD_INTEGRAL != 0 {
+        return bit_cell_coord(reader, prop.num_bits, true, false)
+    }
+
+    // scaled between low_value and high_value
+    if prop.num_bits == 0 {
+        return Ok(prop.low_value)
+    }
+    let value = reader.bits(prop.num_bits)? as f64 / ((1u64 << prop.num_bits) - 1) as f64;
+    Ok(prop.low_value + (prop.high_value - prop.low_value) * value as f32)
+}
+
+fn fraction(reader: &mut BitReader, low_precision: bool) -> Result<f32, String> {
+    let bits = if low_precision { coord::FRACTIONAL_BITS_LOWPRECISION } else { coord::FRACTIONAL_BITS };
+    Ok(reader.bits(bits)? as f32 / (1 << bits) as f32)
+}
+
+fn bit_coord(reader: &mut BitReader) -> Result<f32, String> {
+    let has_integer = reader.bit()?;
+    let has_fraction = reader.bit()?;
+    if !has_integer && !has_fraction {
+        return Ok(0.0)
+    }
+    let negative = reader.bit()?;
+    let mut value = 0.0;
+    if has_integer {
+        value += (reader.bits(coord::INTEGER_BITS)? + 1) as f32;
+    }
+    if has_fraction {
+        value += fraction(reader, false)?;
+    }
+    Ok(if negative { -value } else { value })
+}
+
+fn bit_coord_mp(reader: &mut BitReader, integral: bool, low_precision: bool) -> Result<f32, String> {
+    let in_bounds = reader.bit()?;
+    let integer_bits = if in_bounds { coord::INTEGER_BITS_MP } else { coord::INTEGER_BITS };
+    if integral {
+        if !reader.bit()? {
+            return Ok(0.0)
+        }
+        let negative = reader.bit()?;
+        let value = (reader.bits(integer_bits)? + 1) as f32;
+        return Ok(if negative { -value } else { value })
+    }
+
+    let has_integer = reader.bit()?;
+    let negative = reader.bit()?;
+    let mut value = 0.0;
+    if has_integer {
+        value += (reader.bits(integer_bits)? + 1) as f32;
+    }
+    value += fraction(reader, low_precision)?;
+    Ok(if negative { -value } else { value })
+}
+
+fn bit_cell_coord(reader: &mut BitReader, bits: usize, integral: bool, low_precision: bool) -> Result<f32, String> {
+    let integer = reader.bits(bits)? as f32;
+    if integral {
+        return Ok(integer)
+    }
+    Ok(integer + fraction(reader, low_precision)?)
+}
+
+// a send prop as it is in the data tables, before flattening.
+struct TableProp {
+    prop_type: u64,
+    name: String,
+    flags: u32,
+    priority: u32,
+    dt_name: String,
+    num_elements: usize,
+    low_value: f32,
+    high_value: f32,
+    num_bits: usize,
+}
+
+impl TableProp {
+    fn to_send_prop(&self, prefix: &str, element: Option<Box<SendProp>>) -> Result<SendProp, String> {
+        let kind = match self.prop_type {
+            prop_type::INT => PropKind::Int,
+            prop_type::FLOAT => PropKind::Float,
+            prop_type::VECTOR => PropKind::Vector,
+            prop_type::VECTOR_XY => PropKind::VectorXY,
+            prop_type::STRING => PropKind::String,
+            prop_type::ARRAY => PropKind::Array,
+            prop_type::INT64 => PropKind::Int64,
+            other => return Err(format!("{} has unknown type {}", self.name, other))
+        };
+        Ok(SendProp {
+            name: format!("{}{}", prefix, self.name),
+            kind,
+            flags: self.flags,
+            num_bits: self.num_bits,
+            low_value: self.low_value,
+            high_value: self.high_value,
+            num_elements: self.num_elements,
+            priority: self.priority,
+            element,
+        })
+    }
+}
+
+struct SendTable {
+    name: String,
+    props: Vec<TableProp>,
+}
+
+type SendTables = HashMap<String, SendTable>;
+
+fn server_classes(document: &DemoDocument) -> Result<Vec<ServerClass>, String> {
+    let data = document.sign_on_frames.iter().chain(document.frames.iter()).find_map(|f| match &f.body {
+        FrameBody::DataTables(data) => Some(data),
+        _ => None
+    });
+    let data_tables = match data {
+        Some(data) => DataTablesData::from_data_tables_index(DataTablesIndex { data: data.clone() })
+            .map_err(|e| format!("couldn't parse the data tables: {}", e))?,
+        None => return Err("the demo has no data tables".into())
+    };
+
+    let tables: SendTables = data_tables.send_tables.iter().map(|st| {
+        let name = st.net_table_name.clone().unwrap_or_default();
+        let props = st.SendProp.iter().map(|sp| TableProp {
+            prop_type: sp.sendprop_type.unwrap_or(0),
+            name: sp.var_name.clone().unwrap_or_default(),
+            flags: sp.flags.unwrap_or(0) as u32,
+            priority: sp.priority.unwrap_or(0) as u32,
+            dt_name: sp.dt_name.clone().unwrap_or_default(),
+            num_elements: sp.num_elements.unwrap_or(0) as usize,
+            low_value: f32::from_bits(sp.low_value.unwrap_or(0)),
+            high_value: f32::from_bits(sp.high_value.unwrap_or(0)),
+            num_bits: sp.num_bits.unwrap_or(0) as usize,
+        }).collect();
+        (name.clone(), SendTable { name, props })
+    }).collect();
+
+    data_tables.class_descriptions.iter().map(|cd| {
+        let table = send_table(&tables, &cd.table_name)?;
+        let props = flatten(&tables, table).map_err(|e| format!("{}: {}", cd.network_name, e))?;
+        Ok(ServerClass {
+            class_id: cd.class_id,
+            network_name: cd.network_name.clone(),
+            table_name: cd.table_name.clone(),
+            props,
+        })
+    }).collect()
+}
+
+fn send_table<'a>(tables: &'a SendTables, name: &str) -> Result<&'a SendTable, String> {
+    tables.get(name).ok_or_else(|| format!("there is no send table {}", name))
+}
+
+// the engine's SendTable_BuildHierarchy: every prop of the table and the tables it
+// references minus the excluded ones, then sorted by priority.
+fn flatten(tables: &SendTables, table: &SendTable) -> Result<Vec<SendProp>, String> {
+    let mut excludes = Vec::new();
+    gather_excludes(tables, table, &mut excludes)?;
+    let mut props = Vec::new();
+    gather_props(tables, table, &excludes, "", &mut props)?;
+
+    let mut priorities: Vec<u32> = props.iter().map(|p| p.priority).collect();
+    priorities.push(CHANGES_OFTEN_PRIORITY);
+    priorities.sort();
+    priorities.dedup();
+    // moves each priority's props to the front, keeping their order
+    let mut start = 0;
+    for priority in priorities {
+        let mut end = start;
+        for i in start..props.len() {
+            let prop = &props[i];
+            if prop.priority == priority || (priority == CHANGES_OFTEN_PRIORITY && prop.flags & prop_flag::CHANGES_OFTEN != 0) {
+                props.swap(end, i);
+                end += 1;
+            }
+        }
+        start = end;
+    }
+    Ok(props)
+}
+
+// (table name, prop name) of every excluded prop
+fn gather_excludes(tables: &SendTables, table: &SendTable, excludes: &mut Vec<(String, String)>) -> Result<(), String> {
+    for prop in &table.props {
+        if prop.flags & prop_flag::EXCLUDE != 0 {
+            excludes.push((prop.dt_name.clone(), prop.name.clone()));
+        } else if prop.prop_type == prop_type::DATA_TABLE {
+            gather_excludes(tables, send_table(tables, &prop.dt_name)?, excludes)?;
+        }
+    }
+    Ok(())
+}
+
+// a table's own props (and those of its collapsible tables) come after the props
+// of the other tables it references.
+fn gather_props(
+    tables: &SendTables,
+    table: &SendTable,
+    excludes: &[(String, String)],
+    prefix: &str,
+    props: &mut Vec<SendProp>
+) -> Result<(), String> {
+    let mut own = Vec::new();
+    gather_props_iterate(tables, table, excludes, prefix, props, &mut own)?;
+    props.extend(own);
+    Ok(())
+}
+
+fn gather_props_iterate(
+    tables: &SendTables,
+    table: &SendTable,
+    excludes: &[(String, String)],
+    prefix: &str,
+    props: &mut Vec<SendProp>,
+    own: &mut Vec<SendProp>
+) -> Result<(), String> {
+    for (i, prop) in table.props.iter().enumerate() {
+        if prop.flags & (prop_flag::EXCLUDE | prop_flag::INSIDEARRAY) != 0
+            || excludes.iter().any(|(t, n)| *t == table.name && *n == prop.name) {
+            continue
+        }
+
+        if prop.prop_type == prop_type::DATA_TABLE {
+            let sub_table = send_table(tables, &prop.dt_name)?;
+            let sub_prefix = match prop.name.as_str() {
+                "baseclass" => prefix.to_owned(),
+                name => format!("{}{}.", prefix, name)
+            };
+            if prop.flags & prop_flag::COLLAPSIBLE != 0 {
+                gather_props_iterate(tables, sub_table, excludes, &sub_prefix, props, own)?;
+            } else {
+                gather_props(tables, sub_table, excludes, &sub_prefix, props)?;
+            }
+        } else {
+            // an array's element is the prop before it
+            let element = match prop.prop_type {
+                prop_type::ARRAY => match i.checked_sub(1).map(|e| &table.props[e]) {
+                    Some(e) => Some(Box::new(e.to_send_prop(prefix, None)?)),
+                    None => return Err(format!("array {} has no element prop", prop.name))
+                },
+                _ => None
+            };
+            own.push(prop.to_send_prop(prefix, element)?);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::bit_reader::pack_bits;
+
+    // each case is the packed fields and the value they decode to, all of the bits are read
+    fn check(cases: &[(&[(u64, usize)], f32)], read: impl Fn(&mut BitReader) -> Result<f32, String>) {
+        for (fields, value) in cases {
+            let data = pack_bits(fields);
+            let mut reader = BitReader::new(&data);
+            assert_eq!(read(&mut reader), Ok(*value), "fields {:?}", fields);
+            assert_eq!(reader.pos, fields.iter().map(|(_, count)| count).sum::<usize>());
+        }
+    }
+
+    #[test]
+    fn coord() {
+        check(&[
+            (&[(0, 1), (0, 1)], 0.0),
+            (&[(1, 1), (0, 1), (0, 1), (9, 14)], 10.0),
+            (&[(1, 1), (1, 1), (1, 1), (4, 14), (16, 5)], -5.5),
+            (&[(0, 1), (1, 1), (0, 1), (8, 5)], 0.25),
+        ], bit_coord);
+    }
+
+    #[test]
+    fn coord_mp() {
+        // in bounds, has integer, negative, integer, fraction
+        check(&[
+            (&[(1, 1), (1, 1), (1, 1), (99, 11), (16, 5)], -100.5),
+            (&[(0, 1), (1, 1), (0, 1), (9999, 14), (0, 5)], 10000.0),
+            (&[(1, 1), (0, 1), (0, 1), (8, 5)], 0.25),
+        ], |reader| bit_coord_mp(reader, false, false));
+
+        check(&[
+            (&[(1, 1), (0, 1), (0, 1), (4, 3)], 0.5),
+            (&[(1, 1), (1, 1), (1, 1), (0, 11), (1, 3)], -1.125),
+        ], |reader| bit_coord_mp(reader, false, true));
+
+        // in bounds, has value, negative, integer
+        check(&[
+            (&[(1, 1), (0, 1)], 0.0),
+            (&[(1, 1), (1, 1), (1, 1), (41, 11)], -42.0),
+            (&[(0, 1), (1, 1), (0, 1), (16383, 14)], 16384.0),
+        ], |reader| bit_coord_mp(reader, true, false));
+    }
+
+    #[test]
+    fn cell_coord() {
+        check(&[
+            (&[(300, 10), (8, 5)], 300.25),
+            (&[(0, 10), (31, 5)], 31.0 / 32.0),
+        ], |reader| bit_cell_coord(reader, 10, false, false));
+
+        check(&[
+            (&[(300, 10), (2, 3)], 300.25),
+        ], |reader| bit_cell_coord(reader, 10, false, true));
+
+        check(&[
+            (&[(1023, 10)], 1023.0),
+        ], |reader| bit_cell_coord(reader, 10, true, false));
+    }
+
+    #[test]
+    fn coord_truncated() {
+        let data = pack_bits(&[(1, 1), (0, 1), (0, 1), (9, 5)]);
+        assert!(bit_coord(&mut BitReader::new(&data)).is_err());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+
+use source_demo_tool::demo_file::FullGameEventKey;
+
+use super::{ DemoDocument, string_table::{ self, StringTableUpdate } };
+
+// the player_info_t stored as user data in the userinfo string table. its numbers
+// are big endian, the strings are nul padded to their field's width.
+
+pub const USERINFO_TABLE_NAME: &str = "userinfo";
+// xuid of steam account id 0
+pub const XUID_BASE: u64 = 76561197960265728;
+// game event keys whose value is a player's user id
+pub const USER_ID_KEYS: [&str; 3] = ["userid", "attacker", "assister"];
+
+const XUID_OFFSET: usize = 8;
+const NAME_OFFSET: usize = 16;
+const NAME_LENGTH: usize = 128;
+const USER_ID_OFFSET: usize = 144;
+const GUID_OFFSET: usize = 148;
+const GUID_LENGTH: usize = 33;
+const FRIENDS_ID_OFFSET: usize = 184;
+const FRIENDS_NAME_OFFSET: usize = 188;
+const FAKE_PLAYER_OFFSET: usize = 316;
+const IS_HLTV_OFFSET: usize = 317;
+const MIN_LENGTH: usize = IS_HLTV_OFFSET + 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlayerInfo {
+    pub xuid: u64,
+    pub name: String,
+    pub user_id: i32,
+    // the SteamID as text, "BOT" for bots
+    pub guid: String,
+    pub friends_id: u32,
+    pub friends_name: String,
+    pub fake_player: bool,
+    pub is_hltv: bool,
+}
+
+impl PlayerInfo {
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        if data.len() < MIN_LENGTH {
+            return None
+        }
+        Some(Self {
+            xuid: u64::from_be_bytes(data[XUID_OFFSET..XUID_OFFSET + 8].try_into().ok()?),
+            name: read_string(&data[NAME_OFFSET..NAME_OFFSET + NAME_LENGTH]),
+            user_id: i32::from_be_bytes(data[USER_ID_OFFSET..USER_ID_OFFSET + 4].try_into().ok()?),
+            guid: read_string(&data[GUID_OFFSET..GUID_OFFSET + GUID_LENGTH]),
+            friends_id: u32::from_be_bytes(data[FRIENDS_ID_OFFSET..FRIENDS_ID_OFFSET + 4].try_into().ok()?),
+            friends_name: read_string(&data[FRIENDS_NAME_OFFSET..FRIENDS_NAME_OFFSET + NAME_LENGTH]),
+            fake_player: data[FAKE_PLAYER_OFFSET] != 0,
+            is_hltv: data[IS_HLTV_OFFSET] != 0,
+        })
+    }
+
+    // writes the fields over data, bytes this struct doesn't know about are left alone.
+    pub fn write(&self, data: &mut [u8]) -> Result<(), String> {
+        if data.len() < MIN_LENGTH {
+            return Err(format!("player info is {} bytes, expected at least {}", data.len(), MIN_LENGTH))
+        }
+        data[XUID_OFFSET..XUID_OFFSET + 8].copy_from_slice(&self.xuid.to_be_bytes());
+        write_string("name", &mut data[NAME_OFFSET..NAME_OFFSET + NAME_LENGTH], &self.name)?;
+        data[USER_ID_OFFSET..USER_ID_OFFSET + 4].copy_from_slice(&self.user_id.to_be_bytes());
+        write_string("guid", &mut data[GUID_OFFSET..GUID_OFFSET + GUID_LENGTH], &self.guid)?;
+        data[FRIENDS_ID_OFFSET..FRIENDS_ID_OFFSET + 4].copy_from_slice(&self.friends_id.to_be_bytes());
+        write_string("friends name", &mut data[FRIENDS_NAME_OFFSET..FRIENDS_NAME_OFFSET + NAME_LENGTH], &self.friends_name)?;
+        data[FAKE_PLAYER_OFFSET] = self.fake_player as u8;
+        data[IS_HLTV_OFFSET] = self.is_hltv as u8;
+        Ok(())
+    }
+
+    // the steam account id, None for bots and players without a steam account
+    pub fn account_id(&self) -> Option<u32> {
+        if self.xuid <= XUID_BASE {
+            return None
+        }
+        Some(self.xuid as u32)
+    }
+
+    // STEAM_1:Y:Z
+    pub fn steam_id_2(&self) -> Option<String> {
+        let account_id = self.account_id()?;
+        Some(format!("STEAM_1:{}:{}", account_id & 1, account_id >> 1))
+    }
+
+    // [U:1:account id]
+    pub fn steam_id_3(&self) -> Option<String> {
+        Some(format!("[U:1:{}]", self.account_id()?))
+    }
+}
+
+// one connection of a player, from their userinfo entry being set until it's cleared
+// or taken by another user id.
+#[derive(Debug, Clone)]
+pub struct Player {
+    // the userinfo entry, the player's entity index is one more
+    pub slot: usize,
+    // as last sent
+    pub info: PlayerInfo,
+    // every name the player had and the tick it was set at, in order
+    pub names: Vec<(i32, String)>,
+    pub connect_tick: i32,
+    // None when still connected at the end of the demo
+    pub disconnect_tick: Option<i32>,
+}
+
+impl Player {
+    pub fn is_connected_at(&self, tick: i32) -> bool {
+        tick >= self.connect_tick && self.disconnect_tick.is_none_or(|t| tick <= t)
+    }
+
+    pub fn name_at(&self, tick: i32) -> &str {
+        self.names
+            .iter()
+            .rev()
+            .find(|(t, _)| *t <= tick)
+            .or(self.names.first())
+            .map(|(_, name)| name.as_str())
+            .unwrap_or(&self.info.name)
+    }
+
+    // "name (userid)" as the player was called at tick
+    pub fn describe_at(&self, tick: i32) -> String {
+        format!("{} ({})", self.name_at(tick), self.info.user_id)
+    }
+}
+
+// finds who had a user id at a tick, game events only refer to players by their user id.
+#[derive(Debug, Clone, Default)]
+pub struct UserIdLookup {
+    players: Vec<Player>,
+}
+
+impl UserIdLookup {
+    pub fn new(players: Vec<Player>) -> Self {
+        Self { players }
+    }
+
+    pub fn from_document(document: &DemoDocument) -> Result<Self, String> {
+        Ok(Self::new(players(document)?))
+    }
+
+    pub fn players(&self) -> &[Player] {
+        &self.players
+    }
+
+    // the index into players and the player
+    pub fn find(&self, user_id: i32, tick: i32) -> Option<(usize, &Player)> {
+        self.players
+            .iter()
+            .enumerate()
+            .find(|(_, p)| p.info.user_id == user_id && p.is_connected_at(tick))
+    }
+
+    // the player a USER_ID_KEYS event key refers to. None for other keys and for user ids
+    // nobody had at the tick, e.g. an attacker of 0 is the world.
+    pub fn find_key(&self, key: &FullGameEventKey, tick: i32) -> Option<(usize, &Player)> {
+        if !USER_ID_KEYS.contains(&key.key_name.as_str()) {
+            return None
+        }
+        self.find(key.val_int? as i32, tick)
+    }
+}
+
+pub fn userinfo_updates(document: &DemoDocument) -> Result<Vec<StringTableUpdate>, String> {
+    Ok(string_table::string_table_updates(document)?
+        .into_iter()
+        .filter(|u| u.table_name == USERINFO_TABLE_NAME)
+        .collect())
+}
+
+// every player that was in the demo in the order they connected, bots and the GOTV client included.
+pub fn players(document: &DemoDocument) -> Result<Vec<Player>, String> {
+    let mut players: Vec<Player> = Vec::new();
+    // slot -> index into players of who's in it
+    let mut connected: HashMap<usize, usize> = HashMap::new();
+    for update in userinfo_updates(document)? {
+        for entry in &update.entries {
+            // updates that only send the string leave the player as they are
+            let user_data = match &entry.user_data {
+                Some(d) => d,
+                None => continue
+            };
+            let info = PlayerInfo::from_bytes(user_data);
+            if let (Some(info), Some(&i)) = (&info, connected.get(&entry.index)) {
+                let player = &mut players[i];
+                if player.info.user_id == info.user_id {
+                    if player.info.name != info.name {
+                        player.names.push((update.tick, info.name.clone()));
+                    }
+                    player.info = info.clone();
+                    continue
+                }
+            }
+
+            if let Some(i) = connected.remove(&entry.index) {
+                players[i].disconnect_tick = Some(update.tick);
+            }
+            if let Some(info) = info {
+                connected.insert(entry.index, players.len());
+                players.push(Player {
+                    slot: entry.index,
+                    names: vec![(update.tick, info.name.clone())],
+                    info,
+                    connect_tick: update.tick,
+                    disconnect_tick: None,
+                });
+            }
+        }
+    }
+    Ok(players)
+}
+
+fn read_string(field: &[u8]) -> String {
+    let end = field.iter().position(|&c| c == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).to_string()
+}
+
+// the whole field is rewritten so nothing of the old value is left after the nul.
+fn write_string(name: &str, field: &mut [u8], value: &str) -> Result<(), String> {
+    if value.len() >= field.len() {
+        return Err(format!("{} '{}' is longer than {} bytes", name, value, field.len() - 1))
+    }
+    field.fill(0);
+    field[..value.len()].copy_from_slice(value.as_bytes());
+    Ok(())
+}
\ No newline at end of file
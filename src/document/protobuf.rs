@@ -0,0 +1,157 @@
+use buf_redux::BufReader;
+use source_demo_tool::{
+    demo_file::packet::{ netmessage::NetMessage, usermessage::UserMessage },
+    protobuf_message::{ ProtobufMessage, WireMessage, wire_type },
+};
+
+pub fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0b0111_1111) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return
+        }
+        out.push(byte | 0b1000_0000);
+    }
+}
+
+// reads a varint at pos, advancing pos past it.
+pub fn read_varint(data: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut value: u64 = 0;
+    for i in 0..10 {
+        let byte = *data.get(*pos)?;
+        *pos += 1;
+        value |= ((byte & 0b0111_1111) as u64) << (7 * i);
+        if byte & 0b1000_0000 == 0 {
+            return Some(value)
+        }
+    }
+    None
+}
+
+// decodes the wire level fields of a message, unknown fields and field order are kept.
+pub fn decode_fields(data: &[u8]) -> Result<Vec<ProtobufMessage>, String> {
+    match ProtobufMessage::many_from_vec(&data.to_vec()) {
+        Ok(fields) => Ok(fields),
+        Err(e) => Err(format!("couldn't decode protobuf fields: {:?}", e))
+    }
+}
+
+pub fn encode_fields(fields: &[ProtobufMessage]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for field in fields {
+        let field_number = (field.field_number as u64) << 3;
+        match &field.message {
+            WireMessage::VarInt(v) => {
+                write_varint(&mut out, field_number | wire_type::VARINT as u64);
+                write_varint(&mut out, *v);
+            },
+            WireMessage::Length(v) => {
+                write_varint(&mut out, field_number | wire_type::LENGTH as u64);
+                write_varint(&mut out, v.len() as u64);
+                out.extend_from_slice(v);
+            },
+            WireMessage::Fixed32(v) => {
+                write_varint(&mut out, field_number | wire_type::FIXED32 as u64);
+                out.extend_from_slice(&v.to_le_bytes());
+            },
+        }
+    }
+    out
+}
+
+// parses a netmessage's data the way the viewer does.
+pub fn parse_net_message(id: u64, data: &[u8]) -> Result<NetMessage, String> {
+    let mut reader = BufReader::with_capacity(data.len(), data);
+    if let Err(e) = reader.read_into_buf() {
+        return Err(e.to_string())
+    }
+    match NetMessage::parse_from_id_and_bufredux_reader(id, &mut reader) {
+        Ok((message, _)) => Ok(message),
+        Err(e) => Err(format!("couldn't parse netmessage {}: {:?}", id, e))
+    }
+}
+
+// parses the msg_data of a UserMessage netmessage.
+pub fn parse_user_message(msg_type: u64, data: &[u8]) -> Result<UserMessage, String> {
+    let mut reader = BufReader::with_capacity(data.len(), data);
+    if let Err(e) = reader.read_into_buf() {
+        return Err(e.to_string())
+    }
+    match UserMessage::parse_from_id_and_bufredux_reader(msg_type, &mut reader) {
+        Ok((message, _)) => Ok(message),
+        Err(e) => Err(format!("couldn't parse user message {}: {:?}", msg_type, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn varint_boundaries() {
+        let cases: [(u64, &[u8]); 7] = [
+            (0, &[0x00]),
+            (127, &[0x7f]),
+            (128, &[0x80, 0x01]),
+            (16383, &[0xff, 0x7f]),
+            (16384, &[0x80, 0x80, 0x01]),
+            (u32::MAX as u64, &[0xff, 0xff, 0xff, 0xff, 0x0f]),
+            (u64::MAX, &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]),
+        ];
+        for (value, bytes) in cases {
+            let mut out = Vec::new();
+            write_varint(&mut out, value);
+            assert_eq!(out, bytes, "encoding {}", value);
+
+            let mut pos = 0;
+            assert_eq!(read_varint(&out, &mut pos), Some(value), "decoding {}", value);
+            assert_eq!(pos, bytes.len());
+        }
+    }
+
+    #[test]
+    fn varint_reads_from_pos() {
+        let data = [0x01, 0xac, 0x02, 0x05];
+        let mut pos = 1;
+        assert_eq!(read_varint(&data, &mut pos), Some(300));
+        assert_eq!(pos, 3);
+    }
+
+    #[test]
+    fn varint_truncated_or_too_long() {
+        let mut pos = 0;
+        assert_eq!(read_varint(&[0x80, 0x80], &mut pos), None);
+
+        let mut pos = 0;
+        assert_eq!(read_varint(&[], &mut pos), None);
+
+        let mut pos = 0;
+        assert_eq!(read_varint(&[0xff; 11], &mut pos), None);
+    }
+
+    #[test]
+    fn fields_round_trip() {
+        let fields = vec![
+            ProtobufMessage { field_number: 1, message: WireMessage::VarInt(13) },
+            ProtobufMessage { field_number: 8, message: WireMessage::Fixed32(0x1122_3344) },
+            ProtobufMessage { field_number: 16, message: WireMessage::Length(b"de_dust2".to_vec()) },
+            ProtobufMessage { field_number: 2, message: WireMessage::Length(Vec::new()) },
+            ProtobufMessage { field_number: 31, message: WireMessage::VarInt(u64::MAX) },
+        ];
+        let data = encode_fields(&fields);
+        // field order is kept, field 16's tag takes two bytes
+        assert_eq!(&data[..2], &[1 << 3, 13]);
+        assert_eq!(decode_fields(&data).unwrap(), fields);
+        assert_eq!(encode_fields(&decode_fields(&data).unwrap()), data);
+    }
+
+    #[test]
+    fn decode_fields_rejects_truncated_data() {
+        let data = encode_fields(&[
+            ProtobufMessage { field_number: 4, message: WireMessage::Length(b"hello".to_vec()) },
+        ]);
+        assert!(decode_fields(&data[..data.len() - 2]).is_err());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,376 @@
+use source_demo_tool::protobuf_message::{ ProtobufMessage, WireMessage };
+
+use super::{ DemoDocument, FrameBody, MessageLocation, bit_reader::BitReader, net_message_id };
+
+mod create_field {
+    pub const                NAME: u8 = 1;
+    pub const         MAX_ENTRIES: u8 = 2;
+    pub const         NUM_ENTRIES: u8 = 3;
+    pub const USER_DATA_FIXED_SIZE: u8 = 4;
+    pub const USER_DATA_SIZE_BITS: u8 = 6;
+    pub const         STRING_DATA: u8 = 8;
+}
+
+mod update_field {
+    pub const            TABLE_ID: u8 = 1;
+    pub const NUM_CHANGED_ENTRIES: u8 = 2;
+    pub const         STRING_DATA: u8 = 3;
+}
+
+const HISTORY_LENGTH: usize = 32;
+const HISTORY_INDEX_BITS: usize = 5;
+const SUBSTRING_BITS: usize = 5;
+const USER_DATA_LENGTH_BITS: usize = 14;
+
+// where a string table's entries were sent, string_data offsets are relative to this.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StringTableLocation {
+    // a CreateStringTable or UpdateStringTable netmessage
+    Message(MessageLocation),
+    // a StringTables frame, a snapshot of every table
+    Frame { sign_on: bool, frame_index: usize },
+}
+
+impl StringTableLocation {
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Message(location) => location.describe(),
+            Self::Frame { sign_on, frame_index } => format!(
+                "{} {}",
+                if *sign_on { "Sign On Frame" } else { "Frame" },
+                frame_index + 1
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StringTableEntry {
+    pub index: usize,
+    // the entry's string, taken from the table when the update didn't send it
+    pub string: String,
+    pub user_data: Option<Vec<u8>>,
+    // where user_data is in the string data, in bits
+    pub user_data_offset: usize,
+    pub user_data_bits: usize,
+}
+
+// the entries of one table changed by a single message or frame.
+#[derive(Debug, Clone)]
+pub struct StringTableUpdate {
+    pub location: StringTableLocation,
+    // the tick of the frame it was sent in
+    pub tick: i32,
+    pub table_name: String,
+    // true for CreateStringTable and StringTables frames, these replace the table
+    pub is_full: bool,
+    pub entries: Vec<StringTableEntry>,
+}
+
+impl StringTableUpdate {
+    pub fn is_sign_on(&self) -> bool {
+        match self.location {
+            StringTableLocation::Message(l) => l.sign_on,
+            StringTableLocation::Frame { sign_on, .. } => sign_on,
+        }
+    }
+
+    // whether the tables at tick include this update, updates from the sign on frames always apply.
+    pub fn applies_at(&self, tick: i32) -> bool {
+        self.is_sign_on() || self.tick <= tick
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StringTable {
+    pub name: String,
+    pub max_entries: usize,
+    pub user_data_fixed_size: bool,
+    pub user_data_size_bits: usize,
+    pub entries: Vec<(String, Option<Vec<u8>>)>,
+}
+
+impl StringTable {
+    fn apply(&mut self, update: &StringTableUpdate) {
+        if update.is_full {
+            self.entries.clear();
+        }
+        for entry in &update.entries {
+            if self.entries.len() <= entry.index {
+                self.entries.resize(entry.index + 1, (String::new(), None));
+            }
+            let slot = &mut self.entries[entry.index];
+            slot.0 = entry.string.clone();
+            if entry.user_data.is_some() || update.is_full {
+                slot.1 = entry.user_data.clone();
+            }
+        }
+    }
+
+    fn parse_entries(&self, data: &[u8], num_entries: usize) -> Result<Vec<StringTableEntry>, String> {
+        let mut reader = BitReader::new(data);
+        if reader.bit()? {
+            return Err(format!("string table '{}' uses dictionary encoding, which isn't supported", self.name))
+        }
+
+        let mut entry_bits = 0;
+        while (self.max_entries >> (entry_bits + 1)) != 0 {
+            entry_bits += 1;
+        }
+
+        let mut entries = Vec::with_capacity(num_entries);
+        let mut history: Vec<String> = Vec::with_capacity(HISTORY_LENGTH);
+        let mut last_index: isize = -1;
+        for _ in 0..num_entries {
+            let index = if reader.bit()? {
+                (last_index + 1) as usize
+            } else {
+                reader.bits(entry_bits)? as usize
+            };
+            last_index = index as isize;
+
+            let string = if reader.bit()? {
+                if reader.bit()? {
+                    let history_index = reader.bits(HISTORY_INDEX_BITS)? as usize;
+                    let length = reader.bits(SUBSTRING_BITS)? as usize;
+                    let prefix = match history.get(history_index) {
+                        Some(s) => s.as_bytes(),
+                        None => return Err(format!("string table '{}' references missing history entry {}", self.name, history_index))
+                    };
+                    let mut string = prefix[..length.min(prefix.len())].to_vec();
+                    string.extend_from_slice(&reader.string()?);
+                    String::from_utf8_lossy(&string).to_string()
+                } else {
+                    String::from_utf8_lossy(&reader.string()?).to_string()
+                }
+            } else {
+                self.entries.get(index).map(|e| e.0.clone()).unwrap_or_default()
+            };
+
+            let mut user_data = None;
+            let mut user_data_offset = 0;
+            let mut user_data_bits = 0;
+            if reader.bit()? {
+                user_data_bits = if self.user_data_fixed_size {
+                    self.user_data_size_bits
+                } else {
+                    reader.bits(USER_DATA_LENGTH_BITS)? as usize * 8
+                };
+                user_data_offset = reader.pos;
+                user_data = Some(reader.bytes_from_bits(user_data_bits)?);
+            }
+
+            if history.len() == HISTORY_LENGTH {
+                history.remove(0);
+            }
+            history.push(string.clone());
+
+            entries.push(StringTableEntry { index, string, user_data, user_data_offset, user_data_bits });
+        }
+        Ok(entries)
+    }
+}
+
+// every string table change in the demo in file order, with the tables' state
+// used to fill in entries that only sent user data.
+pub fn string_table_updates(document: &DemoDocument) -> Result<Vec<StringTableUpdate>, String> {
+    let mut tables: Vec<StringTable> = Vec::new();
+    let mut updates = Vec::new();
+
+    for (sign_on, frames) in [(true, &document.sign_on_frames), (false, &document.frames)] {
+        for (frame_index, frame) in frames.iter().enumerate() {
+            let tick = frame.tick;
+            match &frame.body {
+                FrameBody::SignOn(packet) | FrameBody::Packet(packet) => {
+                    for (message_index, message) in packet.messages.iter().enumerate() {
+                        if message.id != net_message_id::CREATE_STRING_TABLE
+                            && message.id != net_message_id::UPDATE_STRING_TABLE {
+                            continue
+                        }
+                        let location = MessageLocation { sign_on, frame_index, message_index };
+                        let fields = message.fields()
+                            .map_err(|e| format!("{}: {}", location.describe(), e))?;
+                        let update = if message.id == net_message_id::CREATE_STRING_TABLE {
+                            let table = StringTable {
+                                name: field_string(&fields, create_field::NAME),
+                                max_entries: field_varint(&fields, create_field::MAX_ENTRIES) as usize,
+                                user_data_fixed_size: field_varint(&fields, create_field::USER_DATA_FIXED_SIZE) != 0,
+                                user_data_size_bits: field_varint(&fields, create_field::USER_DATA_SIZE_BITS) as usize,
+                                entries: Vec::new(),
+                            };
+                            let entries = table.parse_entries(
+                                field_bytes(&fields, create_field::STRING_DATA),
+                                field_varint(&fields, create_field::NUM_ENTRIES) as usize
+                            ).map_err(|e| format!("{}: {}", location.describe(), e))?;
+                            tables.push(table);
+                            StringTableUpdate {
+                                location: StringTableLocation::Message(location),
+                                tick,
+                                table_name: tables[tables.len() - 1].name.clone(),
+                                is_full: true,
+                                entries,
+                            }
+                        } else {
+                            let table_id = field_varint(&fields, update_field::TABLE_ID) as usize;
+                            let table = match tables.get(table_id) {
+                                Some(table) => table,
+                                None => return Err(format!("{}: update for unknown string table {}", location.describe(), table_id))
+                            };
+                            let entries = table.parse_entries(
+                                field_bytes(&fields, update_field::STRING_DATA),
+                                field_varint(&fields, update_field::NUM_CHANGED_ENTRIES) as usize
+                            ).map_err(|e| format!("{}: {}", location.describe(), e))?;
+                            StringTableUpdate {
+                                location: StringTableLocation::Message(location),
+                                tick,
+                                table_name: table.name.clone(),
+                                is_full: false,
+                                entries,
+                            }
+                        };
+                        if let Some(table) = tables.iter_mut().rev().find(|t| t.name == update.table_name) {
+                            table.apply(&update);
+                        }
+                        updates.push(update);
+                    }
+                },
+                FrameBody::StringTables(data) => {
+                    let location = StringTableLocation::Frame { sign_on, frame_index };
+                    let snapshot = parse_string_tables_frame(data, location, tick)
+                        .map_err(|e| format!("{}: {}", location.describe(), e))?;
+                    for update in snapshot {
+                        if let Some(table) = tables.iter_mut().rev().find(|t| t.name == update.table_name) {
+                            table.apply(&update);
+                        }
+                        updates.push(update);
+                    }
+                },
+                _ => {}
+            }
+        }
+    }
+    Ok(updates)
+}
+
+// the tables after every update up to and including tick, in the order they were created.
+pub fn string_tables_at(updates: &[StringTableUpdate], tick: i32) -> Vec<StringTable> {
+    let mut tables: Vec<StringTable> = Vec::new();
+    for update in updates.iter().filter(|u| u.applies_at(tick)) {
+        let table = match tables.iter().position(|t| t.name == update.table_name) {
+            Some(i) => &mut tables[i],
+            None => {
+                tables.push(StringTable { name: update.table_name.clone(), ..Default::default() });
+                tables.last_mut().unwrap()
+            }
+        };
+        table.apply(update);
+    }
+    tables
+}
+
+fn parse_string_tables_frame(data: &[u8], location: StringTableLocation, tick: i32) -> Result<Vec<StringTableUpdate>, String> {
+    let mut reader = BitReader::new(data);
+    let table_count = reader.bits(8)?;
+    let mut updates = Vec::with_capacity(table_count as usize);
+    for _ in 0..table_count {
+        let table_name = String::from_utf8_lossy(&reader.string()?).to_string();
+        let mut entries = Vec::new();
+        // client side entries come second, they're only read past
+        for client_side in [false, true] {
+            if client_side && !reader.bit()? {
+                break
+            }
+            let entry_count = reader.bits(16)? as usize;
+            for index in 0..entry_count {
+                let string = String::from_utf8_lossy(&reader.string()?).to_string();
+                let mut user_data = None;
+                let mut user_data_offset = 0;
+                let mut user_data_bits = 0;
+                if reader.bit()? {
+                    user_data_bits = reader.bits(16)? as usize * 8;
+                    user_data_offset = reader.pos;
+                    user_data = Some(reader.bytes_from_bits(user_data_bits)?);
+                }
+                if !client_side {
+                    entries.push(StringTableEntry { index, string, user_data, user_data_offset, user_data_bits });
+                }
+            }
+        }
+        updates.push(StringTableUpdate { location, tick, table_name, is_full: true, entries });
+    }
+    Ok(updates)
+}
+
+// overwrites an entry's user data in place, the data must be the same length as the original.
+pub fn write_user_data(
+    document: &mut DemoDocument,
+    location: StringTableLocation,
+    entry: &StringTableEntry,
+    user_data: &[u8]
+) -> Result<(), String> {
+    if user_data.len() * 8 < entry.user_data_bits {
+        return Err(format!("{}: user data for entry {} is too short", location.describe(), entry.index))
+    }
+
+    match location {
+        StringTableLocation::Message(message_location) => {
+            let message = match document.message_mut(message_location) {
+                Some(m) => m,
+                None => return Err(format!("{}: no such message", message_location.describe()))
+            };
+            let data_field = match message.id {
+                net_message_id::CREATE_STRING_TABLE => create_field::STRING_DATA,
+                _ => update_field::STRING_DATA,
+            };
+            let mut fields = message.fields()?;
+            for field in &mut fields {
+                if let (n, WireMessage::Length(data)) = (field.field_number, &mut field.message) {
+                    if n == data_field {
+                        write_bits(data, entry.user_data_offset, user_data, entry.user_data_bits)?;
+                    }
+                }
+            }
+            message.set_fields(&fields);
+        },
+        StringTableLocation::Frame { sign_on, frame_index } => {
+            let frames = if sign_on { &mut document.sign_on_frames } else { &mut document.frames };
+            match frames.get_mut(frame_index).map(|f| &mut f.body) {
+                Some(FrameBody::StringTables(data)) => {
+                    write_bits(data, entry.user_data_offset, user_data, entry.user_data_bits)?;
+                },
+                _ => return Err(format!("{}: not a StringTables frame", location.describe()))
+            }
+        },
+    }
+    Ok(())
+}
+
+fn write_bits(data: &mut [u8], offset: usize, value: &[u8], bit_count: usize) -> Result<(), String> {
+    if offset + bit_count > data.len() * 8 {
+        return Err("user data runs past the end of the string data".into())
+    }
+    for i in 0..bit_count {
+        let bit = (value[i / 8] >> (i % 8)) & 1;
+        let pos = offset + i;
+        data[pos / 8] = (data[pos / 8] & !(1 << (pos % 8))) | (bit << (pos % 8));
+    }
+    Ok(())
+}
+
+fn field_varint(fields: &[ProtobufMessage], field_number: u8) -> u64 {
+    fields.iter().find_map(|f| match (f.field_number == field_number, &f.message) {
+        (true, WireMessage::VarInt(v)) => Some(*v),
+        _ => None
+    }).unwrap_or(0)
+}
+
+fn field_bytes(fields: &[ProtobufMessage], field_number: u8) -> &[u8] {
+    fields.iter().find_map(|f| match (f.field_number == field_number, &f.message) {
+        (true, WireMessage::Length(v)) => Some(v.as_slice()),
+        _ => None
+    }).unwrap_or(&[])
+}
+
+fn field_string(fields: &[ProtobufMessage], field_number: u8) -> String {
+    String::from_utf8_lossy(field_bytes(fields, field_number)).to_string()
+}
\ No newline at end of file
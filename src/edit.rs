@@ -0,0 +1,20 @@
+pub mod anonymize;
+pub mod concat;
+pub mod console_command;
+pub mod header;
+pub mod journal;
+pub mod map_switch;
+pub mod patch;
+pub mod protobuf_field;
+pub mod repair;
+pub mod strip;
+pub mod trim;
+
+// a single value changed by an edit, shown to the user before saving.
+#[derive(Debug, Clone)]
+pub struct FieldChange {
+    pub location: String,
+    pub field: String,
+    pub old: String,
+    pub new: String,
+}
\ No newline at end of file
@@ -0,0 +1,485 @@
+use std::collections::HashMap;
+
+use source_demo_tool::protobuf_message::{ ProtobufMessage, WireMessage };
+
+use super::FieldChange;
+use crate::document::{
+    DemoDocument,
+    MessageLocation,
+    net_message_id,
+    player_info::{ self, PlayerInfo, XUID_BASE },
+    protobuf,
+    string_table::{ self, StringTableUpdate },
+};
+
+// user messages whose strings can hold player names
+mod user_message_id {
+    pub const   SAY_TEXT: u64 = 5;
+    pub const SAY_TEXT_2: u64 = 6;
+    pub const   TEXT_MSG: u64 = 7;
+    pub const RADIO_TEXT: u64 = 22;
+}
+
+mod user_message_field {
+    pub const MSG_TYPE: u8 = 1;
+    pub const MSG_DATA: u8 = 2;
+}
+
+mod game_event_field {
+    pub const EVENT_ID: u8 = 2;
+    pub const     KEYS: u8 = 3;
+}
+
+mod game_event_key_field {
+    pub const VAL_STRING: u8 = 2;
+    pub const VAL_UINT64: u8 = 8;
+}
+
+mod game_event_list_field {
+    pub const   DESCRIPTORS: u8 = 1;
+    pub const      EVENT_ID: u8 = 1;
+    pub const          NAME: u8 = 2;
+    pub const          KEYS: u8 = 3;
+    pub const      KEY_NAME: u8 = 2;
+}
+
+// shorter names are only replaced when they are the whole string, not inside text
+const MIN_SUBSTRING_LENGTH: usize = 3;
+
+// replaces player names, SteamIDs and XUIDs with "Player N" identities in the userinfo
+// string table, chat user messages, game event keys and the header's client name.
+// a player keeps the same identity everywhere, bots and the GOTV client are left alone.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Anonymize;
+
+#[derive(Debug, Clone)]
+struct Identity {
+    name: String,
+    xuid: u64,
+    guid: String,
+    account_id: u32,
+}
+
+#[derive(Default)]
+struct Substitutions {
+    identities: Vec<Identity>,
+    by_xuid: HashMap<u64, usize>,
+    by_name: HashMap<String, usize>,
+    // original -> replacement, for text. longest first so names containing others go first
+    strings: Vec<(String, String)>,
+}
+
+impl Substitutions {
+    // the identity player is replaced with, made by new_identity from the next fake
+    // account id the first time the player is seen.
+    fn identity_for(&mut self, player: &PlayerInfo, new_identity: impl FnOnce(u32) -> Identity) -> usize {
+        let existing = match player.xuid {
+            0 => self.by_name.get(&player.name).copied(),
+            xuid => self.by_xuid.get(&xuid).copied(),
+        };
+        let index = existing.unwrap_or_else(|| {
+            let account_id = self.identities.len() as u32 + 1;
+            self.identities.push(new_identity(account_id));
+            self.identities.len() - 1
+        });
+        if player.xuid != 0 {
+            self.by_xuid.insert(player.xuid, index);
+        }
+        self.by_name.insert(player.name.clone(), index);
+        if !player.friends_name.is_empty() {
+            self.by_name.insert(player.friends_name.clone(), index);
+        }
+
+        let identity = &self.identities[index];
+        let mut strings = vec![(player.name.clone(), identity.name.clone())];
+        if !player.guid.is_empty() {
+            strings.push((player.guid.clone(), identity.guid.clone()));
+        }
+        if player.xuid != 0 {
+            strings.push((player.xuid.to_string(), identity.xuid.to_string()));
+        }
+        for (old, new) in strings {
+            if old != new && !self.strings.iter().any(|(o, _)| *o == old) {
+                self.strings.push((old, new));
+            }
+        }
+        index
+    }
+
+    fn finish(&mut self) {
+        self.strings.sort_by_key(|(old, _)| std::cmp::Reverse(old.len()));
+    }
+
+    fn text(&self, value: &str) -> Option<String> {
+        if let Some((_, new)) = self.strings.iter().find(|(old, _)| old == value) {
+            return Some(new.clone())
+        }
+        let mut out = value.to_owned();
+        for (old, new) in &self.strings {
+            if old.len() >= MIN_SUBSTRING_LENGTH && out.contains(old.as_str()) {
+                out = out.replace(old.as_str(), new);
+            }
+        }
+        if out == value { None } else { Some(out) }
+    }
+}
+
+// renames players everywhere Anonymize replaces them, their SteamIDs and XUIDs are
+// kept. names are matched against the userinfo name and friends name, bots and the
+// GOTV client can't be renamed.
+#[derive(Debug, Clone, Default)]
+pub struct ReplaceNames {
+    // old name -> new name
+    pub names: Vec<(String, String)>,
+}
+
+impl ReplaceNames {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.names.is_empty() {
+            return Err("no names to replace were given".into())
+        }
+        for (old, new) in &self.names {
+            if old.is_empty() || new.is_empty() {
+                return Err("player names can't be empty".into())
+            }
+            if new.contains('\0') {
+                return Err(format!("the new name for '{}' can't contain nul characters", old))
+            }
+        }
+        Ok(())
+    }
+
+    // fails if a name matches no player.
+    pub fn apply(&self, document: &mut DemoDocument) -> Result<Vec<FieldChange>, String> {
+        self.validate()?;
+        let userinfo = player_info::userinfo_updates(document)?;
+
+        let mut substitutions = Substitutions::default();
+        let mut unmatched: Vec<&str> = self.names.iter().map(|(old, _)| old.as_str()).collect();
+        for player in players(&userinfo) {
+            let new_name = self.names
+                .iter()
+                .find(|(old, _)| *old == player.name || *old == player.friends_name)
+                .map(|(old, new)| {
+                    unmatched.retain(|n| n != old);
+                    new.clone()
+                });
+            if let Some(name) = new_name {
+                substitutions.identity_for(&player, |_| Identity {
+                    name,
+                    xuid: player.xuid,
+                    guid: player.guid.clone(),
+                    account_id: player.friends_id,
+                });
+            }
+        }
+        if !unmatched.is_empty() {
+            return Err(format!("no player is named '{}'", unmatched.join("', '")))
+        }
+        substitutions.finish();
+
+        Anonymize::substitute(document, &userinfo, &substitutions)
+    }
+}
+
+// every real player in the userinfo updates, bots and the GOTV client are left out.
+fn players(userinfo: &[StringTableUpdate]) -> impl Iterator<Item = PlayerInfo> + '_ {
+    userinfo.iter()
+        .flat_map(|update| update.entries.iter())
+        .filter_map(|entry| entry.user_data.as_deref().and_then(PlayerInfo::from_bytes))
+        .filter(|player| !player.fake_player && !player.is_hltv)
+}
+
+impl Anonymize {
+    pub fn apply(&self, document: &mut DemoDocument) -> Result<Vec<FieldChange>, String> {
+        let userinfo = player_info::userinfo_updates(document)?;
+
+        let mut substitutions = Substitutions::default();
+        for player in players(&userinfo) {
+            substitutions.identity_for(&player, |account_id| Identity {
+                name: format!("Player {}", account_id),
+                xuid: XUID_BASE + account_id as u64,
+                guid: format!("STEAM_1:{}:{}", account_id % 2, account_id / 2),
+                account_id,
+            });
+        }
+        if substitutions.identities.is_empty() {
+            return Err("no players were found in the userinfo string table".into())
+        }
+        substitutions.finish();
+
+        Self::substitute(document, &userinfo, &substitutions)
+    }
+
+    // replaces every player substitutions has an identity for.
+    fn substitute(
+        document: &mut DemoDocument,
+        userinfo: &[StringTableUpdate],
+        substitutions: &Substitutions
+    ) -> Result<Vec<FieldChange>, String> {
+        let mut changes = Vec::new();
+        Self::userinfo(document, userinfo, substitutions, &mut changes)?;
+
+        let client_identity = substitutions.by_name.get(&document.header.client_name);
+        if let Some(&index) = client_identity.filter(|&&i| substitutions.identities[i].name != document.header.client_name) {
+            let name = substitutions.identities[index].name.clone();
+            changes.push(FieldChange {
+                location: "Header".into(),
+                field: "client_name".into(),
+                old: std::mem::replace(&mut document.header.client_name, name.clone()),
+                new: name,
+            });
+        }
+
+        Self::user_messages(document, substitutions, &mut changes)?;
+        Self::game_events(document, substitutions, &mut changes)?;
+        Ok(changes)
+    }
+
+    fn userinfo(
+        document: &mut DemoDocument,
+        userinfo: &[StringTableUpdate],
+        substitutions: &Substitutions,
+        changes: &mut Vec<FieldChange>
+    ) -> Result<(), String> {
+        for update in userinfo {
+            for entry in &update.entries {
+                let mut user_data = match &entry.user_data {
+                    Some(data) => data.clone(),
+                    None => continue
+                };
+                let player = match PlayerInfo::from_bytes(&user_data) {
+                    Some(p) if !p.fake_player && !p.is_hltv => p,
+                    _ => continue
+                };
+                let index = match player.xuid {
+                    0 => substitutions.by_name.get(&player.name),
+                    xuid => substitutions.by_xuid.get(&xuid),
+                };
+                let index = match index {
+                    Some(&index) => index,
+                    None => continue
+                };
+                let identity = &substitutions.identities[index];
+                let anonymized = PlayerInfo {
+                    xuid: if player.xuid == 0 { 0 } else { identity.xuid },
+                    name: identity.name.clone(),
+                    guid: if player.guid.is_empty() { String::new() } else { identity.guid.clone() },
+                    friends_id: if player.friends_id == 0 { 0 } else { identity.account_id },
+                    friends_name: if player.friends_name.is_empty() { String::new() } else { identity.name.clone() },
+                    ..player.clone()
+                };
+
+                let location = format!("userinfo entry {} ({})", entry.index, update.location.describe());
+                let fields = [
+                    ("name", player.name.clone(), anonymized.name.clone()),
+                    ("xuid", player.xuid.to_string(), anonymized.xuid.to_string()),
+                    ("guid", player.guid.clone(), anonymized.guid.clone()),
+                    ("friends_id", player.friends_id.to_string(), anonymized.friends_id.to_string()),
+                    ("friends_name", player.friends_name.clone(), anonymized.friends_name.clone()),
+                ];
+                for (field, old, new) in fields {
+                    if old != new {
+                        changes.push(FieldChange { location: location.clone(), field: field.into(), old, new });
+                    }
+                }
+
+                anonymized.write(&mut user_data)?;
+                string_table::write_user_data(document, update.location, entry, &user_data)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn user_messages(
+        document: &mut DemoDocument,
+        substitutions: &Substitutions,
+        changes: &mut Vec<FieldChange>
+    ) -> Result<(), String> {
+        for location in document.message_locations(net_message_id::USER_MESSAGE) {
+            let message = match document.message_mut(location) {
+                Some(m) => m,
+                None => continue
+            };
+            let mut fields = message.fields().map_err(|e| format!("{}: {}", location.describe(), e))?;
+            let msg_type = fields.iter().find_map(|f| match (f.field_number, &f.message) {
+                (user_message_field::MSG_TYPE, WireMessage::VarInt(v)) => Some(*v),
+                _ => None
+            });
+            let msg_name = match msg_type {
+                Some(user_message_id::SAY_TEXT) => "SayText",
+                Some(user_message_id::SAY_TEXT_2) => "SayText2",
+                Some(user_message_id::TEXT_MSG) => "TextMsg",
+                Some(user_message_id::RADIO_TEXT) => "RadioText",
+                _ => continue
+            };
+
+            let mut b_changed = false;
+            for field in &mut fields {
+                if let (user_message_field::MSG_DATA, WireMessage::Length(data)) = (field.field_number, &mut field.message) {
+                    let mut inner = match protobuf::decode_fields(data) {
+                        Ok(inner) => inner,
+                        Err(_) => continue
+                    };
+                    for inner_field in &mut inner {
+                        let field_name = format!("field {}", inner_field.field_number);
+                        b_changed |= substitute_string(
+                            &mut inner_field.message,
+                            substitutions,
+                            &format!("UserMessage {} ({})", msg_name, location.describe()),
+                            &field_name,
+                            changes
+                        );
+                    }
+                    *data = protobuf::encode_fields(&inner);
+                }
+            }
+            if b_changed {
+                message.set_fields(&fields);
+            }
+        }
+        Ok(())
+    }
+
+    fn game_events(
+        document: &mut DemoDocument,
+        substitutions: &Substitutions,
+        changes: &mut Vec<FieldChange>
+    ) -> Result<(), String> {
+        let descriptors = game_event_descriptors(document);
+        for location in document.message_locations(net_message_id::GAME_EVENT) {
+            let message = match document.message_mut(location) {
+                Some(m) => m,
+                None => continue
+            };
+            let mut fields = message.fields().map_err(|e| format!("{}: {}", location.describe(), e))?;
+            let event_id = fields.iter().find_map(|f| match (f.field_number, &f.message) {
+                (game_event_field::EVENT_ID, WireMessage::VarInt(v)) => Some(*v),
+                _ => None
+            });
+            let descriptor = event_id.and_then(|id| descriptors.get(&id));
+            let event_name = descriptor.map(|d| d.0.as_str()).unwrap_or("unknown");
+
+            let mut b_changed = false;
+            let mut key_index = 0;
+            for field in &mut fields {
+                let data = match (field.field_number, &mut field.message) {
+                    (game_event_field::KEYS, WireMessage::Length(data)) => data,
+                    _ => continue
+                };
+                let key_name = descriptor
+                    .and_then(|d| d.1.get(key_index))
+                    .cloned()
+                    .unwrap_or_else(|| format!("key {}", key_index + 1));
+                key_index += 1;
+
+                let mut key = match protobuf::decode_fields(data) {
+                    Ok(key) => key,
+                    Err(_) => continue
+                };
+                let location_name = format!("GameEvent {} ({})", event_name, location.describe());
+                let mut b_key_changed = false;
+                for key_field in &mut key {
+                    match (key_field.field_number, &mut key_field.message) {
+                        (game_event_key_field::VAL_STRING, _) => {
+                            b_key_changed |= substitute_string(&mut key_field.message, substitutions, &location_name, &key_name, changes);
+                        },
+                        (game_event_key_field::VAL_UINT64, WireMessage::VarInt(xuid)) => {
+                            let new = substitutions.by_xuid.get(xuid).map(|&i| substitutions.identities[i].xuid);
+                            if let Some(new) = new.filter(|new| new != xuid) {
+                                changes.push(FieldChange {
+                                    location: location_name.clone(),
+                                    field: key_name.clone(),
+                                    old: xuid.to_string(),
+                                    new: new.to_string(),
+                                });
+                                *xuid = new;
+                                b_key_changed = true;
+                            }
+                        },
+                        _ => {}
+                    }
+                }
+                if b_key_changed {
+                    *data = protobuf::encode_fields(&key);
+                    b_changed = true;
+                }
+            }
+            if b_changed {
+                message.set_fields(&fields);
+            }
+        }
+        Ok(())
+    }
+}
+
+// replaces names and ids in a string field, returns true if it changed.
+fn substitute_string(
+    message: &mut WireMessage,
+    substitutions: &Substitutions,
+    location: &str,
+    field: &str,
+    changes: &mut Vec<FieldChange>
+) -> bool {
+    let value = match message {
+        WireMessage::Length(v) => v,
+        _ => return false
+    };
+    let text = match std::str::from_utf8(value) {
+        Ok(text) => text,
+        Err(_) => return false
+    };
+    match substitutions.text(text) {
+        Some(new) => {
+            changes.push(FieldChange {
+                location: location.to_owned(),
+                field: field.to_owned(),
+                old: text.to_owned(),
+                new: new.clone(),
+            });
+            *value = new.into_bytes();
+            true
+        },
+        None => false
+    }
+}
+
+// event id -> (event name, key names) from the GameEventList.
+fn game_event_descriptors(document: &DemoDocument) -> HashMap<u64, (String, Vec<String>)> {
+    let mut descriptors = HashMap::new();
+    let location: Option<MessageLocation> = document.message_locations(net_message_id::GAME_EVENT_LIST).first().copied();
+    let fields = match location.and_then(|l| document.message(l)).map(|m| m.fields()) {
+        Some(Ok(fields)) => fields,
+        _ => return descriptors
+    };
+
+    for field in fields.iter().filter(|f| f.field_number == game_event_list_field::DESCRIPTORS) {
+        let descriptor = match &field.message {
+            WireMessage::Length(data) => protobuf::decode_fields(data).unwrap_or_default(),
+            _ => continue
+        };
+        let mut event_id = None;
+        let mut name = String::new();
+        let mut keys = Vec::new();
+        for descriptor_field in &descriptor {
+            match (descriptor_field.field_number, &descriptor_field.message) {
+                (game_event_list_field::EVENT_ID, WireMessage::VarInt(v)) => event_id = Some(*v),
+                (game_event_list_field::NAME, WireMessage::Length(v)) => name = String::from_utf8_lossy(v).to_string(),
+                (game_event_list_field::KEYS, WireMessage::Length(v)) => keys.push(key_name(v)),
+                _ => {}
+            }
+        }
+        if let Some(event_id) = event_id {
+            descriptors.insert(event_id, (name, keys));
+        }
+    }
+    descriptors
+}
+
+fn key_name(data: &[u8]) -> String {
+    let fields: Vec<ProtobufMessage> = protobuf::decode_fields(data).unwrap_or_default();
+    fields.iter().find_map(|f| match (f.field_number, &f.message) {
+        (game_event_list_field::KEY_NAME, WireMessage::Length(v)) => Some(String::from_utf8_lossy(v).to_string()),
+        _ => None
+    }).unwrap_or_default()
+}
\ No newline at end of file
@@ -0,0 +1,173 @@
+use std::{ cmp::Ordering, iter::Peekable, path::Path, str::Chars };
+
+use crate::document::{ DemoDocument, DocumentFrame, FrameBody, string_table };
+
+// appends the next part of a recording that was split over several demos. the
+// first part's sign on data is kept, the next part's frames are shifted to run on
+// after the first's, and its SyncTick and Stop frames are dropped except the last Stop.
+// the next part's sign on StringTables frames are moved to its first tick so the
+// players that joined between the parts are still known.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Concatenate;
+
+#[derive(Debug, Clone, Default)]
+pub struct ConcatPart {
+    pub frames_appended: usize,
+    pub tick_offset: i32,
+}
+
+impl Concatenate {
+    // the parts must be from the same server, map and game version.
+    pub fn check(first: &DemoDocument, next: &DemoDocument) -> Result<(), String> {
+        let (a, b) = (&first.header, &next.header);
+        let checks = [
+            ("map", &a.map_name, &b.map_name),
+            ("server", &a.server_name, &b.server_name),
+            ("game directory", &a.game_directory, &b.game_directory),
+        ];
+        for (name, first_value, next_value) in checks {
+            if first_value != next_value {
+                return Err(format!("the {} doesn't match, '{}' and '{}'", name, first_value, next_value))
+            }
+        }
+        if a.demo_protocol != b.demo_protocol || a.network_protocol != b.network_protocol {
+            return Err(format!(
+                "the protocols don't match, demo {} network {} and demo {} network {}",
+                a.demo_protocol, a.network_protocol, b.demo_protocol, b.network_protocol
+            ))
+        }
+        for (part, document) in [("first", first), ("next", next)] {
+            if !document.trailing_data.is_empty() {
+                return Err(format!("the {} part is cut off, repair it first", part))
+            }
+        }
+        Ok(())
+    }
+
+    // without a StringTables frame the next part's tables can't be carried over, they
+    // have to match the tables at the end of the demo it's appended to.
+    fn check_string_tables(first: &DemoDocument, next: &DemoDocument) -> Result<(), String> {
+        let read_error = |e| format!("couldn't read the string tables: {}", e);
+        let first_updates = string_table::string_table_updates(first).map_err(read_error)?;
+        let tables = string_table::string_tables_at(&first_updates, i32::MAX);
+        let next_updates: Vec<_> = string_table::string_table_updates(next)
+            .map_err(read_error)?
+            .into_iter()
+            .filter(|u| u.is_sign_on())
+            .collect();
+        for next_table in string_table::string_tables_at(&next_updates, i32::MIN) {
+            match tables.iter().find(|t| t.name == next_table.name) {
+                Some(table) if table.entries == next_table.entries => {},
+                _ => return Err(format!(
+                    "the '{}' string table changed between the parts and the next part has no StringTables frame to carry it over",
+                    next_table.name
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    // tick_interval is used for the header's playback time, see DemoDocument::update_header_counts.
+    pub fn apply(&self, document: &mut DemoDocument, next: DemoDocument, tick_interval: f32) -> Result<ConcatPart, String> {
+        Self::check(document, &next)?;
+        let string_tables: Vec<DocumentFrame> = next.sign_on_frames
+            .iter()
+            .filter(|f| matches!(f.body, FrameBody::StringTables(_)))
+            .cloned()
+            .collect();
+        if string_tables.is_empty() {
+            Self::check_string_tables(document, &next)?;
+        }
+
+        while matches!(document.frames.last().map(|f| &f.body), Some(FrameBody::Stop)) {
+            document.frames.pop();
+        }
+        let last_tick = document.frames.iter().map(|f| f.tick).max().unwrap_or(0);
+
+        let frames: Vec<DocumentFrame> = next.frames
+            .into_iter()
+            .filter(|f| !matches!(f.body, FrameBody::SyncTick | FrameBody::Stop))
+            .collect();
+        let first_tick = match frames.iter().map(|f| f.tick).min() {
+            Some(tick) => tick,
+            None => return Err("the next part has no frames".into())
+        };
+        let tick_offset = last_tick + 1 - first_tick;
+
+        let frames_appended = frames.len() + string_tables.len();
+        let string_tables = string_tables.into_iter().map(|mut frame| {
+            frame.tick = first_tick;
+            frame
+        });
+        document.frames.extend(string_tables.chain(frames).map(|mut frame| {
+            frame.tick += tick_offset;
+            frame
+        }));
+        let tick = document.frames.iter().map(|f| f.tick).max().unwrap_or(0);
+        document.frames.push(DocumentFrame {
+            tick,
+            player_slot: 0,
+            body: FrameBody::Stop,
+        });
+        document.update_header_counts(tick_interval);
+
+        Ok(ConcatPart { frames_appended, tick_offset })
+    }
+}
+
+// orders the parts of a split recording by name with the numbers in it compared by
+// value, so x_part2.dem comes before x_part10.dem.
+pub fn part_order(a: &Path, b: &Path) -> Ordering {
+    let (a, b) = (a.to_string_lossy(), b.to_string_lossy());
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        let ordering = match (a.peek(), b.peek()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let (x, y) = (take_number(&mut a), take_number(&mut b));
+                // without leading zeros the longer number is the bigger one
+                let (x, y) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                x.len().cmp(&y.len()).then_with(|| x.cmp(y))
+            },
+            (Some(x), Some(y)) => {
+                let ordering = x.cmp(y);
+                a.next();
+                b.next();
+                ordering
+            }
+        };
+        if ordering != Ordering::Equal {
+            return ordering
+        }
+    }
+}
+
+fn take_number(chars: &mut Peekable<Chars>) -> String {
+    let mut number = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+        number.push(c);
+    }
+    number
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parts_are_in_number_order() {
+        let cases = [
+            ("x_part2.dem", "x_part10.dem", Ordering::Less),
+            ("x_part10.dem", "x_part9.dem", Ordering::Greater),
+            ("x_part02.dem", "x_part2.dem", Ordering::Equal),
+            ("x_part1.dem", "x_part1b.dem", Ordering::Less),
+            ("a/match_3.dem", "b/match_1.dem", Ordering::Less),
+            ("match.dem", "match_1.dem", Ordering::Less),
+        ];
+        for (a, b, ordering) in cases {
+            assert_eq!(part_order(Path::new(a), Path::new(b)), ordering, "{} and {}", a, b);
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,57 @@
+use crate::document::{ DemoDocument, DocumentFrame, FrameBody, command_id };
+
+// the demo player reads console commands into a 1024 byte buffer, nul included
+const MAX_COMMAND_LENGTH: usize = 1023;
+
+// a ConsoleCmd frame to add to a demo, it runs when playback reaches its tick.
+#[derive(Debug, Clone)]
+pub struct InsertConsoleCommand {
+    pub tick: i32,
+    pub player_slot: u8,
+    pub command: String,
+}
+
+impl InsertConsoleCommand {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.command.trim().is_empty() {
+            return Err("the command can't be empty".into())
+        }
+        if self.command.len() > MAX_COMMAND_LENGTH {
+            return Err(format!("the command can't be longer than {} bytes", MAX_COMMAND_LENGTH))
+        }
+        if self.command.contains(['\0', '\n', '\r']) {
+            return Err("the command can't contain nul characters or line breaks".into())
+        }
+        Ok(())
+    }
+
+    // index in frames the command goes at: after every frame up to its tick, before the Stop frame.
+    fn position(&self, frames: &[DocumentFrame]) -> Result<usize, String> {
+        let first_tick = frames.iter().map(|f| f.tick).min().unwrap_or(0);
+        let last_tick = frames.iter().map(|f| f.tick).max().unwrap_or(0);
+        let stop_index = frames.iter().position(|f| f.command() == command_id::STOP).unwrap_or(frames.len());
+        if self.tick < first_tick || self.tick > last_tick {
+            return Err(format!("tick {} is outside of the demo (ticks {} to {})", self.tick, first_tick, last_tick))
+        }
+
+        Ok(frames[..stop_index]
+            .iter()
+            .position(|f| f.tick > self.tick)
+            .unwrap_or(stop_index))
+    }
+
+    // returns the index of the new frame.
+    pub fn apply(&self, document: &mut DemoDocument) -> Result<usize, String> {
+        self.validate()?;
+        let index = self.position(&document.frames)?;
+
+        let mut data = self.command.as_bytes().to_vec();
+        data.push(0);
+        document.frames.insert(index, DocumentFrame {
+            tick: self.tick,
+            player_slot: self.player_slot,
+            body: FrameBody::ConsoleCmd(data),
+        });
+        Ok(index)
+    }
+}
\ No newline at end of file
@@ -0,0 +1,100 @@
+use source_demo_tool::demo_file::header::DemoHeader;
+
+use super::FieldChange;
+use crate::document::{ DemoDocument, MAX_HEADER_STRING_LENGTH };
+
+// new values for the editable header fields. the protocols and sign on length
+// aren't editable, the sign on length is recomputed when the demo is written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaderEdit {
+    pub client_name: String,
+    pub server_name: String,
+    pub map_name: String,
+    pub game_directory: String,
+    pub playback_time: f32,
+    pub ticks: i32,
+    pub frames: i32,
+}
+
+impl HeaderEdit {
+    pub fn from_header(header: &DemoHeader) -> Self {
+        Self {
+            client_name: header.client_name.clone(),
+            server_name: header.server_name.clone(),
+            map_name: header.map_name.clone(),
+            game_directory: header.game_directory.clone(),
+            playback_time: header.playback_time,
+            ticks: header.ticks,
+            frames: header.frames,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let strings = [
+            ("client name", &self.client_name),
+            ("server name", &self.server_name),
+            ("map name", &self.map_name),
+            ("game directory", &self.game_directory),
+        ];
+        for (name, value) in strings {
+            if value.len() > MAX_HEADER_STRING_LENGTH {
+                return Err(format!("the {} can't be longer than {} bytes", name, MAX_HEADER_STRING_LENGTH))
+            }
+            if value.contains('\0') {
+                return Err(format!("the {} can't contain nul characters", name))
+            }
+        }
+        if self.map_name.is_empty() {
+            return Err("the map name can't be empty".into())
+        }
+        if !self.playback_time.is_finite() || self.playback_time < 0.0 {
+            return Err("the playback time must be a positive number of seconds".into())
+        }
+        if self.ticks < 0 {
+            return Err("the ticks can't be negative".into())
+        }
+        if self.frames < 0 {
+            return Err("the frames can't be negative".into())
+        }
+        Ok(())
+    }
+
+    // the fields that differ from header.
+    pub fn changes(&self, header: &DemoHeader) -> Vec<FieldChange> {
+        let fields = [
+            ("client_name", header.client_name.clone(), self.client_name.clone()),
+            ("server_name", header.server_name.clone(), self.server_name.clone()),
+            ("map_name", header.map_name.clone(), self.map_name.clone()),
+            ("game_directory", header.game_directory.clone(), self.game_directory.clone()),
+            ("playback_time", header.playback_time.to_string(), self.playback_time.to_string()),
+            ("ticks", header.ticks.to_string(), self.ticks.to_string()),
+            ("frames", header.frames.to_string(), self.frames.to_string()),
+        ];
+        fields.into_iter()
+            .filter(|(_, old, new)| old != new)
+            .map(|(field, old, new)| FieldChange {
+                location: "Header".into(),
+                field: field.into(),
+                old,
+                new,
+            })
+            .collect()
+    }
+
+    // returns the changes made, see changes, and the edit that puts the old values back.
+    pub fn apply(&self, document: &mut DemoDocument) -> Result<(Vec<FieldChange>, HeaderEdit), String> {
+        self.validate()?;
+        let changes = self.changes(&document.header);
+        let undo = Self::from_header(&document.header);
+
+        let header = &mut document.header;
+        header.client_name = self.client_name.clone();
+        header.server_name = self.server_name.clone();
+        header.map_name = self.map_name.clone();
+        header.game_directory = self.game_directory.clone();
+        header.playback_time = self.playback_time;
+        header.ticks = self.ticks;
+        header.frames = self.frames;
+        Ok((changes, undo))
+    }
+}
\ No newline at end of file
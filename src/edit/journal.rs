@@ -0,0 +1,294 @@
+use std::cmp::Ordering;
+
+use super::{
+    console_command::InsertConsoleCommand,
+    header::HeaderEdit,
+    protobuf_field::{ MessageTarget, ProtobufFieldEdit },
+};
+use crate::document::{ DemoDocument, DocumentFrame, FrameBody, MessageLocation, RawMessage };
+
+// a single change to an open document, every edit made in the gui is one of these
+// so it can be undone and written to a patch.
+#[derive(Debug, Clone)]
+pub enum DocumentEdit {
+    Header(HeaderEdit),
+    ProtobufField(ProtobufFieldEdit),
+    // becomes an InsertFrame once applied
+    ConsoleCommand(InsertConsoleCommand),
+    InsertFrame { sign_on: bool, index: usize, frame: DocumentFrame },
+    RemoveFrame { sign_on: bool, index: usize },
+    InsertMessage { location: MessageLocation, message: RawMessage },
+    RemoveMessage { location: MessageLocation },
+}
+
+// an edit as it was applied: edit is what was done to the document, undo puts it back.
+#[derive(Debug, Clone)]
+pub struct JournalEntry {
+    pub description: String,
+    pub edit: DocumentEdit,
+    pub undo: DocumentEdit,
+}
+
+impl DocumentEdit {
+    pub fn apply(&self, document: &mut DemoDocument) -> Result<JournalEntry, String> {
+        match self {
+            DocumentEdit::Header(edit) => {
+                let (changes, undo) = edit.apply(document)?;
+                let fields: Vec<String> = changes
+                    .iter()
+                    .map(|c| format!("{}: {} → {}", c.field, c.old, c.new))
+                    .collect();
+                let description = match fields.is_empty() {
+                    true => "Header: no changes".to_owned(),
+                    false => format!("Header {}", fields.join(", "))
+                };
+                Ok(JournalEntry {
+                    description,
+                    edit: self.clone(),
+                    undo: DocumentEdit::Header(undo),
+                })
+            },
+            DocumentEdit::ProtobufField(edit) => {
+                let (change, undo) = edit.apply(document)?;
+                Ok(JournalEntry {
+                    description: format!("{} {}: {} → {}", change.location, change.field, change.old, change.new),
+                    edit: self.clone(),
+                    undo: DocumentEdit::ProtobufField(undo),
+                })
+            },
+            DocumentEdit::ConsoleCommand(insert) => {
+                let index = insert.apply(document)?;
+                Ok(JournalEntry {
+                    description: format!("Insert {}", describe_frame(&document.frames[index], false, index)),
+                    edit: DocumentEdit::InsertFrame { sign_on: false, index, frame: document.frames[index].clone() },
+                    undo: DocumentEdit::RemoveFrame { sign_on: false, index },
+                })
+            },
+            DocumentEdit::InsertFrame { sign_on, index, frame } => {
+                let frames = frames_mut(document, *sign_on);
+                if *index > frames.len() {
+                    return Err(format!("{} is past the end of the demo", frame_name(*sign_on, *index)))
+                }
+                frames.insert(*index, frame.clone());
+                Ok(JournalEntry {
+                    description: format!("Insert {}", describe_frame(frame, *sign_on, *index)),
+                    edit: self.clone(),
+                    undo: DocumentEdit::RemoveFrame { sign_on: *sign_on, index: *index },
+                })
+            },
+            DocumentEdit::RemoveFrame { sign_on, index } => {
+                let frames = frames_mut(document, *sign_on);
+                if *index >= frames.len() {
+                    return Err(format!("there is no {}", frame_name(*sign_on, *index)))
+                }
+                let frame = frames.remove(*index);
+                Ok(JournalEntry {
+                    description: format!("Remove {}", describe_frame(&frame, *sign_on, *index)),
+                    edit: self.clone(),
+                    undo: DocumentEdit::InsertFrame { sign_on: *sign_on, index: *index, frame },
+                })
+            },
+            DocumentEdit::InsertMessage { location, message } => {
+                let messages = messages_mut(document, *location)?;
+                if location.message_index > messages.len() {
+                    return Err(format!("{} is past the end of the packet", location.describe()))
+                }
+                messages.insert(location.message_index, message.clone());
+                Ok(JournalEntry {
+                    description: format!("Insert message {} at {}", message.id, location.describe()),
+                    edit: self.clone(),
+                    undo: DocumentEdit::RemoveMessage { location: *location },
+                })
+            },
+            DocumentEdit::RemoveMessage { location } => {
+                let messages = messages_mut(document, *location)?;
+                if location.message_index >= messages.len() {
+                    return Err(format!("there is no message at {}", location.describe()))
+                }
+                let message = messages.remove(location.message_index);
+                Ok(JournalEntry {
+                    description: format!("Remove message {} from {}", message.id, location.describe()),
+                    edit: self.clone(),
+                    undo: DocumentEdit::InsertMessage { location: *location, message },
+                })
+            },
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            DocumentEdit::Header(_) => "Header",
+            DocumentEdit::ProtobufField(_) => "ProtobufField",
+            DocumentEdit::ConsoleCommand(_) => "ConsoleCommand",
+            DocumentEdit::InsertFrame { .. } => "InsertFrame",
+            DocumentEdit::RemoveFrame { .. } => "RemoveFrame",
+            DocumentEdit::InsertMessage { .. } => "InsertMessage",
+            DocumentEdit::RemoveMessage { .. } => "RemoveMessage",
+        }
+    }
+
+    // where the frame at index is after this edit was applied, None if the edit removed it.
+    // ConsoleCommand edits haven't been placed yet and don't move anything.
+    pub fn shift_frame_index(&self, sign_on: bool, index: usize) -> Option<usize> {
+        match self {
+            DocumentEdit::InsertFrame { sign_on: s, index: at, .. } if *s == sign_on && index >= *at => Some(index + 1),
+            DocumentEdit::RemoveFrame { sign_on: s, index: at } if *s == sign_on => match index.cmp(at) {
+                Ordering::Less => Some(index),
+                Ordering::Equal => None,
+                Ordering::Greater => Some(index - 1),
+            },
+            _ => Some(index)
+        }
+    }
+
+    // where the message at location is after this edit was applied, None if the edit removed it.
+    pub fn shift_location(&self, location: MessageLocation) -> Option<MessageLocation> {
+        let frame_index = self.shift_frame_index(location.sign_on, location.frame_index)?;
+        let message_index = match self {
+            DocumentEdit::InsertMessage { location: at, .. }
+                if same_frame(at, &location) && location.message_index >= at.message_index => location.message_index + 1,
+            DocumentEdit::RemoveMessage { location: at } if same_frame(at, &location) => {
+                match location.message_index.cmp(&at.message_index) {
+                    Ordering::Less => location.message_index,
+                    Ordering::Equal => return None,
+                    Ordering::Greater => location.message_index - 1,
+                }
+            },
+            _ => location.message_index
+        };
+        Some(MessageLocation { sign_on: location.sign_on, frame_index, message_index })
+    }
+
+    pub fn shift_target(&self, target: MessageTarget) -> Option<MessageTarget> {
+        Some(MessageTarget {
+            location: self.shift_location(target.location)?,
+            user_message: target.user_message,
+        })
+    }
+
+    // whether the edit adds or removes frames or messages, moving the ones after it.
+    pub fn moves_messages(&self) -> bool {
+        matches!(
+            self,
+            DocumentEdit::InsertFrame { .. } | DocumentEdit::RemoveFrame { .. }
+            | DocumentEdit::InsertMessage { .. } | DocumentEdit::RemoveMessage { .. }
+        )
+    }
+}
+
+fn same_frame(a: &MessageLocation, b: &MessageLocation) -> bool {
+    a.sign_on == b.sign_on && a.frame_index == b.frame_index
+}
+
+fn frames_mut(document: &mut DemoDocument, sign_on: bool) -> &mut Vec<DocumentFrame> {
+    if sign_on { &mut document.sign_on_frames } else { &mut document.frames }
+}
+
+fn messages_mut(document: &mut DemoDocument, location: MessageLocation) -> Result<&mut Vec<RawMessage>, String> {
+    let frame = frames_mut(document, location.sign_on).get_mut(location.frame_index);
+    match frame.map(|f| &mut f.body) {
+        Some(FrameBody::SignOn(packet) | FrameBody::Packet(packet)) => Ok(&mut packet.messages),
+        _ => Err(format!("{} is not a packet", frame_name(location.sign_on, location.frame_index)))
+    }
+}
+
+fn frame_name(sign_on: bool, index: usize) -> String {
+    format!("{} {}", if sign_on { "sign on frame" } else { "frame" }, index + 1)
+}
+
+fn describe_frame(frame: &DocumentFrame, sign_on: bool, index: usize) -> String {
+    match frame.console_command() {
+        Some(command) => format!("ConsoleCmd '{}' at tick {} ({})", command, frame.tick, frame_name(sign_on, index)),
+        None => format!("{} at tick {} ({})", frame.command_name(), frame.tick, frame_name(sign_on, index))
+    }
+}
+
+// the edits made to an open document, in order. undoing moves the last edit to
+// the undone list, any new edit clears it.
+pub struct EditJournal {
+    done: Vec<JournalEntry>,
+    undone: Vec<JournalEntry>,
+    // how many edits were done when the document was last saved, None once that
+    // state can't be reached by undoing or redoing anymore
+    saved_at: Option<usize>,
+}
+
+impl Default for EditJournal {
+    fn default() -> Self {
+        Self {
+            done: Vec::new(),
+            undone: Vec::new(),
+            saved_at: Some(0),
+        }
+    }
+}
+
+impl EditJournal {
+    pub fn apply(&mut self, document: &mut DemoDocument, edit: &DocumentEdit) -> Result<&JournalEntry, String> {
+        let entry = edit.apply(document)?;
+        if matches!(self.saved_at, Some(saved_at) if saved_at > self.done.len()) {
+            self.saved_at = None;
+        }
+        self.undone.clear();
+        self.done.push(entry);
+        Ok(self.done.last().unwrap())
+    }
+
+    // undoes the last edit, returning the edit made to the document. None if there's nothing to undo.
+    pub fn undo(&mut self, document: &mut DemoDocument) -> Option<Result<DocumentEdit, String>> {
+        let entry = self.done.pop()?;
+        match entry.undo.apply(document) {
+            Ok(applied) => {
+                self.undone.push(entry);
+                Some(Ok(applied.edit))
+            },
+            Err(e) => {
+                self.done.push(entry);
+                Some(Err(e))
+            }
+        }
+    }
+
+    // redoes the last undone edit, see undo.
+    pub fn redo(&mut self, document: &mut DemoDocument) -> Option<Result<DocumentEdit, String>> {
+        let entry = self.undone.pop()?;
+        match entry.edit.apply(document) {
+            Ok(applied) => {
+                self.done.push(entry);
+                Some(Ok(applied.edit))
+            },
+            Err(e) => {
+                self.undone.push(entry);
+                Some(Err(e))
+            }
+        }
+    }
+
+    // oldest first
+    pub fn done(&self) -> &[JournalEntry] {
+        &self.done
+    }
+
+    // the next to redo is last
+    pub fn undone(&self) -> &[JournalEntry] {
+        &self.undone
+    }
+
+    pub fn edits(&self) -> Vec<DocumentEdit> {
+        self.done.iter().map(|e| e.edit.clone()).collect()
+    }
+
+    // how many edits the document is away from the last save
+    pub fn unsaved_count(&self) -> usize {
+        match self.saved_at {
+            Some(saved_at) => saved_at.abs_diff(self.done.len()),
+            None => self.done.len().max(1),
+        }
+    }
+
+    // done_count is done().len() when the document that was saved was taken, edits made
+    // while it was being written stay unsaved
+    pub fn mark_saved(&mut self, done_count: usize) {
+        self.saved_at = Some(done_count);
+    }
+}
\ No newline at end of file
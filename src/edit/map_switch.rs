@@ -0,0 +1,98 @@
+use source_demo_tool::protobuf_message::WireMessage;
+
+use super::FieldChange;
+use crate::document::{ DemoDocument, MAX_HEADER_STRING_LENGTH, net_message_id };
+
+mod server_info_field {
+    pub const MAP_CRC: u8 = 8;
+    pub const MAP_NAME: u8 = 16;
+}
+
+// rewrites the map a demo was recorded on, in the header and every ServerInfo.
+// map_crc replaces the ServerInfo map CRC when set and the message has one.
+#[derive(Debug, Clone)]
+pub struct MapSwitch {
+    pub map_name: String,
+    pub map_crc: Option<u32>,
+}
+
+impl MapSwitch {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.map_name.is_empty() {
+            return Err("the map name can't be empty".into())
+        }
+        if self.map_name.len() > MAX_HEADER_STRING_LENGTH {
+            return Err(format!("the map name can't be longer than {} bytes", MAX_HEADER_STRING_LENGTH))
+        }
+        if self.map_name.contains('\0') {
+            return Err("the map name can't contain nul characters".into())
+        }
+        Ok(())
+    }
+
+    // every value the switch touches with its current and new value.
+    pub fn changes(&self, document: &DemoDocument) -> Vec<FieldChange> {
+        let mut changes = vec![FieldChange {
+            location: "Header".into(),
+            field: "map_name".into(),
+            old: document.header.map_name.clone(),
+            new: self.map_name.clone(),
+        }];
+
+        for location in document.message_locations(net_message_id::SERVER_INFO) {
+            let fields = match document.message(location).map(|m| m.fields()) {
+                Some(Ok(fields)) => fields,
+                _ => continue
+            };
+            for field in &fields {
+                match (field.field_number, &field.message) {
+                    (server_info_field::MAP_NAME, WireMessage::Length(v)) => changes.push(FieldChange {
+                        location: format!("ServerInfo ({})", location.describe()),
+                        field: "map_name".into(),
+                        old: String::from_utf8_lossy(v).to_string(),
+                        new: self.map_name.clone(),
+                    }),
+                    (server_info_field::MAP_CRC, WireMessage::Fixed32(v)) => changes.push(FieldChange {
+                        location: format!("ServerInfo ({})", location.describe()),
+                        field: "map_crc".into(),
+                        old: format!("0x{:08x}", v),
+                        new: format!("0x{:08x}", self.map_crc.unwrap_or(*v)),
+                    }),
+                    _ => {}
+                }
+            }
+        }
+        changes
+    }
+
+    // returns the changes made, see changes.
+    pub fn apply(&self, document: &mut DemoDocument) -> Result<Vec<FieldChange>, String> {
+        self.validate()?;
+        let changes = self.changes(document);
+
+        document.header.map_name = self.map_name.clone();
+        for location in document.message_locations(net_message_id::SERVER_INFO) {
+            let message = match document.message_mut(location) {
+                Some(m) => m,
+                None => continue
+            };
+            let mut fields = match message.fields() {
+                Ok(fields) => fields,
+                Err(e) => return Err(format!("couldn't decode ServerInfo ({}): {}", location.describe(), e))
+            };
+            for field in &mut fields {
+                match (field.field_number, &mut field.message) {
+                    (server_info_field::MAP_NAME, WireMessage::Length(v)) => *v = self.map_name.as_bytes().to_vec(),
+                    (server_info_field::MAP_CRC, WireMessage::Fixed32(v)) => {
+                        if let Some(crc) = self.map_crc {
+                            *v = crc;
+                        }
+                    },
+                    _ => {}
+                }
+            }
+            message.set_fields(&fields);
+        }
+        Ok(changes)
+    }
+}
\ No newline at end of file
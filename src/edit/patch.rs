@@ -0,0 +1,695 @@
+use std::collections::BTreeSet;
+
+use serde_json::{ json, Map, Value };
+use source_demo_tool::{
+    demo_file::packet::{ netmessage::NetMessage, usermessage::UserMessage },
+    protobuf_message::ProtobufMessageEnumTraits,
+};
+
+use super::{
+    anonymize::{ Anonymize, ReplaceNames },
+    console_command::InsertConsoleCommand,
+    header::HeaderEdit,
+    journal::DocumentEdit,
+    map_switch::MapSwitch,
+    protobuf_field::{ FieldValue, MessageTarget, ProtobufFieldEdit },
+    strip::{ MessageCounts, StripMessages },
+    trim::Trim,
+};
+use crate::document::{ DemoDocument, DocumentFrame, FrameBody, MessageLocation, RawMessage };
+
+pub const PATCH_FORMAT: &str = "source-demo-crawler patch";
+pub const PATCH_VERSION: u64 = 1;
+
+// a patch is a json (or toml) object listing edits in the order they're applied, frame
+// and message indices are document (file order) indices starting at 0:
+//   { "format": "source-demo-crawler patch", "version": 1, "edits": [
+//     { "op": "set_field", "sign_on": false, "frame_index": 12, "message_index": 3,
+//       "user_message": false, "field": "tick", "type": "varint", "value": 100 },
+//     { "op": "insert_console_command", "tick": 100, "player_slot": 0, "command": "say hi" } ] }
+// format and version are optional. besides the edits the history exports there are
+// rules that work on any demo: map_switch, strip_messages, replace_names, trim and
+// anonymize. a rule that matches nothing in a demo fails the patch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchSyntax {
+    Json,
+    Toml,
+}
+
+impl PatchSyntax {
+    pub fn from_extension(extension: &str) -> Self {
+        if extension.eq_ignore_ascii_case("toml") {
+            PatchSyntax::Toml
+        } else {
+            PatchSyntax::Json
+        }
+    }
+}
+
+// a header op only sets the fields it has.
+#[derive(Debug, Clone, Default)]
+pub struct HeaderFields {
+    pub client_name: Option<String>,
+    pub server_name: Option<String>,
+    pub map_name: Option<String>,
+    pub game_directory: Option<String>,
+    pub playback_time: Option<f32>,
+    pub ticks: Option<i32>,
+    pub frames: Option<i32>,
+}
+
+#[derive(Debug, Clone)]
+pub enum PatchOp {
+    Edit(DocumentEdit),
+    Header(HeaderFields),
+    MapSwitch(MapSwitch),
+    StripMessages(StripMessages),
+    ReplaceNames(ReplaceNames),
+    Trim(Trim),
+    Anonymize,
+}
+
+impl PatchOp {
+    // returns a summary of what changed.
+    pub fn apply(&self, document: &mut DemoDocument) -> Result<String, String> {
+        match self {
+            PatchOp::Edit(edit) => Ok(edit.apply(document)?.description),
+            PatchOp::Header(fields) => {
+                let header = &document.header;
+                let edit = HeaderEdit {
+                    client_name: fields.client_name.clone().unwrap_or_else(|| header.client_name.clone()),
+                    server_name: fields.server_name.clone().unwrap_or_else(|| header.server_name.clone()),
+                    map_name: fields.map_name.clone().unwrap_or_else(|| header.map_name.clone()),
+                    game_directory: fields.game_directory.clone().unwrap_or_else(|| header.game_directory.clone()),
+                    playback_time: fields.playback_time.unwrap_or(header.playback_time),
+                    ticks: fields.ticks.unwrap_or(header.ticks),
+                    frames: fields.frames.unwrap_or(header.frames),
+                };
+                if edit.changes(header).is_empty() {
+                    return Err("the header already has these values".to_owned())
+                }
+                Ok(DocumentEdit::Header(edit).apply(document)?.description)
+            },
+            PatchOp::MapSwitch(map_switch) => {
+                let changed = map_switch.apply(document)?
+                    .iter()
+                    .filter(|c| c.old != c.new)
+                    .count();
+                if changed == 0 {
+                    return Err(format!("the map is already {}", map_switch.map_name))
+                }
+                Ok(format!("Switched the map to {}, {} values changed", map_switch.map_name, changed))
+            },
+            PatchOp::StripMessages(strip) => {
+                let counts = MessageCounts::from_document(document);
+                let missing: Vec<String> = strip.net_message_ids
+                    .iter()
+                    .filter(|id| !counts.net_messages.contains_key(id))
+                    .map(|id| message_name(NetMessage::get_id_map(), *id))
+                    .chain(strip.user_message_ids
+                        .iter()
+                        .filter(|id| !counts.user_messages.contains_key(id))
+                        .map(|id| message_name(UserMessage::get_id_map(), *id)))
+                    .collect();
+                if !missing.is_empty() {
+                    return Err(format!("the demo has no {} messages", missing.join(", ")))
+                }
+                let report = strip.apply(document)?;
+                Ok(format!(
+                    "Removed {} messages from {} packets ({} bytes)",
+                    report.messages_removed, report.packets_changed, report.bytes_removed
+                ))
+            },
+            PatchOp::ReplaceNames(replace) => {
+                let changes = replace.apply(document)?;
+                let names: Vec<String> = replace.names
+                    .iter()
+                    .map(|(old, new)| format!("{} → {}", old, new))
+                    .collect();
+                Ok(format!("Renamed {}, {} values changed", names.join(", "), changes.len()))
+            },
+            PatchOp::Trim(trim) => {
+                let tick_interval = document.tick_interval();
+                let report = trim.apply(document, tick_interval)?;
+                Ok(format!(
+                    "Trimmed to ticks {} to {}, kept {} frames and removed {}",
+                    report.start_tick, trim.end_tick, report.frames_kept, report.frames_removed
+                ))
+            },
+            PatchOp::Anonymize => {
+                let changes = Anonymize.apply(document)?;
+                Ok(format!("Anonymized the players, {} values changed", changes.len()))
+            },
+        }
+    }
+}
+
+fn message_name(names: std::collections::BTreeMap<usize, &'static str>, id: u64) -> String {
+    match names.get(&(id as usize)) {
+        Some(name) => name.to_string(),
+        None => format!("Unknown ({})", id)
+    }
+}
+
+pub fn patch_to_json(edits: &[DocumentEdit]) -> Result<Value, String> {
+    let edits = edits.iter().map(edit_to_json).collect::<Result<Vec<Value>, String>>()?;
+    Ok(json!({
+        "format": PATCH_FORMAT,
+        "version": PATCH_VERSION,
+        "edits": edits,
+    }))
+}
+
+fn edit_to_json(edit: &DocumentEdit) -> Result<Value, String> {
+    let value = match edit {
+        DocumentEdit::Header(header) => json!({
+            "op": "header",
+            "client_name": header.client_name,
+            "server_name": header.server_name,
+            "map_name": header.map_name,
+            "game_directory": header.game_directory,
+            "playback_time": header.playback_time,
+            "ticks": header.ticks,
+            "frames": header.frames,
+        }),
+        DocumentEdit::ProtobufField(field_edit) => {
+            let (value_type, value) = match &field_edit.value {
+                FieldValue::VarInt(v) => ("varint", json!(v)),
+                FieldValue::Fixed32(v) => ("fixed32", json!(v)),
+                FieldValue::Float32(v) => ("float32", json!(v)),
+                FieldValue::String(v) => ("string", json!(v)),
+                FieldValue::Length(v) => ("bytes", json!(to_hex(v))),
+            };
+            let mut object = location_to_json("set_field", field_edit.target.location);
+            object.insert("user_message".into(), json!(field_edit.target.user_message));
+            object.insert("field".into(), json!(field_edit.field));
+            object.insert("type".into(), json!(value_type));
+            object.insert("value".into(), value);
+            Value::Object(object)
+        },
+        DocumentEdit::ConsoleCommand(insert) => json!({
+            "op": "insert_console_command",
+            "tick": insert.tick,
+            "player_slot": insert.player_slot,
+            "command": insert.command,
+        }),
+        // only console commands can be inserted from the gui, other frames are
+        // inserted by undoing their removal and can't be written out
+        DocumentEdit::InsertFrame { sign_on, index, frame } => match frame.console_command() {
+            Some(command) => json!({
+                "op": "insert_frame",
+                "sign_on": sign_on,
+                "frame_index": index,
+                "tick": frame.tick,
+                "player_slot": frame.player_slot,
+                "command": command,
+            }),
+            None => return Err(format!("the inserted {} frame can't be written to a patch", frame.command_name()))
+        },
+        DocumentEdit::RemoveFrame { sign_on, index } => json!({
+            "op": "remove_frame",
+            "sign_on": sign_on,
+            "frame_index": index,
+        }),
+        DocumentEdit::InsertMessage { location, message } => {
+            let mut object = location_to_json("insert_message", *location);
+            object.insert("id".into(), json!(message.id));
+            object.insert("data".into(), json!(to_hex(&message.data)));
+            Value::Object(object)
+        },
+        DocumentEdit::RemoveMessage { location } => Value::Object(location_to_json("remove_message", *location)),
+    };
+    Ok(value)
+}
+
+fn location_to_json(op: &str, location: MessageLocation) -> Map<String, Value> {
+    let mut object = Map::new();
+    object.insert("op".into(), json!(op));
+    object.insert("sign_on".into(), json!(location.sign_on));
+    object.insert("frame_index".into(), json!(location.frame_index));
+    object.insert("message_index".into(), json!(location.message_index));
+    object
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+fn from_hex(text: &str) -> Result<Vec<u8>, String> {
+    if text.len() % 2 == 1 || !text.is_ascii() {
+        return Err(format!("'{}' is not hex data", text))
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).map_err(|_| format!("'{}' is not hex data", text)))
+        .collect()
+}
+
+pub fn parse_patch(text: &str, syntax: PatchSyntax) -> Result<Vec<PatchOp>, String> {
+    let patch: Value = match syntax {
+        PatchSyntax::Json => serde_json::from_str(text).map_err(|e| format!("invalid json: {}", e))?,
+        PatchSyntax::Toml => {
+            let value: toml::Value = toml::from_str(text).map_err(|e| format!("invalid toml: {}", e))?;
+            serde_json::to_value(value).map_err(|e| e.to_string())?
+        }
+    };
+    let object = match &patch {
+        Value::Object(object) => object,
+        _ => return Err("the patch must be an object with an edits list".into())
+    };
+    for key in object.keys() {
+        if !["format", "version", "edits"].contains(&key.as_str()) {
+            return Err(format!("unknown key '{}'", key))
+        }
+    }
+    match object.get("format") {
+        None => {},
+        Some(Value::String(format)) if format == PATCH_FORMAT => {},
+        Some(_) => return Err(format!("the file is not a {} file", PATCH_FORMAT))
+    }
+    match object.get("version").map(|v| v.as_u64()) {
+        None => {},
+        Some(Some(version)) if version <= PATCH_VERSION => {},
+        Some(_) => return Err(format!("only version {} patches are supported", PATCH_VERSION))
+    }
+    let edits = match object.get("edits") {
+        Some(Value::Array(edits)) => edits,
+        _ => return Err("the patch has no edits list".into())
+    };
+    if edits.is_empty() {
+        return Err("the edits list is empty".into())
+    }
+
+    edits.iter().enumerate().map(|(i, edit)| {
+        let op = match edit.get("op").and_then(|op| op.as_str()) {
+            Some(op) => op,
+            None => return Err(format!("edit {} has no op", i + 1))
+        };
+        let fields = match edit {
+            Value::Object(fields) => OpFields { op, fields },
+            _ => return Err(format!("edit {} is not an object", i + 1))
+        };
+        fields.parse().map_err(|e| format!("edit {} ({}): {}", i + 1, op, e))
+    }).collect()
+}
+
+struct OpFields<'a> {
+    op: &'a str,
+    fields: &'a Map<String, Value>,
+}
+
+impl<'a> OpFields<'a> {
+    fn parse(&self) -> Result<PatchOp, String> {
+        let op = match self.op {
+            "header" => {
+                self.check_keys(&["client_name", "server_name", "map_name", "game_directory", "playback_time", "ticks", "frames"])?;
+                PatchOp::Header(HeaderFields {
+                    client_name: self.opt_string("client_name")?,
+                    server_name: self.opt_string("server_name")?,
+                    map_name: self.opt_string("map_name")?,
+                    game_directory: self.opt_string("game_directory")?,
+                    playback_time: self.opt_f64("playback_time")?.map(|v| v as f32),
+                    ticks: self.opt_int("ticks")?,
+                    frames: self.opt_int("frames")?,
+                })
+            },
+            "set_field" => {
+                self.check_keys(&["sign_on", "frame_index", "message_index", "user_message", "field", "type", "value"])?;
+                let value = match self.string("type")?.as_str() {
+                    "varint" => match self.value("value")? {
+                        Value::Number(n) if n.is_u64() => FieldValue::VarInt(n.as_u64().unwrap()),
+                        // negative int32/int64 values are sent as their 64 bit two's complement
+                        Value::Number(n) if n.is_i64() => FieldValue::VarInt(n.as_i64().unwrap() as u64),
+                        _ => return Err("'value' must be a whole number".into())
+                    },
+                    "fixed32" => FieldValue::Fixed32(self.u32_or_hex("value")?),
+                    "float32" => FieldValue::Float32(self.f64("value")? as f32),
+                    "string" => FieldValue::String(self.string("value")?),
+                    "bytes" => FieldValue::Length(from_hex(&self.string("value")?)?),
+                    other => return Err(format!("unknown type '{}', expected varint, fixed32, float32, string or bytes", other))
+                };
+                PatchOp::Edit(DocumentEdit::ProtobufField(ProtobufFieldEdit {
+                    target: MessageTarget {
+                        location: self.location()?,
+                        user_message: self.opt_bool("user_message")?.unwrap_or(false),
+                    },
+                    field: self.string("field")?,
+                    value,
+                }))
+            },
+            "insert_console_command" => {
+                self.check_keys(&["tick", "player_slot", "command"])?;
+                PatchOp::Edit(DocumentEdit::ConsoleCommand(self.console_command()?))
+            },
+            "insert_frame" => {
+                self.check_keys(&["sign_on", "frame_index", "tick", "player_slot", "command"])?;
+                let insert = self.console_command()?;
+                let mut data = insert.command.into_bytes();
+                data.push(0);
+                PatchOp::Edit(DocumentEdit::InsertFrame {
+                    sign_on: self.opt_bool("sign_on")?.unwrap_or(false),
+                    index: self.index("frame_index")?,
+                    frame: DocumentFrame {
+                        tick: insert.tick,
+                        player_slot: insert.player_slot,
+                        body: FrameBody::ConsoleCmd(data),
+                    },
+                })
+            },
+            "remove_frame" => {
+                self.check_keys(&["sign_on", "frame_index"])?;
+                PatchOp::Edit(DocumentEdit::RemoveFrame {
+                    sign_on: self.opt_bool("sign_on")?.unwrap_or(false),
+                    index: self.index("frame_index")?,
+                })
+            },
+            "insert_message" => {
+                self.check_keys(&["sign_on", "frame_index", "message_index", "id", "data"])?;
+                PatchOp::Edit(DocumentEdit::InsertMessage {
+                    location: self.location()?,
+                    message: RawMessage {
+                        id: self.u64("id")?,
+                        data: from_hex(&self.string("data")?)?,
+                    },
+                })
+            },
+            "remove_message" => {
+                self.check_keys(&["sign_on", "frame_index", "message_index"])?;
+                PatchOp::Edit(DocumentEdit::RemoveMessage { location: self.location()? })
+            },
+            "map_switch" => {
+                self.check_keys(&["map_name", "map_crc"])?;
+                let map_switch = MapSwitch {
+                    map_name: self.string("map_name")?,
+                    map_crc: match self.fields.contains_key("map_crc") {
+                        true => Some(self.u32_or_hex("map_crc")?),
+                        false => None
+                    },
+                };
+                map_switch.validate()?;
+                PatchOp::MapSwitch(map_switch)
+            },
+            "strip_messages" => {
+                self.check_keys(&["net_messages", "user_messages"])?;
+                let strip = StripMessages {
+                    net_message_ids: self.message_ids("net_messages", NetMessage::get_id_map())?,
+                    user_message_ids: self.message_ids("user_messages", UserMessage::get_id_map())?,
+                };
+                if strip.is_empty() {
+                    return Err("no net_messages or user_messages to strip were given".into())
+                }
+                PatchOp::StripMessages(strip)
+            },
+            "replace_names" => {
+                self.check_keys(&["names"])?;
+                let names = match self.value("names")? {
+                    Value::Object(names) => names,
+                    _ => return Err("'names' must be a table of old name = new name".into())
+                };
+                let names = names.iter().map(|(old, new)| match new.as_str() {
+                    Some(new) => Ok((old.clone(), new.to_owned())),
+                    None => Err(format!("the new name for '{}' must be a string", old))
+                }).collect::<Result<Vec<_>, String>>()?;
+                let replace = ReplaceNames { names };
+                replace.validate()?;
+                PatchOp::ReplaceNames(replace)
+            },
+            "trim" => {
+                self.check_keys(&["start_tick", "end_tick"])?;
+                let trim = Trim {
+                    start_tick: self.int("start_tick")?,
+                    end_tick: self.int("end_tick")?,
+                };
+                trim.validate()?;
+                PatchOp::Trim(trim)
+            },
+            "anonymize" => {
+                self.check_keys(&[])?;
+                PatchOp::Anonymize
+            },
+            other => return Err(format!("unknown op '{}'", other))
+        };
+        Ok(op)
+    }
+
+    // misspelt keys would otherwise be ignored silently
+    fn check_keys(&self, keys: &[&str]) -> Result<(), String> {
+        match self.fields.keys().find(|k| k.as_str() != "op" && !keys.contains(&k.as_str())) {
+            Some(key) => Err(format!("unknown key '{}'", key)),
+            None => Ok(())
+        }
+    }
+
+    fn value(&self, key: &str) -> Result<&'a Value, String> {
+        self.fields.get(key).ok_or_else(|| format!("'{}' is missing", key))
+    }
+
+    fn string(&self, key: &str) -> Result<String, String> {
+        match self.value(key)? {
+            Value::String(v) => Ok(v.clone()),
+            _ => Err(format!("'{}' must be a string", key))
+        }
+    }
+
+    fn opt_string(&self, key: &str) -> Result<Option<String>, String> {
+        match self.fields.contains_key(key) {
+            true => self.string(key).map(Some),
+            false => Ok(None)
+        }
+    }
+
+    fn opt_bool(&self, key: &str) -> Result<Option<bool>, String> {
+        match self.fields.get(key) {
+            None => Ok(None),
+            Some(Value::Bool(v)) => Ok(Some(*v)),
+            Some(_) => Err(format!("'{}' must be true or false", key))
+        }
+    }
+
+    fn u64(&self, key: &str) -> Result<u64, String> {
+        self.value(key)?.as_u64().ok_or_else(|| format!("'{}' must be a positive whole number", key))
+    }
+
+    fn index(&self, key: &str) -> Result<usize, String> {
+        Ok(self.u64(key)? as usize)
+    }
+
+    fn int(&self, key: &str) -> Result<i32, String> {
+        match self.value(key)?.as_i64().map(i32::try_from) {
+            Some(Ok(v)) => Ok(v),
+            _ => Err(format!("'{}' must be a 32 bit whole number", key))
+        }
+    }
+
+    fn opt_int(&self, key: &str) -> Result<Option<i32>, String> {
+        match self.fields.contains_key(key) {
+            true => self.int(key).map(Some),
+            false => Ok(None)
+        }
+    }
+
+    fn f64(&self, key: &str) -> Result<f64, String> {
+        self.value(key)?.as_f64().ok_or_else(|| format!("'{}' must be a number", key))
+    }
+
+    fn opt_f64(&self, key: &str) -> Result<Option<f64>, String> {
+        match self.fields.contains_key(key) {
+            true => self.f64(key).map(Some),
+            false => Ok(None)
+        }
+    }
+
+    // a number, or a "0x" prefixed hex string
+    fn u32_or_hex(&self, key: &str) -> Result<u32, String> {
+        let parsed = match self.value(key)? {
+            Value::String(v) => v.strip_prefix("0x").and_then(|hex| u32::from_str_radix(hex, 16).ok()),
+            v => v.as_u64().and_then(|v| u32::try_from(v).ok()),
+        };
+        parsed.ok_or_else(|| format!("'{}' must be a 32 bit unsigned number or 0x prefixed hex", key))
+    }
+
+    fn location(&self) -> Result<MessageLocation, String> {
+        Ok(MessageLocation {
+            sign_on: self.opt_bool("sign_on")?.unwrap_or(false),
+            frame_index: self.index("frame_index")?,
+            message_index: self.index("message_index")?,
+        })
+    }
+
+    fn console_command(&self) -> Result<InsertConsoleCommand, String> {
+        let player_slot = match self.fields.contains_key("player_slot") {
+            true => u8::try_from(self.u64("player_slot")?).map_err(|_| "'player_slot' must be 0 to 255".to_owned())?,
+            false => 0
+        };
+        let insert = InsertConsoleCommand {
+            tick: self.int("tick")?,
+            player_slot,
+            command: self.string("command")?,
+        };
+        insert.validate()?;
+        Ok(insert)
+    }
+
+    // message types by name (e.g. "Sounds") or id
+    fn message_ids(&self, key: &str, names: std::collections::BTreeMap<usize, &'static str>) -> Result<BTreeSet<u64>, String> {
+        let values = match self.fields.get(key) {
+            None => return Ok(BTreeSet::new()),
+            Some(Value::Array(values)) => values,
+            Some(_) => return Err(format!("'{}' must be a list of message names or ids", key))
+        };
+        values.iter().map(|value| match value {
+            Value::String(name) => names
+                .iter()
+                .find(|(_, n)| n.eq_ignore_ascii_case(name))
+                .map(|(id, _)| *id as u64)
+                .ok_or_else(|| format!("unknown message type '{}' in '{}'", name, key)),
+            v => v.as_u64().ok_or_else(|| format!("'{}' must be a list of message names or ids", key)),
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::tests::demo_bytes;
+
+    // whether an op was parsed the way it should be
+    type CheckOp = fn(&PatchOp) -> bool;
+
+    fn parse_edit(edit: &str) -> Result<PatchOp, String> {
+        let mut ops = parse_patch(&format!(r#"{{ "edits": [ {} ] }}"#, edit), PatchSyntax::Json)?;
+        assert_eq!(ops.len(), 1);
+        Ok(ops.remove(0))
+    }
+
+    #[test]
+    fn valid_ops() {
+        let cases: [(&str, CheckOp); 14] = [
+            (r#"{ "op": "header", "map_name": "de_inferno", "ticks": 100 }"#,
+                |op| matches!(op, PatchOp::Header(h) if h.map_name.as_deref() == Some("de_inferno") && h.ticks == Some(100) && h.frames.is_none())),
+            (r#"{ "op": "set_field", "frame_index": 2, "message_index": 1, "field": "tick", "type": "varint", "value": 100 }"#,
+                |op| matches!(op, PatchOp::Edit(DocumentEdit::ProtobufField(e)) if e.field == "tick" && !e.target.location.sign_on && e.target.location.frame_index == 2)),
+            (r#"{ "op": "set_field", "frame_index": 0, "message_index": 0, "field": "x", "type": "varint", "value": -1 }"#,
+                |op| matches!(op, PatchOp::Edit(DocumentEdit::ProtobufField(e)) if matches!(e.value, FieldValue::VarInt(u64::MAX)))),
+            (r#"{ "op": "set_field", "frame_index": 0, "message_index": 0, "field": "x", "type": "fixed32", "value": "0x11223344" }"#,
+                |op| matches!(op, PatchOp::Edit(DocumentEdit::ProtobufField(e)) if matches!(e.value, FieldValue::Fixed32(0x1122_3344)))),
+            (r#"{ "op": "set_field", "sign_on": true, "frame_index": 0, "message_index": 0, "user_message": true, "field": "x", "type": "bytes", "value": "00ff" }"#,
+                |op| matches!(op, PatchOp::Edit(DocumentEdit::ProtobufField(e)) if e.target.user_message && e.target.location.sign_on && matches!(&e.value, FieldValue::Length(v) if v == &[0x00, 0xff]))),
+            (r#"{ "op": "insert_console_command", "tick": 10, "command": "say hi" }"#,
+                |op| matches!(op, PatchOp::Edit(DocumentEdit::ConsoleCommand(c)) if c.tick == 10 && c.player_slot == 0 && c.command == "say hi")),
+            (r#"{ "op": "insert_frame", "frame_index": 3, "tick": 10, "player_slot": 1, "command": "echo" }"#,
+                |op| matches!(op, PatchOp::Edit(DocumentEdit::InsertFrame { index: 3, frame, .. }) if frame.console_command().as_deref() == Some("echo"))),
+            (r#"{ "op": "remove_frame", "sign_on": true, "frame_index": 1 }"#,
+                |op| matches!(op, PatchOp::Edit(DocumentEdit::RemoveFrame { sign_on: true, index: 1 }))),
+            (r#"{ "op": "insert_message", "frame_index": 1, "message_index": 0, "id": 4, "data": "0864" }"#,
+                |op| matches!(op, PatchOp::Edit(DocumentEdit::InsertMessage { message, .. }) if message.id == 4 && message.data == [0x08, 0x64])),
+            (r#"{ "op": "remove_message", "frame_index": 1, "message_index": 0 }"#,
+                |op| matches!(op, PatchOp::Edit(DocumentEdit::RemoveMessage { .. }))),
+            (r#"{ "op": "map_switch", "map_name": "de_inferno", "map_crc": 1234 }"#,
+                |op| matches!(op, PatchOp::MapSwitch(m) if m.map_name == "de_inferno" && m.map_crc == Some(1234))),
+            (r#"{ "op": "strip_messages", "net_messages": ["sounds", 4] }"#,
+                |op| matches!(op, PatchOp::StripMessages(s) if s.net_message_ids.len() == 2 && s.net_message_ids.contains(&4))),
+            (r#"{ "op": "trim", "start_tick": 10, "end_tick": 20 }"#,
+                |op| matches!(op, PatchOp::Trim(t) if t.start_tick == 10 && t.end_tick == 20)),
+            (r#"{ "op": "anonymize" }"#,
+                |op| matches!(op, PatchOp::Anonymize)),
+        ];
+        for (edit, check) in cases {
+            match parse_edit(edit) {
+                Ok(op) => assert!(check(&op), "{} parsed as {:?}", edit, op),
+                Err(e) => panic!("{} failed: {}", edit, e)
+            }
+        }
+    }
+
+    #[test]
+    fn valid_toml() {
+        let text = r#"
+            format = "source-demo-crawler patch"
+            version = 1
+
+            [[edits]]
+            op = "map_switch"
+            map_name = "de_inferno"
+
+            [[edits]]
+            op = "replace_names"
+            names = { "player one" = "one", "player two" = "two" }
+
+            [[edits]]
+            op = "trim"
+            start_tick = 0
+            end_tick = 64
+        "#;
+        let ops = parse_patch(text, PatchSyntax::Toml).unwrap();
+        assert_eq!(ops.len(), 3);
+        assert!(matches!(&ops[0], PatchOp::MapSwitch(m) if m.map_name == "de_inferno" && m.map_crc.is_none()));
+        assert!(matches!(&ops[1], PatchOp::ReplaceNames(r) if r.names.len() == 2));
+        assert!(matches!(&ops[2], PatchOp::Trim(t) if t.end_tick == 64));
+    }
+
+    #[test]
+    fn invalid_patches() {
+        // the patch, and what the error should say
+        let cases: [(&str, PatchSyntax, &str); 18] = [
+            (r#"{ "edits": [ { "op": "trim", "start_tick": 0, "end_tick": 10, "end": 5 } ] }"#, PatchSyntax::Json, "edit 1 (trim): unknown key 'end'"),
+            (r#"{ "edits": [ { "op": "anonymize", "names": [] } ] }"#, PatchSyntax::Json, "unknown key 'names'"),
+            (r#"{ "edits": [], "edit": [] }"#, PatchSyntax::Json, "unknown key 'edit'"),
+            ("[[edits]]\nop = \"header\"\nmap = \"de_inferno\"", PatchSyntax::Toml, "unknown key 'map'"),
+            (r#"{ "edits": [ { "op": "header", "ticks": "100" } ] }"#, PatchSyntax::Json, "'ticks' must be a 32 bit whole number"),
+            (r#"{ "edits": [ { "op": "header", "map_name": 5 } ] }"#, PatchSyntax::Json, "'map_name' must be a string"),
+            (r#"{ "edits": [ { "op": "remove_frame", "sign_on": 1, "frame_index": 0 } ] }"#, PatchSyntax::Json, "'sign_on' must be true or false"),
+            (r#"{ "edits": [ { "op": "remove_frame", "frame_index": -1 } ] }"#, PatchSyntax::Json, "'frame_index' must be a positive whole number"),
+            (r#"{ "edits": [ { "op": "map_switch", "map_name": "x", "map_crc": "1234" } ] }"#, PatchSyntax::Json, "'map_crc' must be a 32 bit unsigned number"),
+            (r#"{ "edits": [ { "op": "strip_messages", "net_messages": "Sounds" } ] }"#, PatchSyntax::Json, "'net_messages' must be a list"),
+            ("[[edits]]\nop = \"trim\"\nstart_tick = 0\nend_tick = 1.5", PatchSyntax::Toml, "'end_tick' must be a 32 bit whole number"),
+            (r#"{ "edits": [ { "op": "set_field", "frame_index": 0, "message_index": 0, "field": "x", "type": "varint", "value": 1.5 } ] }"#, PatchSyntax::Json, "'value' must be a whole number"),
+            (r#"{ "edits": [ { "op": "trim" } ] }"#, PatchSyntax::Json, "'start_tick' is missing"),
+            (r#"{ "edits": [ { "op": "rename" } ] }"#, PatchSyntax::Json, "unknown op 'rename'"),
+            (r#"{ "edits": [ { "tick": 1 } ] }"#, PatchSyntax::Json, "edit 1 has no op"),
+            (r#"{ "edits": [] }"#, PatchSyntax::Json, "the edits list is empty"),
+            (r#"{ "format": "something else", "edits": [ { "op": "anonymize" } ] }"#, PatchSyntax::Json, "is not a source-demo-crawler patch file"),
+            (r#"{ "version": 2, "edits": [ { "op": "anonymize" } ] }"#, PatchSyntax::Json, "only version 1 patches are supported"),
+        ];
+        for (text, syntax, error) in cases {
+            match parse_patch(text, syntax) {
+                Ok(ops) => panic!("{} parsed as {:?}", text, ops),
+                Err(e) => assert!(e.contains(error), "{} failed with '{}', expected '{}'", text, e, error)
+            }
+        }
+    }
+
+    #[test]
+    fn rules_that_match_nothing() {
+        // the test demo's map is de_dust2, it has no TempEntities messages and no players
+        let cases: [(&str, &str); 7] = [
+            (r#"{ "op": "header", "map_name": "de_dust2" }"#, "the header already has these values"),
+            (r#"{ "op": "map_switch", "map_name": "de_dust2" }"#, "the map is already de_dust2"),
+            (r#"{ "op": "strip_messages", "net_messages": ["TempEntities"] }"#, "the demo has no TempEntities messages"),
+            (r#"{ "op": "replace_names", "names": { "nobody": "someone" } }"#, "no player is named 'nobody'"),
+            (r#"{ "op": "trim", "start_tick": 1000, "end_tick": 2000 }"#, "there are no frames between tick 1000 and 2000"),
+            (r#"{ "op": "anonymize" }"#, "no players were found in the userinfo string table"),
+            (r#"{ "op": "remove_message", "frame_index": 40, "message_index": 0 }"#, "frame 41 is not a packet"),
+        ];
+        for (edit, error) in cases {
+            let mut document = DemoDocument::from_bytes(&demo_bytes()).unwrap();
+            match parse_edit(edit).unwrap().apply(&mut document) {
+                Ok(summary) => panic!("{} applied: {}", edit, summary),
+                Err(e) => assert!(e.contains(error), "{} failed with '{}', expected '{}'", edit, e, error)
+            }
+        }
+    }
+
+    #[test]
+    fn rules_that_match_apply() {
+        let mut document = DemoDocument::from_bytes(&demo_bytes()).unwrap();
+        let ops = parse_patch(r#"{ "edits": [
+            { "op": "header", "map_name": "de_inferno" },
+            { "op": "strip_messages", "net_messages": [5] }
+        ] }"#, PatchSyntax::Json).unwrap();
+        for op in &ops {
+            op.apply(&mut document).unwrap();
+        }
+        assert_eq!(document.header.map_name, "de_inferno");
+        assert!(document.message_locations(5).is_empty());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,308 @@
+use source_demo_tool::{
+    demo_file::packet::protobuf_value::ProtobufValue,
+    protobuf_message::{ ProtobufMessage, ProtobufMessageEnumTraits, WireMessage },
+};
+
+use super::FieldChange;
+use crate::document::{ DemoDocument, MessageLocation, net_message_id, protobuf };
+
+mod user_message_field {
+    pub const MSG_TYPE: u8 = 1;
+    pub const MSG_DATA: u8 = 2;
+}
+
+// the message an edit applies to, user_message edits the user message inside
+// a UserMessage netmessage instead of the netmessage itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageTarget {
+    pub location: MessageLocation,
+    pub user_message: bool,
+}
+
+impl MessageTarget {
+    pub fn describe(&self) -> String {
+        if self.user_message {
+            format!("User Message ({})", self.location.describe())
+        } else {
+            self.location.describe()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    VarInt(u64),
+    Fixed32(u32),
+    Float32(f32),
+    String(String),
+    Length(Vec<u8>),
+}
+
+impl FieldValue {
+    // parses text as a value of the same type as current, None types (Proto,
+    // Repeated, None and Length) can't be typed in.
+    pub fn parse_like(current: &ProtobufValue, text: &str) -> Result<Self, String> {
+        let trimmed = text.trim();
+        match current {
+            ProtobufValue::VarInt(_) => {
+                if let Ok(v) = trimmed.parse::<u64>() {
+                    return Ok(FieldValue::VarInt(v))
+                }
+                // negative int32/int64 values are sent as their 64 bit two's complement
+                match trimmed.parse::<i64>() {
+                    Ok(v) => Ok(FieldValue::VarInt(v as u64)),
+                    Err(_) => Err(format!("'{}' is not a whole number", text))
+                }
+            },
+            ProtobufValue::Fixed32(_) => {
+                let parsed = match trimmed.strip_prefix("0x") {
+                    Some(hex) => u32::from_str_radix(hex, 16),
+                    None => trimmed.parse::<u32>()
+                };
+                match parsed {
+                    Ok(v) => Ok(FieldValue::Fixed32(v)),
+                    Err(_) => Err(format!("'{}' is not a 32 bit unsigned number", text))
+                }
+            },
+            ProtobufValue::Float32(_) => match trimmed.parse::<f32>() {
+                Ok(v) => Ok(FieldValue::Float32(v)),
+                Err(_) => Err(format!("'{}' is not a number", text))
+            },
+            ProtobufValue::String(_) => Ok(FieldValue::String(text.to_owned())),
+            _ => Err("this field can't be edited as text".into())
+        }
+    }
+
+    pub fn to_protobuf_value(&self) -> ProtobufValue {
+        match self {
+            FieldValue::VarInt(v) => ProtobufValue::VarInt(*v),
+            FieldValue::Fixed32(v) => ProtobufValue::Fixed32(*v),
+            FieldValue::Float32(v) => ProtobufValue::Float32(*v),
+            FieldValue::String(v) => ProtobufValue::String(v.clone()),
+            FieldValue::Length(v) => ProtobufValue::Length(v.clone()),
+        }
+    }
+
+    fn to_wire(&self) -> WireMessage {
+        match self {
+            FieldValue::VarInt(v) => WireMessage::VarInt(*v),
+            FieldValue::Fixed32(v) => WireMessage::Fixed32(*v),
+            FieldValue::Float32(v) => WireMessage::Fixed32(v.to_bits()),
+            FieldValue::String(v) => WireMessage::Length(v.as_bytes().to_vec()),
+            FieldValue::Length(v) => WireMessage::Length(v.clone()),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            FieldValue::VarInt(v) => v.to_string(),
+            FieldValue::Fixed32(v) => v.to_string(),
+            FieldValue::Float32(v) => v.to_string(),
+            FieldValue::String(v) => v.clone(),
+            FieldValue::Length(v) => format!("Data ({} Bytes)", v.len()),
+        }
+    }
+}
+
+// sets one field of a message. field is the flattened name the inspector shows,
+// see flatten_protobuf_fields, e.g. "map_name" or "GameEventKeys[2].val_string".
+#[derive(Debug, Clone)]
+pub struct ProtobufFieldEdit {
+    pub target: MessageTarget,
+    pub field: String,
+    pub value: FieldValue,
+}
+
+// wire field number and which occurrence of it, from the outermost message in.
+type FieldPath = Vec<(u8, usize)>;
+
+impl ProtobufFieldEdit {
+    // returns the change made and the edit that puts the old value back.
+    pub fn apply(&self, document: &mut DemoDocument) -> Result<(FieldChange, ProtobufFieldEdit), String> {
+        let message = match document.message_mut(self.target.location) {
+            Some(m) => m,
+            None => return Err(format!("there is no message at {}", self.target.location.describe()))
+        };
+
+        let old = if self.target.user_message {
+            if message.id != net_message_id::USER_MESSAGE {
+                return Err(format!("{} is not a user message", self.target.location.describe()))
+            }
+            let fields = message.fields()?;
+            let msg_type = fields.iter().find_map(|f| match (f.field_number, &f.message) {
+                (user_message_field::MSG_TYPE, WireMessage::VarInt(v)) => Some(*v),
+                _ => None
+            });
+            let msg_data = fields.iter().find_map(|f| match (f.field_number, &f.message) {
+                (user_message_field::MSG_DATA, WireMessage::Length(v)) => Some(v.clone()),
+                _ => None
+            });
+            let (msg_type, msg_data) = match (msg_type, msg_data) {
+                (Some(t), Some(d)) => (t, d),
+                _ => return Err("the user message has no type or data".into())
+            };
+
+            let parsed = protobuf::parse_user_message(msg_type, &msg_data)?;
+            let path = self.field_path(&parsed, &msg_data)?;
+            let (msg_data, old) = replace_field(&msg_data, &path, &self.value)?;
+            let path = vec![(user_message_field::MSG_DATA, 0)];
+            let (data, _) = replace_field(&message.data, &path, &FieldValue::Length(msg_data))?;
+            message.data = data;
+            old
+        } else {
+            let parsed = protobuf::parse_net_message(message.id, &message.data)?;
+            let path = self.field_path(&parsed, &message.data)?;
+            let (data, old) = replace_field(&message.data, &path, &self.value)?;
+            message.data = data;
+            old
+        };
+
+        let change = FieldChange {
+            location: self.target.describe(),
+            field: self.field.clone(),
+            old: old.describe(),
+            new: self.value.describe(),
+        };
+        let undo = ProtobufFieldEdit {
+            target: self.target,
+            field: self.field.clone(),
+            value: old,
+        };
+        Ok((change, undo))
+    }
+
+    fn field_path(&self, parsed: &dyn ProtobufMessageEnumTraits, data: &[u8]) -> Result<FieldPath, String> {
+        let named = parsed.to_vec()
+            .into_iter()
+            .map(|(name, value)| (name.to_owned(), value))
+            .collect();
+        let mut paths = Vec::new();
+        if let Err(e) = field_paths(named, data, &Vec::new(), &mut paths) {
+            return Err(format!("can't edit {}: {}", parsed.to_str(), e))
+        }
+        match paths.into_iter().find(|(name, _)| *name == self.field) {
+            Some((_, path)) => Ok(path),
+            None => Err(format!("{} has no field {}", parsed.to_str(), self.field))
+        }
+    }
+}
+
+// pairs every flattened field name with where it is on the wire. the parsed fields
+// are in field number order with None for absent ones, so the present ones line up
+// with the distinct field numbers of the wire data. messages with fields the parser
+// doesn't know can't be lined up.
+fn field_paths(
+    named: Vec<(String, ProtobufValue)>,
+    data: &[u8],
+    prefix: &FieldPath,
+    paths: &mut Vec<(String, FieldPath)>
+) -> Result<(), String> {
+    let wire = protobuf::decode_fields(data)?;
+    let mut field_numbers: Vec<u8> = wire.iter().map(|f| f.field_number).collect();
+    field_numbers.sort();
+    field_numbers.dedup();
+
+    let present: Vec<(String, ProtobufValue)> = named
+        .into_iter()
+        .filter(|(_, v)| !matches!(v, ProtobufValue::None))
+        .collect();
+    if present.len() != field_numbers.len() {
+        return Err("the message has fields the parser doesn't know".into())
+    }
+
+    for ((name, value), field_number) in present.into_iter().zip(field_numbers) {
+        let count = wire.iter().filter(|f| f.field_number == field_number).count();
+        match value {
+            ProtobufValue::Repeated(values) => {
+                if values.len() != count {
+                    return Err(format!("{} doesn't match the message data", name))
+                }
+                for (i, value) in values.into_iter().enumerate() {
+                    let name = format!("{}[{}]", name, i);
+                    field_path(name, value, &wire, field_number, i, prefix, paths)?;
+                }
+            },
+            value => {
+                if count != 1 {
+                    return Err(format!("{} is in the message data {} times", name, count))
+                }
+                field_path(name, value, &wire, field_number, 0, prefix, paths)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn field_path(
+    name: String,
+    value: ProtobufValue,
+    wire: &[ProtobufMessage],
+    field_number: u8,
+    occurrence: usize,
+    prefix: &FieldPath,
+    paths: &mut Vec<(String, FieldPath)>
+) -> Result<(), String> {
+    let wire_message = &wire
+        .iter()
+        .filter(|f| f.field_number == field_number)
+        .nth(occurrence)
+        .unwrap()
+        .message;
+    let mut path = prefix.clone();
+    path.push((field_number, occurrence));
+
+    match (value, wire_message) {
+        (ProtobufValue::Proto(sub_fields), WireMessage::Length(data)) => {
+            let named = sub_fields
+                .into_iter()
+                .map(|(sub_name, v)| (format!("{}.{}", name, sub_name), v))
+                .collect();
+            field_paths(named, data, &path, paths)
+        },
+        (ProtobufValue::VarInt(_), WireMessage::VarInt(_))
+        | (ProtobufValue::Fixed32(_) | ProtobufValue::Float32(_), WireMessage::Fixed32(_))
+        | (ProtobufValue::String(_) | ProtobufValue::Length(_), WireMessage::Length(_)) => {
+            paths.push((name, path));
+            Ok(())
+        },
+        _ => Err(format!("{} doesn't match the message data", name))
+    }
+}
+
+// sets the field at path, re-encoding every message around it. returns the new
+// data and the old value, as the same type as value.
+fn replace_field(data: &[u8], path: &[(u8, usize)], value: &FieldValue) -> Result<(Vec<u8>, FieldValue), String> {
+    let (field_number, occurrence) = path[0];
+    let mut fields = protobuf::decode_fields(data)?;
+    let field = match fields.iter_mut().filter(|f| f.field_number == field_number).nth(occurrence) {
+        Some(f) => f,
+        None => return Err(format!("field {} is missing from the message data", field_number))
+    };
+
+    if path.len() > 1 {
+        let old = match &mut field.message {
+            WireMessage::Length(inner) => {
+                let (new_inner, old) = replace_field(inner, &path[1..], value)?;
+                *inner = new_inner;
+                old
+            },
+            _ => return Err(format!("field {} is not a message", field_number))
+        };
+        return Ok((protobuf::encode_fields(&fields), old))
+    }
+
+    let old = match (&field.message, value) {
+        (WireMessage::VarInt(v), FieldValue::VarInt(_)) => FieldValue::VarInt(*v),
+        (WireMessage::Fixed32(v), FieldValue::Fixed32(_)) => FieldValue::Fixed32(*v),
+        (WireMessage::Fixed32(v), FieldValue::Float32(_)) => FieldValue::Float32(f32::from_bits(*v)),
+        // strings that aren't valid utf-8 are kept as bytes so undoing gives the same data
+        (WireMessage::Length(v), FieldValue::String(_)) => match String::from_utf8(v.clone()) {
+            Ok(s) => FieldValue::String(s),
+            Err(_) => FieldValue::Length(v.clone())
+        },
+        (WireMessage::Length(v), FieldValue::Length(_)) => FieldValue::Length(v.clone()),
+        _ => return Err(format!("field {} has a different type than the new value", field_number))
+    };
+    field.message = value.to_wire();
+    Ok((protobuf::encode_fields(&fields), old))
+}
\ No newline at end of file
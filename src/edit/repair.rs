@@ -0,0 +1,96 @@
+use super::FieldChange;
+use crate::document::{ DemoDocument, DocumentFrame, FrameBody };
+
+// fixes what a crashed or killed recording leaves behind: a header that was never
+// filled in, a last frame that was cut off and no Stop frame. everything that
+// couldn't be read as a frame is dropped.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Repair;
+
+#[derive(Debug, Clone, Default)]
+pub struct RepairReport {
+    pub bytes_discarded: usize,
+    pub sign_on_frames_recovered: usize,
+    pub stop_appended: bool,
+    pub changes: Vec<FieldChange>,
+}
+
+impl Repair {
+    // what's wrong with the demo, empty when it doesn't need repairing.
+    pub fn problems(document: &DemoDocument) -> Vec<String> {
+        let mut problems = Vec::new();
+        if !document.trailing_data.is_empty() {
+            problems.push(format!(
+                "the last {} bytes can't be read as a frame, the recording was cut off",
+                document.trailing_data.len()
+            ));
+        }
+        if !matches!(document.frames.last().map(|f| &f.body), Some(FrameBody::Stop)) {
+            problems.push("the demo doesn't end with a Stop frame".into());
+        }
+        let header = &document.header;
+        if header.ticks == 0 && header.frames == 0 && header.playback_time == 0.0 && !document.frames.is_empty() {
+            problems.push("the header's ticks, frames and playback time were never filled in".into());
+        }
+        if document.sign_on_frames.is_empty() && Self::leading_sign_on_frames(document) > 0 {
+            problems.push("the header's sign on length is zero, the sign on frames are read as regular frames".into());
+        }
+        problems
+    }
+
+    // SignOn and DataTables frames before the first regular frame, these are the
+    // sign on data when the header doesn't say how long it is.
+    fn leading_sign_on_frames(document: &DemoDocument) -> usize {
+        document.frames.iter()
+            .take_while(|f| matches!(f.body, FrameBody::SignOn(_) | FrameBody::DataTables(_) | FrameBody::StringTables(_)))
+            .count()
+    }
+
+    // tick_interval is used for the header's playback time, see DemoDocument::update_header_counts.
+    pub fn apply(&self, document: &mut DemoDocument, tick_interval: f32) -> Result<RepairReport, String> {
+        if Self::problems(document).is_empty() {
+            return Err("the demo doesn't need repairing".into())
+        }
+
+        let mut report = RepairReport {
+            bytes_discarded: document.trailing_data.len(),
+            ..Default::default()
+        };
+        document.trailing_data.clear();
+        document.index_error = None;
+
+        if document.sign_on_frames.is_empty() {
+            let count = Self::leading_sign_on_frames(document);
+            document.sign_on_frames = document.frames.drain(..count).collect();
+            report.sign_on_frames_recovered = count;
+        }
+        if document.frames.is_empty() {
+            return Err("there are no complete frames after the sign on data".into())
+        }
+
+        if !matches!(document.frames.last().map(|f| &f.body), Some(FrameBody::Stop)) {
+            let tick = document.frames.iter().map(|f| f.tick).max().unwrap_or(0);
+            document.frames.push(DocumentFrame {
+                tick,
+                player_slot: 0,
+                body: FrameBody::Stop,
+            });
+            report.stop_appended = true;
+        }
+
+        let old = document.header.clone();
+        document.update_header_counts(tick_interval);
+        let header = &document.header;
+        let fields = [
+            ("ticks", old.ticks.to_string(), header.ticks.to_string()),
+            ("frames", old.frames.to_string(), header.frames.to_string()),
+            ("playback_time", old.playback_time.to_string(), header.playback_time.to_string()),
+        ];
+        for (field, old, new) in fields {
+            if old != new {
+                report.changes.push(FieldChange { location: "Header".into(), field: field.into(), old, new });
+            }
+        }
+        Ok(report)
+    }
+}
\ No newline at end of file
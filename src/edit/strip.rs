@@ -0,0 +1,104 @@
+use std::collections::{ BTreeMap, BTreeSet };
+
+use source_demo_tool::protobuf_message::WireMessage;
+
+use crate::document::{ DemoDocument, FrameBody, RawMessage, net_message_id };
+
+const USER_MESSAGE_MSG_TYPE: u8 = 1;
+
+// removes netmessages by type from every packet, sign on packets included. user
+// messages can also be removed by their own type, e.g. only SayText2. packet
+// headers get their data_length recomputed when the demo is written.
+#[derive(Debug, Clone, Default)]
+pub struct StripMessages {
+    pub net_message_ids: BTreeSet<u64>,
+    pub user_message_ids: BTreeSet<u64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StripReport {
+    pub messages_removed: usize,
+    pub packets_changed: usize,
+    pub bytes_removed: usize,
+}
+
+// how many of each netmessage and user message type a demo has, by id.
+#[derive(Debug, Clone, Default)]
+pub struct MessageCounts {
+    pub net_messages: BTreeMap<u64, usize>,
+    pub user_messages: BTreeMap<u64, usize>,
+}
+
+impl MessageCounts {
+    pub fn from_document(document: &DemoDocument) -> Self {
+        let mut counts = Self::default();
+        for message in packet_messages(document) {
+            *counts.net_messages.entry(message.id).or_insert(0) += 1;
+            if let Some(msg_type) = user_message_type(message) {
+                *counts.user_messages.entry(msg_type).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+}
+
+impl StripMessages {
+    pub fn is_empty(&self) -> bool {
+        self.net_message_ids.is_empty() && self.user_message_ids.is_empty()
+    }
+
+    fn strips(&self, message: &RawMessage) -> bool {
+        self.net_message_ids.contains(&message.id)
+            || user_message_type(message).is_some_and(|t| self.user_message_ids.contains(&t))
+    }
+
+    pub fn apply(&self, document: &mut DemoDocument) -> Result<StripReport, String> {
+        if self.is_empty() {
+            return Err("no message types were selected".into())
+        }
+
+        let mut report = StripReport::default();
+        for frame in document.sign_on_frames.iter_mut().chain(document.frames.iter_mut()) {
+            let packet = match &mut frame.body {
+                FrameBody::SignOn(packet) | FrameBody::Packet(packet) => packet,
+                _ => continue
+            };
+            let count = packet.messages.len();
+            let mut encoded = Vec::new();
+            packet.messages.retain(|message| {
+                if !self.strips(message) {
+                    return true
+                }
+                encoded.clear();
+                message.write(&mut encoded);
+                report.bytes_removed += encoded.len();
+                false
+            });
+            if packet.messages.len() != count {
+                report.messages_removed += count - packet.messages.len();
+                report.packets_changed += 1;
+            }
+        }
+        Ok(report)
+    }
+}
+
+fn packet_messages(document: &DemoDocument) -> impl Iterator<Item = &RawMessage> {
+    document.sign_on_frames.iter()
+        .chain(document.frames.iter())
+        .filter_map(|frame| match &frame.body {
+            FrameBody::SignOn(packet) | FrameBody::Packet(packet) => Some(packet.messages.iter()),
+            _ => None
+        })
+        .flatten()
+}
+
+fn user_message_type(message: &RawMessage) -> Option<u64> {
+    if message.id != net_message_id::USER_MESSAGE {
+        return None
+    }
+    message.fields().ok()?.iter().find_map(|f| match (f.field_number, &f.message) {
+        (USER_MESSAGE_MSG_TYPE, WireMessage::VarInt(v)) => Some(*v),
+        _ => None
+    })
+}
\ No newline at end of file
@@ -0,0 +1,176 @@
+use source_demo_tool::demo_file::packet::netmessage::NetMessage;
+
+use crate::document::{ DemoDocument, command_id, net_message_id, protobuf, DocumentFrame, DocumentPacket, FrameBody };
+
+// messages before the range that playback still needs, string tables build on
+// every earlier update so none of them can be dropped.
+const STATE_MESSAGE_IDS: [u64; 3] = [
+    net_message_id::SET_CON_VAR,
+    net_message_id::CREATE_STRING_TABLE,
+    net_message_id::UPDATE_STRING_TABLE,
+];
+
+// cuts a demo down to the frames in [start_tick, end_tick]. sign on frames are kept,
+// the data tables, string tables and string table updates from before the range are
+// moved to its first tick. PacketEntities deltas build on every update since the last
+// full one, so the range is moved back to start at it.
+#[derive(Debug, Clone, Copy)]
+pub struct Trim {
+    pub start_tick: i32,
+    pub end_tick: i32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TrimReport {
+    // the tick the clip starts at, before start_tick when it had to include the last full entity update
+    pub start_tick: i32,
+    pub frames_kept: usize,
+    pub frames_removed: usize,
+    // DataTables, StringTables and SyncTick frames from before the range
+    pub state_frames_kept: usize,
+    pub state_messages_kept: usize,
+}
+
+impl Trim {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.start_tick < 0 {
+            return Err("the start tick can't be negative".into())
+        }
+        if self.end_tick < self.start_tick {
+            return Err(format!("the end tick ({}) is before the start tick ({})", self.end_tick, self.start_tick))
+        }
+        Ok(())
+    }
+
+    fn contains(&self, tick: i32) -> bool {
+        tick >= self.start_tick && tick <= self.end_tick
+    }
+
+    // the tick the kept frames have to start at so their first PacketEntities message isn't a
+    // delta against a dropped one, the last full update at or before start_tick.
+    fn entities_start_tick(&self, document: &DemoDocument) -> Result<i32, String> {
+        let is_full_update = |data: &[u8]| match protobuf::parse_net_message(net_message_id::PACKET_ENTITIES, data)? {
+            NetMessage::PacketEntity(pe) => Ok(pe.is_delta.unwrap_or(0) == 0),
+            _ => Err("not a PacketEntities message".to_owned())
+        };
+
+        let mut sign_on_full_update = false;
+        for frame in &document.sign_on_frames {
+            if let FrameBody::SignOn(packet) | FrameBody::Packet(packet) = &frame.body {
+                for message in packet.messages.iter().filter(|m| m.id == net_message_id::PACKET_ENTITIES) {
+                    sign_on_full_update |= is_full_update(&message.data)?;
+                }
+            }
+        }
+
+        let mut last_full_update = None;
+        for frame in document.frames.iter().filter(|f| f.tick <= self.end_tick) {
+            let packet = match &frame.body {
+                FrameBody::Packet(packet) => packet,
+                _ => continue
+            };
+            for message in packet.messages.iter().filter(|m| m.id == net_message_id::PACKET_ENTITIES) {
+                let full = is_full_update(&message.data)?;
+                if frame.tick < self.start_tick {
+                    if full {
+                        last_full_update = Some(frame.tick);
+                    }
+                    continue
+                }
+                // the first update in the range
+                return match (full, last_full_update) {
+                    (true, _) => Ok(self.start_tick),
+                    (false, Some(tick)) => Ok(tick),
+                    // every delta since sign on is needed
+                    (false, None) if sign_on_full_update => Ok(document.frames.first().map(|f| f.tick).unwrap_or(self.start_tick)),
+                    (false, None) => Err(format!(
+                        "there's no full entity update at or before tick {}, the clip wouldn't play back",
+                        self.start_tick
+                    ))
+                }
+            }
+        }
+        // no entities in the range
+        Ok(self.start_tick)
+    }
+
+    // tick_interval is used for the header's playback time, see DemoDocument::update_header_counts.
+    pub fn apply(&self, document: &mut DemoDocument, tick_interval: f32) -> Result<TrimReport, String> {
+        self.validate()?;
+        if !document.frames.iter().any(|f| self.contains(f.tick) && f.command() != command_id::STOP) {
+            return Err(format!("there are no frames between tick {} and {}", self.start_tick, self.end_tick))
+        }
+        let range = Trim { start_tick: self.entities_start_tick(document)?, end_tick: self.end_tick };
+
+        let mut report = TrimReport { start_tick: range.start_tick, ..Default::default() };
+        let mut state_frames = Vec::new();
+        let mut state_messages = Vec::new();
+        let mut state_packet: Option<(u8, DocumentPacket)> = None;
+        let mut kept = Vec::new();
+
+        let original_count = document.frames.len();
+        for frame in document.frames.drain(..) {
+            if range.contains(frame.tick) {
+                if !matches!(frame.body, FrameBody::Stop) {
+                    kept.push(frame);
+                }
+                continue
+            }
+            if frame.tick > self.end_tick {
+                continue
+            }
+            match frame.body {
+                FrameBody::SyncTick | FrameBody::DataTables(_) | FrameBody::StringTables(_) => {
+                    state_frames.push(frame);
+                },
+                FrameBody::Packet(packet) => {
+                    state_messages.extend(
+                        packet.messages.iter()
+                        .filter(|m| STATE_MESSAGE_IDS.contains(&m.id))
+                        .cloned()
+                    );
+                    state_packet = Some((frame.player_slot, packet));
+                },
+                _ => {}
+            }
+        }
+
+        let first_tick = kept.first().map(|f| f.tick).unwrap_or(range.start_tick);
+        let last_tick = kept.last().map(|f| f.tick).unwrap_or(self.end_tick);
+
+        report.state_frames_kept = state_frames.len();
+        report.state_messages_kept = state_messages.len();
+        report.frames_kept = kept.len();
+
+        let mut frames = Vec::with_capacity(state_frames.len() + kept.len() + 2);
+        for mut frame in state_frames {
+            frame.tick = first_tick;
+            frames.push(frame);
+        }
+        if let Some((player_slot, mut packet)) = state_packet {
+            if !state_messages.is_empty() {
+                packet.messages = state_messages;
+                packet.trailing_data.clear();
+                frames.push(DocumentFrame {
+                    tick: first_tick,
+                    player_slot,
+                    body: FrameBody::Packet(packet),
+                });
+            }
+        }
+        frames.append(&mut kept);
+        frames.push(DocumentFrame {
+            tick: last_tick,
+            player_slot: 0,
+            body: FrameBody::Stop,
+        });
+        report.frames_removed = original_count - report.frames_kept - report.state_frames_kept;
+
+        document.frames = frames;
+        document.update_header_counts(tick_interval);
+        // whatever couldn't be read is past the new Stop frame
+        document.trailing_data.clear();
+        document.index_error = None;
+        Ok(report)
+    }
+}
\ No newline at end of file
@@ -0,0 +1,332 @@
+use std::io::{ self, Write };
+
+use source_demo_tool::{
+    demo_file::{
+        frame::{ Frame, DataTablesData },
+        FullGameEvent,
+        FullGameEventKey,
+        FullGameEventKeyType,
+        ParsedUserMessage,
+        packet::protobuf_value::ProtobufValue,
+    },
+    protobuf_message::ProtobufMessageEnumTraits,
+};
+
+use crate::{ document::player_info::UserIdLookup, edit::FieldChange };
+
+pub mod json;
+
+// writes a single csv row, quoting fields only when required.
+pub fn write_csv_row<W: Write, S: AsRef<str>>(writer: &mut W, fields: &[S]) -> io::Result<()> {
+    let mut line = String::new();
+    for (i, field) in fields.iter().enumerate() {
+        if i != 0 {
+            line.push(',');
+        }
+        let field = field.as_ref();
+        if field.contains([',', '"', '\n', '\r']) {
+            line.push('"');
+            line += field.replace('"', "\"\"").as_str();
+            line.push('"');
+        } else {
+            line += field;
+        }
+    }
+    line += "\r\n";
+    writer.write_all(line.as_bytes())
+}
+
+pub fn game_event_key_value_string(key: &FullGameEventKey) -> String {
+    match key.key_type {
+        FullGameEventKeyType::Bool => format!("{}", key.val_bool.unwrap()),
+        FullGameEventKeyType::Byte |
+        FullGameEventKeyType::Long |
+        FullGameEventKeyType::Short => format!("{}", key.val_int.unwrap()),
+        FullGameEventKeyType::Float => format!("{}", key.val_float.unwrap()),
+        FullGameEventKeyType::String => key.val_string.as_ref().unwrap().clone(),
+    }
+}
+
+// the key's value, user ids are shown as "name (userid)" when it's known who had them at tick.
+pub fn game_event_key_player_string(key: &FullGameEventKey, tick: i32, players: &UserIdLookup) -> String {
+    match players.find_key(key, tick) {
+        Some((_, player)) => player.describe_at(tick),
+        None => game_event_key_value_string(key)
+    }
+}
+
+pub fn tick_to_time_string(tick_interval: f32, tick: i32) -> String {
+    let mut rval: String = "".into();
+    let seconds_per_tick: f64 = tick_interval as f64;
+    let mut seconds = seconds_per_tick * (tick as f64);
+
+    const ONE_MINUTE: f64 = 60.0;
+    const ONE_HOUR: f64 = 60.0 * ONE_MINUTE;
+
+    { // hours
+        let hours = seconds / ONE_HOUR;
+        let hours: u32 = hours as u32;
+        seconds -= hours as f64 * ONE_HOUR;
+
+        if hours > 0 {
+            rval += format!("{}h", hours).as_str();
+        }
+    }
+    { // minutes
+        let minutes = seconds / ONE_MINUTE;
+        let minutes: u32 = minutes as u32;
+        seconds -= minutes as f64 * ONE_MINUTE;
+
+        if !rval.is_empty() {
+            rval += format!("{:0>2}m", minutes).as_str();
+        } else if minutes > 0 {
+            rval += format!("{}m", minutes).as_str();
+        }
+    }
+    { // seconds
+        if !rval.is_empty() {
+            rval += format!("{:0>6.3}s", seconds).as_str();
+        } else {
+            rval += format!("{:.3}s", seconds).as_str();
+        }
+    }
+
+    rval
+}
+
+// flattens nested Proto/Repeated fields into "parent.child" / "parent[i]"
+// named fields, optionally skipping None values.
+pub fn flatten_protobuf_fields(
+    fields: Vec<(&'static str, ProtobufValue)>,
+    hide_none_values: bool
+) -> Vec<(String, ProtobufValue)> {
+    let mut int_field_data: Vec<(String, ProtobufValue)> = fields
+        .into_iter()
+        .map(|(name, val)| {
+          (name.to_string(), val)
+        })
+        .collect();
+
+    let mut field_data;
+    loop {
+        let mut did_work = false;
+        field_data = Vec::new();
+
+        for (field_name, field_val) in int_field_data {
+            match field_val {
+                ProtobufValue::Proto(vec_proto_fields) => {
+                    for (sub_field_name, sub_field_value) in vec_proto_fields {
+                        let sub_name = format!("{}.{}", field_name, sub_field_name);
+                        field_data.push((sub_name, sub_field_value));
+                    }
+                    did_work = true;
+                },
+                ProtobufValue::Repeated(vec_proto_values) => {
+                    let mut it = 0;
+                    for sub_value in vec_proto_values {
+                        let sub_name = format!("{}[{}]", field_name, it);
+                        field_data.push((sub_name, sub_value));
+                        it += 1;
+                    }
+                    did_work = true;
+                },
+                ProtobufValue::None => {
+                    if !hide_none_values {
+                        field_data.push((field_name, ProtobufValue::None));
+                    }
+                },
+                val => field_data.push((field_name, val))
+            }
+        }
+
+        if !did_work {
+            break
+        }
+
+        // did work, re-run the loop
+        int_field_data = field_data;
+    }
+
+    field_data
+}
+
+// display string for a flattened (non Proto/Repeated) value.
+pub fn protobuf_value_to_string(value: &ProtobufValue) -> String {
+    match value {
+        ProtobufValue::None => "None".to_string(),
+        ProtobufValue::VarInt(v) => v.to_string(),
+        ProtobufValue::Length(v) => format!("Data ({} Bytes)", v.len()),
+        ProtobufValue::String(v) => v.clone(),
+        ProtobufValue::Fixed32(v) => v.to_string(),
+        ProtobufValue::Float32(v) => v.to_string(),
+        ProtobufValue::Proto(_v) => panic!("Proto should be flattened out"),
+        ProtobufValue::Repeated(_v) => panic!("Repeated should be flattened out"),
+    }
+}
+
+// frame_index, frame
+pub fn write_frames_csv<'a, W: Write>(
+    writer: &mut W,
+    frames: impl Iterator<Item = (usize, &'a Frame)>,
+    tick_interval: f32
+) -> io::Result<()> {
+    write_csv_row(writer, &["Frame", "Tick", "Time", "Player Slot", "Command"])?;
+    for (index, frame) in frames {
+        write_csv_row(writer, &[
+            (index + 1).to_string(),
+            frame.tick.to_string(),
+            tick_to_time_string(tick_interval, frame.tick),
+            frame.player_slot.to_string(),
+            frame.command.get_command_str().to_owned(),
+        ])?;
+    }
+    Ok(())
+}
+
+// message_index, tick, name
+pub fn write_messages_csv<W: Write>(
+    writer: &mut W,
+    messages: impl Iterator<Item = (usize, Option<i32>, String)>,
+    tick_interval: Option<f32>
+) -> io::Result<()> {
+    match tick_interval {
+        Some(_) => write_csv_row(writer, &["Index", "Tick", "Time", "Name"])?,
+        None => write_csv_row(writer, &["Index", "Name"])?,
+    }
+    for (index, tick, name) in messages {
+        match tick_interval {
+            Some(tick_interval) => {
+                let tick = tick.unwrap_or(0);
+                write_csv_row(writer, &[
+                    (index + 1).to_string(),
+                    tick.to_string(),
+                    tick_to_time_string(tick_interval, tick),
+                    name,
+                ])?;
+            },
+            None => write_csv_row(writer, &[(index + 1).to_string(), name])?,
+        }
+    }
+    Ok(())
+}
+
+pub fn write_field_changes_csv<W: Write>(writer: &mut W, changes: &[FieldChange]) -> io::Result<()> {
+    write_csv_row(writer, &["Location", "Field", "Original", "Replacement"])?;
+    for change in changes {
+        write_csv_row(writer, &[&change.location, &change.field, &change.old, &change.new])?;
+    }
+    Ok(())
+}
+
+// event_index, event. with key_columns set, each event key gets its own column,
+// this only makes sense when all events are of the same type.
+pub fn write_game_events_csv<'a, W: Write>(
+    writer: &mut W,
+    events: impl Iterator<Item = (usize, &'a FullGameEvent)>,
+    tick_interval: f32,
+    key_columns: bool,
+    players: &UserIdLookup
+) -> io::Result<()> {
+    let mut events = events.peekable();
+    let mut header: Vec<String> = vec![
+        "Index".into(), "Tick".into(), "Time".into(), "Message (id)".into()
+    ];
+    if key_columns {
+        if let Some((_, first)) = events.peek() {
+            for key in &first.event_keys {
+                header.push(key.key_name.clone());
+            }
+        }
+    }
+    write_csv_row(writer, &header)?;
+
+    for (index, event) in events {
+        let mut row = vec![
+            (index + 1).to_string(),
+            event.event_tick.to_string(),
+            tick_to_time_string(tick_interval, event.event_tick),
+            format!("{} ({})", event.event_name, event.event_id),
+        ];
+        if key_columns {
+            for key in &event.event_keys {
+                row.push(game_event_key_player_string(key, event.event_tick, players));
+            }
+        }
+        write_csv_row(writer, &row)?;
+    }
+    Ok(())
+}
+
+pub fn write_class_descriptions_csv<W: Write>(writer: &mut W, data_tables: &DataTablesData) -> io::Result<()> {
+    write_csv_row(writer, &["Class ID", "Network Name", "Table Name"])?;
+    for cd in &data_tables.class_descriptions {
+        write_csv_row(writer, &[
+            cd.class_id.to_string(),
+            cd.network_name.clone(),
+            cd.table_name.clone(),
+        ])?;
+    }
+    Ok(())
+}
+
+// one row per send prop, send tables without props get a single row.
+pub fn write_send_tables_csv<W: Write>(writer: &mut W, data_tables: &DataTablesData) -> io::Result<()> {
+    fn opt<T: ToString>(v: &Option<T>) -> String {
+        match v {
+            Some(v) => v.to_string(),
+            None => "".into()
+        }
+    }
+    fn opt_hex<T: std::fmt::LowerHex>(v: &Option<T>) -> String {
+        match v {
+            Some(v) => format!("0x{:x}", v),
+            None => "".into()
+        }
+    }
+
+    write_csv_row(writer, &[
+        "Table Name", "Is End", "Needs Decoder",
+        "Prop Name", "Type", "DT Name", "Flags", "Priority",
+        "Hi-Value", "Lo-Value", "Bits", "Elements",
+    ])?;
+    for st in &data_tables.send_tables {
+        let table_fields = [
+            opt(&st.net_table_name),
+            opt(&st.is_end),
+            opt(&st.needs_decoder),
+        ];
+        if st.SendProp.is_empty() {
+            let mut row = table_fields.to_vec();
+            row.resize(12, "".into());
+            write_csv_row(writer, &row)?;
+        }
+        for sp in &st.SendProp {
+            let mut row = table_fields.to_vec();
+            row.append(&mut vec![
+                opt(&sp.var_name),
+                opt(&sp.sendprop_type),
+                opt(&sp.dt_name),
+                opt_hex(&sp.flags),
+                opt(&sp.priority),
+                opt_hex(&sp.high_value),
+                opt_hex(&sp.low_value),
+                opt(&sp.num_bits),
+                opt(&sp.num_elements),
+            ]);
+            write_csv_row(writer, &row)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn user_message_rows(user_messages: &[ParsedUserMessage]) -> Vec<(usize, Option<i32>, String)> {
+    let mut rows = Vec::new();
+    for (i, msg) in user_messages.iter().enumerate() {
+        let name = match &msg.message_return.message {
+            Some(m) => m.to_str().to_owned(),
+            None => "????".to_owned()
+        };
+        rows.push((i, Some(msg.tick), name));
+    }
+    rows
+}
\ No newline at end of file
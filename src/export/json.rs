@@ -0,0 +1,279 @@
+use std::io::{ self, Write };
+
+use serde_json::{ json, Map, Value };
+use source_demo_tool::{
+    demo_file::{
+        DemoFile,
+        FullGameEvent,
+        FullGameEventKey,
+        FullGameEventKeyType,
+        frame::{ Command, DataTablesData, Frame },
+        packet::{
+            MessageParseReturn,
+            netmessage::NetMessage,
+            protobuf_value::ProtobufValue,
+        },
+    },
+    protobuf_message::ProtobufMessageEnumTraits,
+};
+
+use crate::document::player_info::UserIdLookup;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonFormat {
+    // a single json document
+    Document,
+    // one json object per line, each with a "record" type
+    Lines,
+}
+
+impl JsonFormat {
+    pub fn from_extension(extension: &str) -> Self {
+        if extension.eq_ignore_ascii_case("ndjson") || extension.eq_ignore_ascii_case("jsonl") {
+            JsonFormat::Lines
+        } else {
+            JsonFormat::Document
+        }
+    }
+}
+
+pub fn protobuf_value_to_json(value: &ProtobufValue) -> Value {
+    match value {
+        ProtobufValue::None => Value::Null,
+        ProtobufValue::VarInt(v) => json!(v),
+        ProtobufValue::Length(v) => {
+            let hex: String = v.iter().map(|b| format!("{:02x}", b)).collect();
+            Value::String(hex)
+        },
+        ProtobufValue::String(v) => json!(v),
+        ProtobufValue::Fixed32(v) => json!(v),
+        ProtobufValue::Float32(v) => json!(v),
+        ProtobufValue::Proto(fields) => {
+            let mut map = Map::new();
+            for (name, val) in fields {
+                map.insert(name.to_string(), protobuf_value_to_json(val));
+            }
+            Value::Object(map)
+        },
+        ProtobufValue::Repeated(values) => {
+            Value::Array(values.iter().map(protobuf_value_to_json).collect())
+        },
+    }
+}
+
+// builds an object from the named fields of a protobuf message.
+pub fn protobuf_message_to_json(message: &dyn ProtobufMessageEnumTraits) -> Value {
+    let mut map = Map::new();
+    for (name, val) in message.to_vec() {
+        map.insert(name.to_string(), protobuf_value_to_json(&val));
+    }
+    Value::Object(map)
+}
+
+pub fn netmessage_to_json(index: usize, message_return: &MessageParseReturn<NetMessage>) -> Value {
+    let mut map = Map::new();
+    map.insert("index".into(), json!(index));
+    match &message_return.message {
+        Some(nmsg) => {
+            map.insert("type".into(), json!(nmsg.to_str()));
+            map.insert("id".into(), json!(nmsg.as_u64()));
+            map.insert("fields".into(), protobuf_message_to_json(nmsg));
+        },
+        None => {
+            map.insert("type".into(), Value::Null);
+        }
+    }
+    if let Some(err) = &message_return.err {
+        map.insert("error".into(), json!(format!("{:?}", err)));
+    }
+    if let Some(warns) = &message_return.warnings {
+        if warns.has_warnings() {
+            map.insert("warnings".into(), json!(format!("{:?}", warns)));
+        }
+    }
+    Value::Object(map)
+}
+
+pub fn header_to_json(demo_file: &DemoFile) -> Value {
+    let header = &demo_file.header;
+    json!({
+        "demo_protocol": header.demo_protocol,
+        "network_protocol": header.network_protocol,
+        "server_name": header.server_name,
+        "client_name": header.client_name,
+        "map_name": header.map_name,
+        "game_directory": header.game_directory,
+        "playback_time": header.playback_time,
+        "ticks": header.ticks,
+        "frames": header.frames,
+        "sign_on_length": header.sign_on_length,
+    })
+}
+
+// with include_messages unset, packet frames only carry their message count,
+// which is what the ndjson output uses as messages get their own records.
+pub fn frame_to_json(index: usize, frame: &Frame, include_messages: bool) -> Value {
+    let mut map = Map::new();
+    map.insert("index".into(), json!(index));
+    map.insert("tick".into(), json!(frame.tick));
+    map.insert("player_slot".into(), json!(frame.player_slot));
+    map.insert("command".into(), json!(frame.command.get_command_str()));
+
+    if let Command::Packet(pd) | Command::SignOn(pd) = &frame.command {
+        map.insert("in_seq".into(), json!(pd.header.in_seq));
+        map.insert("out_seq".into(), json!(pd.header.out_seq));
+        if include_messages {
+            let messages: Vec<Value> = pd.network_messages
+                .iter()
+                .enumerate()
+                .map(|(i, m)| netmessage_to_json(i, m))
+                .collect();
+            map.insert("messages".into(), Value::Array(messages));
+        } else {
+            map.insert("message_count".into(), json!(pd.network_messages.len()));
+        }
+    }
+    Value::Object(map)
+}
+
+pub fn game_event_key_to_json(key: &FullGameEventKey) -> Value {
+    match key.key_type {
+        FullGameEventKeyType::Bool => json!(key.val_bool),
+        FullGameEventKeyType::Byte |
+        FullGameEventKeyType::Long |
+        FullGameEventKeyType::Short => json!(key.val_int),
+        FullGameEventKeyType::Float => json!(key.val_float),
+        FullGameEventKeyType::String => json!(key.val_string),
+    }
+}
+
+// players holds who the user id keys refer to, keyed by the key's name
+pub fn game_event_to_json(index: usize, event: &FullGameEvent, user_ids: &UserIdLookup) -> Value {
+    let mut keys = Map::new();
+    let mut players = Map::new();
+    for key in &event.event_keys {
+        keys.insert(key.key_name.clone(), game_event_key_to_json(key));
+        if let Some((_, player)) = user_ids.find_key(key, event.event_tick) {
+            players.insert(key.key_name.clone(), json!({
+                "name": player.name_at(event.event_tick),
+                "user_id": player.info.user_id,
+                "xuid": player.info.xuid,
+            }));
+        }
+    }
+    json!({
+        "index": index,
+        "tick": event.event_tick,
+        "name": event.event_name,
+        "id": event.event_id,
+        "frame_index": event.frame_index,
+        "keys": keys,
+        "players": players,
+    })
+}
+
+pub fn data_tables_to_json(data_tables: &DataTablesData) -> Value {
+    let send_tables: Vec<Value> = data_tables.send_tables
+        .iter()
+        .map(|st| protobuf_message_to_json(&NetMessage::SendTable(st.clone())))
+        .collect();
+    let class_descriptions: Vec<Value> = data_tables.class_descriptions
+        .iter()
+        .map(|cd| json!({
+            "class_id": cd.class_id,
+            "network_name": cd.network_name,
+            "table_name": cd.table_name,
+        }))
+        .collect();
+    json!({
+        "send_tables": send_tables,
+        "class_descriptions": class_descriptions,
+    })
+}
+
+fn write_record<W: Write>(writer: &mut W, record: &str, value: Value) -> io::Result<()> {
+    let mut map = Map::new();
+    map.insert("record".into(), json!(record));
+    match value {
+        Value::Object(fields) => map.extend(fields),
+        value => { map.insert("value".into(), value); }
+    }
+    serde_json::to_writer(&mut *writer, &Value::Object(map))?;
+    writer.write_all(b"\n")
+}
+
+// writes the header, frames, netmessages, game events and data tables of a demo.
+// frames are streamed one at a time so large demos aren't held as json in memory.
+pub fn write_demo_json<W: Write>(
+    writer: &mut W,
+    demo_file: &DemoFile,
+    user_ids: &UserIdLookup,
+    format: JsonFormat
+) -> io::Result<()> {
+    let game_events = demo_file.get_full_game_events();
+    let frame_sections = [
+        ("sign_on_frames", &demo_file.sign_on_frames),
+        ("frames", &demo_file.frames),
+    ];
+
+    match format {
+        JsonFormat::Lines => {
+            write_record(writer, "header", header_to_json(demo_file))?;
+            for (section, frames) in frame_sections {
+                for (frame_index, frame) in frames.iter().enumerate() {
+                    let mut frame_json = frame_to_json(frame_index, frame, false);
+                    frame_json["section"] = json!(section);
+                    write_record(writer, "frame", frame_json)?;
+
+                    if let Command::Packet(pd) | Command::SignOn(pd) = &frame.command {
+                        for (i, nmsg) in pd.network_messages.iter().enumerate() {
+                            let mut msg_json = netmessage_to_json(i, nmsg);
+                            msg_json["section"] = json!(section);
+                            msg_json["frame_index"] = json!(frame_index);
+                            msg_json["tick"] = json!(frame.tick);
+                            write_record(writer, "netmessage", msg_json)?;
+                        }
+                    }
+                }
+            }
+            for (i, ev) in game_events.iter().enumerate() {
+                write_record(writer, "game_event", game_event_to_json(i, ev, user_ids))?;
+            }
+            for dt in demo_file.get_data_tables() {
+                write_record(writer, "data_tables", data_tables_to_json(dt))?;
+            }
+        },
+        JsonFormat::Document => {
+            writer.write_all(b"{\"header\":")?;
+            serde_json::to_writer(&mut *writer, &header_to_json(demo_file))?;
+
+            for (section, frames) in frame_sections {
+                write!(writer, ",\"{}\":[", section)?;
+                for (frame_index, frame) in frames.iter().enumerate() {
+                    if frame_index != 0 {
+                        writer.write_all(b",")?;
+                    }
+                    serde_json::to_writer(&mut *writer, &frame_to_json(frame_index, frame, true))?;
+                }
+                writer.write_all(b"]")?;
+            }
+
+            let game_events: Vec<Value> = game_events
+                .iter()
+                .enumerate()
+                .map(|(i, ev)| game_event_to_json(i, ev, user_ids))
+                .collect();
+            writer.write_all(b",\"game_events\":")?;
+            serde_json::to_writer(&mut *writer, &game_events)?;
+
+            let data_tables: Vec<Value> = demo_file.get_data_tables()
+                .into_iter()
+                .map(data_tables_to_json)
+                .collect();
+            writer.write_all(b",\"data_tables\":")?;
+            serde_json::to_writer(&mut *writer, &data_tables)?;
+            writer.write_all(b"}\n")?;
+        },
+    }
+    Ok(())
+}
\ No newline at end of file
@@ -20,64 +20,59 @@ mod vm_game_events_tool;
 mod vm_abouthelp;
 mod vm_data_tables;
 mod vm_tasks_tool;
+mod vm_history_tool;
+mod vm_entities_tool;
+mod vm_prop_history;
+mod vm_string_tables_tool;
+mod vm_players_tool;
+mod vm_archive_entries;
+mod vm_diff;
+mod vm_map_switch;
+mod vm_strip_messages;
 // widgets: small tools for displaying common gui components.
 mod w_copyable_field;
 // widget functions: small tools for displaying common gui components implemented as functions.
 mod wfn_text_edit_singleline;
+mod wfn_export_csv_button;
+mod wfn_message_checklist;
 
 use vm_main::MainViewModel;
+use crate::edit::{
+    journal::DocumentEdit,
+    map_switch::MapSwitch,
+    strip::StripMessages,
+    trim::Trim,
+};
 
 pub struct NewCrawlerApp {
     main_view_model: MainViewModel,
     events: Vec<Event>,
     frame_counter: usize,
-    is_first_run: bool,
+    window_title: String,
 }
 
 impl NewCrawlerApp {
-    pub fn new(_cc: &eframe::CreationContext) -> Self {
+    pub fn new(_cc: &eframe::CreationContext, startup_file: Option<PathBuf>) -> Self {
+        let mut events = Vec::new();
+        if let Some(path) = startup_file {
+            events.push(Event::OpenFile(path));
+        }
+
         NewCrawlerApp {
             main_view_model: MainViewModel::new(),
-            events: Vec::new(),
+            events,
             frame_counter: 1,
-            is_first_run: true,
+            window_title: String::new(),
         }
     }
 }
 
 impl eframe::App for NewCrawlerApp {
     fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
-        const VERSION: &str = env!("CARGO_PKG_VERSION");
-        let title = format!(
-            "Source Demo Crawler v{}",
-            VERSION
-        );
-        if self.is_first_run {
-            frame.set_window_title(title.as_str());
-            self.is_first_run = false;
-        }
-
-
         let mut unhandled_events = Vec::new();
-        let mut event_iter = self.events.iter();
-        loop {
-            match event_iter.next() {
-                Some(event) => {
-                    if let Event::NewFile(filepath) = event {
-                        let title = format!(
-                            "Source Demo Crawler v{} -- {}",
-                            VERSION,
-                            filepath.file_stem().unwrap().to_str().unwrap()
-                        );
-                        frame.set_window_title(title.as_str());
-                        continue
-                    }
-
-                    if !self.main_view_model.handle_event(event) {
-                        unhandled_events.push(event);
-                    }
-                },
-                None => break
+        for event in &self.events {
+            if !self.main_view_model.handle_event(event) {
+                unhandled_events.push(event);
             }
         }
 
@@ -90,6 +85,24 @@ impl eframe::App for NewCrawlerApp {
 
         self.events.clear();
 
+        // window title follows the active tab
+        const VERSION: &str = env!("CARGO_PKG_VERSION");
+        let title = match self.main_view_model.get_active_file_path() {
+            Some(filepath) => format!(
+                "Source Demo Crawler v{} -- {}",
+                VERSION,
+                filepath.file_stem().unwrap_or_default().to_string_lossy()
+            ),
+            None => format!(
+                "Source Demo Crawler v{}",
+                VERSION
+            )
+        };
+        if title != self.window_title {
+            frame.set_window_title(title.as_str());
+            self.window_title = title;
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
             self.main_view_model.draw(ui, &mut self.events);
         });
@@ -118,28 +131,83 @@ pub enum Filters {
 
 pub enum Event {
     BeginOpenFile,
-    NewFile(PathBuf),
+    OpenFile(PathBuf),
+    OpenArchiveEntry(PathBuf, String),
+    CancelOpenArchive,
+    SelectTab(usize),
+    CloseTab(usize),
     SetFocus(Focusable),
     SelectFrame(&'static str, usize),
     SelectMessage(&'static str, usize),
     SetTool(&'static str),
     SelectGameEvent(usize),
+    // an index into the Players tool's players
+    SelectPlayer(usize),
     ClearFilter(Filters),
     EmitNetMsgWarnErrs,
+    BeginDiff(Option<usize>),
+    CloseDiff,
+    ExportJson,
+    SaveDemoAs,
+    RoundTripCheck,
+    BeginMapSwitch,
+    PreviewMapSwitch(MapSwitch),
+    SaveMapSwitch(MapSwitch),
+    CloseMapSwitch,
+    TrimDemo(Trim),
+    AnonymizePlayers,
+    RepairDemo,
+    ConcatenateDemos,
+    // edits to the open document go through its journal, DocumentEdited is sent
+    // to every tool with the edit that was made, including undos and redos
+    ApplyEdit(DocumentEdit),
+    DocumentEdited(DocumentEdit),
+    Undo,
+    Redo,
+    ExportPatch,
+    BeginStripMessages,
+    SaveStrippedDemo(StripMessages),
+    CloseStripMessages,
 }
 
 impl Display for Event {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self {
             Event::BeginOpenFile => f.write_str("BeginOpenFile"),
-            Event::NewFile(filepath) => f.write_fmt(format_args!("NewFile({})", filepath.to_str().unwrap())),
+            Event::OpenFile(filepath) => f.write_fmt(format_args!("OpenFile({})", filepath.display())),
+            Event::OpenArchiveEntry(filepath, entry) => f.write_fmt(format_args!("OpenArchiveEntry({}, {})", filepath.display(), entry)),
+            Event::CancelOpenArchive => f.write_str("CancelOpenArchive"),
+            Event::SelectTab(index) => f.write_fmt(format_args!("SelectTab({})", index)),
+            Event::CloseTab(index) => f.write_fmt(format_args!("CloseTab({})", index)),
             Event::SetFocus(foc) => f.write_fmt(format_args!("SetFocus({:?})", foc)),
             Event::SelectFrame(tool_name, index) => f.write_fmt(format_args!("SelectFrame({}, {})", tool_name, index)),
             Event::SelectMessage(tool, index) => f.write_fmt(format_args!("SelectMessage({}, {})", tool, index)),
             Event::SetTool(tool_name) => f.write_fmt(format_args!("SetTool({})", tool_name)),
             Event::SelectGameEvent(index) => f.write_fmt(format_args!("SelectGameEvent({})", index)),
+            Event::SelectPlayer(index) => f.write_fmt(format_args!("SelectPlayer({})", index)),
             Event::ClearFilter(filt) => f.write_fmt(format_args!("ClearFilter({:?})", filt)),
             Event::EmitNetMsgWarnErrs => f.write_str("EmitNetMsgWarnErrs"),
+            Event::BeginDiff(index) => f.write_fmt(format_args!("BeginDiff({:?})", index)),
+            Event::CloseDiff => f.write_str("CloseDiff"),
+            Event::ExportJson => f.write_str("ExportJson"),
+            Event::SaveDemoAs => f.write_str("SaveDemoAs"),
+            Event::RoundTripCheck => f.write_str("RoundTripCheck"),
+            Event::BeginMapSwitch => f.write_str("BeginMapSwitch"),
+            Event::PreviewMapSwitch(map_switch) => f.write_fmt(format_args!("PreviewMapSwitch({})", map_switch.map_name)),
+            Event::SaveMapSwitch(map_switch) => f.write_fmt(format_args!("SaveMapSwitch({})", map_switch.map_name)),
+            Event::CloseMapSwitch => f.write_str("CloseMapSwitch"),
+            Event::AnonymizePlayers => f.write_str("AnonymizePlayers"),
+            Event::RepairDemo => f.write_str("RepairDemo"),
+            Event::ConcatenateDemos => f.write_str("ConcatenateDemos"),
+            Event::ApplyEdit(edit) => f.write_fmt(format_args!("ApplyEdit({})", edit.kind())),
+            Event::DocumentEdited(edit) => f.write_fmt(format_args!("DocumentEdited({})", edit.kind())),
+            Event::Undo => f.write_str("Undo"),
+            Event::Redo => f.write_str("Redo"),
+            Event::ExportPatch => f.write_str("ExportPatch"),
+            Event::BeginStripMessages => f.write_str("BeginStripMessages"),
+            Event::SaveStrippedDemo(strip) => f.write_fmt(format_args!("SaveStrippedDemo({:?}, {:?})", strip.net_message_ids, strip.user_message_ids)),
+            Event::CloseStripMessages => f.write_str("CloseStripMessages"),
+            Event::TrimDemo(trim) => f.write_fmt(format_args!("TrimDemo({}, {})", trim.start_tick, trim.end_tick)),
         }
     }
 }
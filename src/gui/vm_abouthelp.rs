@@ -83,6 +83,7 @@ impl ViewModel for AboutHelpViewModel {
             ui.label("⬆/⬇ to move between items in lists.");
             ui.label("Ctrl + ⬆/⬇ to move to the beginning/end of a list.");
             ui.label("Shift + ⬆/⬇ to move 10 items at a time through a list.");
+            ui.label("Ctrl + Z / Ctrl + Shift + Z to undo/redo edits.");
 
             // changelog
             ui.scope(|ui| {
@@ -0,0 +1,48 @@
+use super::{ Event, ViewModel };
+use eframe::egui::{ self, RichText };
+use std::path::PathBuf;
+
+pub struct ArchiveEntriesViewModel {
+    archive_path: PathBuf,
+    entries: Vec<String>,
+}
+
+impl ArchiveEntriesViewModel {
+    pub fn new(archive_path: PathBuf, entries: Vec<String>) -> Self {
+        Self { archive_path, entries }
+    }
+}
+
+impl ViewModel for ArchiveEntriesViewModel {
+    fn draw(&mut self, ui: &mut egui::Ui, events: &mut Vec<Event>) {
+        ui.vertical_centered(|ui| {
+            ui.add_space(15.0);
+            ui.label(RichText::new("Select a demo to open").size(32.0).italics());
+            ui.label(self.archive_path.file_name().unwrap_or_default().to_string_lossy());
+            ui.add_space(7.5);
+            ui.separator();
+            ui.add_space(7.5);
+
+            egui::ScrollArea::vertical()
+            .max_height(ui.available_height() - 48.0)
+            .show(ui, |ui| {
+                for entry in &self.entries {
+                    if ui.add(
+                        egui::Button::new(entry.as_str())
+                        .min_size([320.0, 24.0].into())
+                    ).clicked() {
+                        events.push(Event::OpenArchiveEntry(self.archive_path.clone(), entry.clone()));
+                    }
+                }
+            });
+
+            ui.add_space(7.5);
+            if ui.button("Cancel").clicked() {
+                events.push(Event::CancelOpenArchive);
+            }
+        });
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any { self }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any { self }
+}
\ No newline at end of file
@@ -9,8 +9,22 @@ use super::{
     vm_game_events_tool::GameEventsToolViewModel,
     vm_abouthelp::AboutHelpViewModel,
     vm_tasks_tool::TasksToolViewModel,
+    vm_history_tool::HistoryToolViewModel,
+    vm_entities_tool::EntitiesToolViewModel,
+    vm_string_tables_tool::StringTablesToolViewModel,
+    vm_players_tool::PlayersToolViewModel,
+};
+use std::collections::HashMap;
+use source_demo_tool::demo_file::{
+    DemoFile,
+    frame::{ Command, Frame },
+    packet::netmessage::NetMessage,
+};
+use crate::{
+    archive::DemoSource,
+    document::{ DemoDocument, entities::EntityTimeline, net_message_id, player_info::UserIdLookup },
+    edit::{ journal::EditJournal, protobuf_field::MessageTarget, repair::Repair },
 };
-use source_demo_tool::demo_file::DemoFile;
 use eframe::{
     egui::{
         self,
@@ -34,6 +48,7 @@ const TOOL_STROKE_COLOUR: Color32 = Color32::from_rgb(255, 220, 255);
 const TOOL_ACTIVE_HOVER_COLOUR: Color32 = Color32::from_rgb(60, 60, 60);
 const TOOL_STROKE_WIDTH: f32 = 1.0;
 const TOOL_ROUNDING_RADIUS: f32 = 4.0;
+const PENDING_CHANGES_COLOUR: Color32 = Color32::LIGHT_YELLOW;
 
 pub struct DemoFileTools {
     name: &'static str,
@@ -43,6 +58,13 @@ pub struct DemoFileTools {
 
 pub struct DemoFileViewModel {
     pub demo_file: DemoFile,
+    // the demo as laid out on disk, this is what gets saved
+    pub document: DemoDocument,
+    pub source: DemoSource,
+    // the players from the userinfo table, empty when it couldn't be decoded
+    pub user_ids: UserIdLookup,
+    // every edit made to the document since it was opened, for undo and redo
+    pub journal: EditJournal,
     tools: Vec<DemoFileTools>,
     active_tool_index: usize,
     hover_tool_index: Option<usize>,
@@ -51,8 +73,9 @@ pub struct DemoFileViewModel {
 }
 
 impl DemoFileViewModel {
-    pub fn new(demo_file: DemoFile) -> Self {
+    pub fn new(demo_file: DemoFile, document: DemoDocument, source: DemoSource) -> Self {
         let header = demo_file.header.clone();
+        let problems = Repair::problems(&document);
         let tick_interval = match demo_file.get_server_info() {
             Some(si) => si.tick_interval.unwrap_or(0.0),
             None => 0.0
@@ -64,12 +87,27 @@ impl DemoFileViewModel {
             Some(si) => Some(si.clone()),
             None => None
         };
+        let server_info_target = document
+            .message_locations(net_message_id::SERVER_INFO)
+            .first()
+            .map(|&location| MessageTarget { location, user_message: false });
+        let frame_indices = document.demo_file_frame_indices(false);
+        let sign_on_frame_indices = document.demo_file_frame_indices(true);
         let game_events = demo_file.get_full_game_events();
         let game_event_ld = demo_file.get_game_event_list();
         let game_event_ld = match game_event_ld {
             Some(ge_ld) => Some(ge_ld.clone()),
             None => None
         };
+        let entity_timeline = EntityTimeline::from_document(&document);
+        let user_ids = UserIdLookup::from_document(&document);
+        let vm_players = PlayersToolViewModel::new(
+            user_ids.clone(),
+            &game_events,
+            &user_messages,
+            avatars(demo_file.sign_on_frames.iter().chain(&demo_file.frames)),
+            tick_interval
+        );
 
         let tools: Vec<DemoFileTools> = vec![
             DemoFileTools {
@@ -84,38 +122,62 @@ impl DemoFileViewModel {
             },
             DemoFileTools {
                 name: "Header",
-                vm: Box::new(HeaderToolViewModel::new(header)),
+                vm: Box::new(HeaderToolViewModel::new(header, problems)),
                 focus: Focusable::None,
             },
             DemoFileTools {
                 name: "Server Info",
-                vm: Box::new(ServerInfoViewModel::new(server_info)),
+                vm: Box::new(ServerInfoViewModel::new(server_info, server_info_target)),
                 focus: Focusable::None,
             },
             DemoFileTools {
                 name: "Frames",
-                vm: Box::new(FramesToolViewModel::new("Frames", frames, tick_interval, game_event_ld.clone())),
+                vm: Box::new(FramesToolViewModel::new("Frames", frames, tick_interval, game_event_ld.clone(), frame_indices.clone())),
                 focus: Focusable::FramesListViewModel,
             },
+            DemoFileTools {
+                name: "Entities",
+                vm: Box::new(EntitiesToolViewModel::new(entity_timeline, tick_interval)),
+                focus: Focusable::None,
+            },
+            DemoFileTools {
+                name: "String Tables",
+                vm: Box::new(StringTablesToolViewModel::new(&document, tick_interval)),
+                focus: Focusable::None,
+            },
+            DemoFileTools {
+                name: "Players",
+                vm: Box::new(vm_players),
+                focus: Focusable::None,
+            },
             DemoFileTools {
                 name: "Sign On Frames",
-                vm: Box::new(FramesToolViewModel::new("SignOnFrames", sign_on_frames, tick_interval, game_event_ld.clone())),
+                vm: Box::new(FramesToolViewModel::new("SignOnFrames", sign_on_frames, tick_interval, game_event_ld.clone(), sign_on_frame_indices)),
                 focus: Focusable::FramesListViewModel,
             },
             DemoFileTools {
                 name: "User Messages",
-                vm: Box::new(UserMessagesToolViewModel::new(user_messages, tick_interval)),
+                vm: Box::new(UserMessagesToolViewModel::new(user_messages, tick_interval, frame_indices)),
                 focus: Focusable::ProtobufMessageListViewModel("user_messages"),
             },
             DemoFileTools {
                 name: "Game Events",
-                vm: Box::new(GameEventsToolViewModel::new(game_events, tick_interval)),
+                vm: Box::new(GameEventsToolViewModel::new(game_events, user_ids.clone().unwrap_or_default(), tick_interval)),
                 focus: Focusable::GameEventsList,
             },
+            DemoFileTools {
+                name: "History",
+                vm: Box::new(HistoryToolViewModel::default()),
+                focus: Focusable::None,
+            },
         ];
 
         Self {
             demo_file,
+            document,
+            source,
+            user_ids: user_ids.unwrap_or_default(),
+            journal: EditJournal::default(),
             tools,
             active_tool_index: 2, // header tool
             hover_tool_index: None,
@@ -124,6 +186,51 @@ impl DemoFileViewModel {
         }
     }
 
+    // shows the journal in the history tool, called whenever it changes.
+    pub fn update_history(&mut self) {
+        for tool in &mut self.tools {
+            if let Some(vm) = tool.vm.as_any_mut().downcast_mut::<HistoryToolViewModel>() {
+                vm.set_journal(&self.journal);
+            }
+        }
+    }
+
+    // passes the tick selected in the Frames tool to the entities and string tables tools,
+    // only while they're shown as they decode the document at it.
+    fn update_tick_tools(&mut self) {
+        let frames_tick = self.tools
+            .iter()
+            .filter_map(|t| t.vm.as_any().downcast_ref::<FramesToolViewModel>())
+            .find(|vm| vm.name == "Frames")
+            .and_then(|vm| vm.active_tick());
+        let tool = self.tools[self.active_tool_index].vm.as_any_mut();
+        if let Some(vm) = tool.downcast_mut::<EntitiesToolViewModel>() {
+            vm.update(&self.document, frames_tick);
+        } else if let Some(vm) = tool.downcast_mut::<StringTablesToolViewModel>() {
+            vm.update(&self.document, frames_tick);
+        }
+    }
+
+    // tells every tool about an edit made to the document so they can show it.
+    fn send_document_edited(&mut self, event: Event) {
+        for tool in &mut self.tools {
+            tool.vm.handle_event(&event);
+        }
+        self.update_history();
+    }
+
+    fn show_edit_error(title: &str, message: String) {
+        rfd::MessageDialog::new()
+            .set_level(rfd::MessageLevel::Error)
+            .set_description(message.as_str())
+            .set_title(title)
+            .show();
+    }
+
+    pub fn get_active_focus(&self) -> Focusable {
+        self.tools[self.active_tool_index].focus.clone()
+    }
+
     pub fn get_active_tool(&mut self) -> &mut dyn ViewModel {
         &mut *self.tools[self.active_tool_index].vm
     }
@@ -236,11 +343,22 @@ impl ViewModel for DemoFileViewModel {
 
                 tool_res.on_hover_cursor(CursorIcon::PointingHand);
             }
+
+            let unsaved_count = self.journal.unsaved_count();
+            if unsaved_count > 0 {
+                let changes: Vec<&str> = self.journal.done()
+                    .iter()
+                    .map(|e| e.description.as_str())
+                    .collect();
+                ui.label(RichText::new(format!("● {} unsaved edits", unsaved_count)).color(PENDING_CHANGES_COLOUR))
+                    .on_hover_text(changes.join("\n"));
+            }
         });
 
         // draw tool
         ui.separator();
 
+        self.update_tick_tools();
         egui::Frame::none().show(ui, |ui| {
             self.tools[self.active_tool_index].vm.draw(ui, events);
         });
@@ -252,6 +370,36 @@ impl ViewModel for DemoFileViewModel {
             return true
         }
 
+        match event {
+            Event::ApplyEdit(edit) => {
+                match self.journal.apply(&mut self.document, edit) {
+                    Ok(entry) => {
+                        let applied = entry.edit.clone();
+                        self.send_document_edited(Event::DocumentEdited(applied));
+                    },
+                    Err(e) => Self::show_edit_error("Edit", format!("Failed to apply the edit: {}", e))
+                }
+                return true
+            },
+            Event::Undo => {
+                match self.journal.undo(&mut self.document) {
+                    Some(Ok(applied)) => self.send_document_edited(Event::DocumentEdited(applied)),
+                    Some(Err(e)) => Self::show_edit_error("Undo", format!("Failed to undo the edit: {}", e)),
+                    None => {}
+                }
+                return true
+            },
+            Event::Redo => {
+                match self.journal.redo(&mut self.document) {
+                    Some(Ok(applied)) => self.send_document_edited(Event::DocumentEdited(applied)),
+                    Some(Err(e)) => Self::show_edit_error("Redo", format!("Failed to redo the edit: {}", e)),
+                    None => {}
+                }
+                return true
+            },
+            _ => {}
+        }
+
         if let Event::SetTool(tool_name) = event {
             return self.set_active_tool_by_name(tool_name)
         }
@@ -268,41 +416,19 @@ impl ViewModel for DemoFileViewModel {
     fn as_any_mut(&mut self) -> &mut dyn std::any::Any { self }
 }
 
-pub fn tick_to_time_string(tick_interval: f32, tick: i32) -> String {
-    let mut rval: String = "".into();
-    let seconds_per_tick: f64 = tick_interval as f64;
-    let mut seconds = seconds_per_tick * (tick as f64);
-
-    const ONE_MINUTE: f64 = 60.0;
-    const ONE_HOUR: f64 = 60.0 * ONE_MINUTE;
-
-    { // hours
-        let hours = seconds / ONE_HOUR;
-        let hours: u32 = hours as u32;
-        seconds -= hours as f64 * ONE_HOUR;
-
-        if hours > 0 {
-            rval += format!("{}h", hours).as_str();
-        }
-    }
-    { // minutes
-        let minutes = seconds / ONE_MINUTE;
-        let minutes: u32 = minutes as u32;
-        seconds -= minutes as f64 * ONE_MINUTE;
-
-        if !rval.is_empty() {
-            rval += format!("{:0>2}m", minutes).as_str();
-        } else if minutes > 0 {
-            rval += format!("{}m", minutes).as_str();
-        }
-    }
-    { // seconds
-        if !rval.is_empty() {
-            rval += format!("{:0>6.3}s", seconds).as_str();
-        } else {
-            rval += format!("{:.3}s", seconds).as_str();
+// the avatars sent in AvatarData netmessages by steam account id, later ones replace earlier ones
+fn avatars<'a>(frames: impl Iterator<Item = &'a Frame>) -> HashMap<u32, Vec<u8>> {
+    let mut avatars = HashMap::new();
+    for frame in frames {
+        if let Command::Packet(pd) | Command::SignOn(pd) = &frame.command {
+            for msg in &pd.network_messages {
+                if let Some(NetMessage::AvatarData(ad)) = &msg.message {
+                    if let (Some(account_id), Some(rgb)) = (ad.account_id, &ad.rgb_bytes) {
+                        avatars.insert(account_id as u32, rgb.clone());
+                    }
+                }
+            }
         }
     }
-
-    rval
+    avatars
 }
\ No newline at end of file
@@ -0,0 +1,202 @@
+use super::{ Event, ViewModel, table_constants };
+use crate::diff::{ DemoDiff, DiffKind };
+use eframe::{egui::{ self, Color32, Layout, RichText }, emath::Align};
+use egui_extras::{ TableBuilder, Column };
+
+const SECTION_LIST_WIDTH: f32 = 220.0;
+const FIELD_NAME_WIDTH: f32 = 280.0;
+const ADDED_COLOUR: Color32 = Color32::from_rgb(120, 220, 120);
+const REMOVED_COLOUR: Color32 = Color32::from_rgb(240, 110, 110);
+const CHANGED_COLOUR: Color32 = Color32::LIGHT_YELLOW;
+
+// lets the user pick which of the other open tabs to compare against.
+pub struct DiffTargetViewModel {
+    // tab index, name
+    targets: Vec<(usize, String)>,
+}
+
+impl DiffTargetViewModel {
+    pub fn new(targets: Vec<(usize, String)>) -> Self {
+        Self { targets }
+    }
+}
+
+impl ViewModel for DiffTargetViewModel {
+    fn draw(&mut self, ui: &mut egui::Ui, events: &mut Vec<Event>) {
+        ui.vertical_centered(|ui| {
+            ui.add_space(15.0);
+            ui.label(RichText::new("Compare with").size(32.0).italics());
+            ui.add_space(7.5);
+            ui.separator();
+            ui.add_space(7.5);
+
+            for (tab_index, name) in &self.targets {
+                if ui.add(
+                    egui::Button::new(name.as_str())
+                    .min_size([320.0, 24.0].into())
+                ).clicked() {
+                    events.push(Event::BeginDiff(Some(*tab_index)));
+                }
+            }
+
+            ui.add_space(7.5);
+            if ui.button("Cancel").clicked() {
+                events.push(Event::CloseDiff);
+            }
+        });
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any { self }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any { self }
+}
+
+pub struct DiffViewModel {
+    diff: DemoDiff,
+    active_section_index: usize,
+    show_unchanged: bool,
+    // row indices of the active section that are displayed
+    display_rows: Vec<usize>,
+}
+
+impl DiffViewModel {
+    pub fn new(diff: DemoDiff) -> Self {
+        // open on the first section with differences
+        let active_section_index = diff.sections
+            .iter()
+            .position(|s| s.has_differences())
+            .unwrap_or(0);
+
+        let mut vm = Self {
+            diff,
+            active_section_index,
+            show_unchanged: false,
+            display_rows: Vec::new(),
+        };
+        vm.update_display_rows();
+        vm
+    }
+
+    fn update_display_rows(&mut self) {
+        self.display_rows = match self.diff.sections.get(self.active_section_index) {
+            Some(section) => section.rows
+                .iter()
+                .enumerate()
+                .filter(|(_, row)| self.show_unchanged || row.kind != DiffKind::Same)
+                .map(|(i, _)| i)
+                .collect(),
+            None => Vec::new()
+        };
+    }
+
+    fn kind_colour(kind: DiffKind) -> Option<Color32> {
+        match kind {
+            DiffKind::Same => None,
+            DiffKind::Added => Some(ADDED_COLOUR),
+            DiffKind::Removed => Some(REMOVED_COLOUR),
+            DiffKind::Changed => Some(CHANGED_COLOUR),
+        }
+    }
+}
+
+impl ViewModel for DiffViewModel {
+    fn draw(&mut self, ui: &mut egui::Ui, events: &mut Vec<Event>) {
+        ui.horizontal(|ui| {
+            ui.label(RichText::new(format!(
+                "{}  ⟷  {}",
+                self.diff.left_name,
+                self.diff.right_name
+            )).size(16.0));
+
+            ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                ui.add_space(20.0);
+                if ui.button("Close").clicked() {
+                    events.push(Event::CloseDiff);
+                }
+                if ui.checkbox(&mut self.show_unchanged, "Show Unchanged").changed() {
+                    self.update_display_rows();
+                }
+                ui.label(RichText::new("changed").color(CHANGED_COLOUR));
+                ui.label(RichText::new("removed").color(REMOVED_COLOUR));
+                ui.label(RichText::new("added").color(ADDED_COLOUR));
+            });
+        });
+        ui.separator();
+
+        let avail_height = ui.available_height() - table_constants::BOTTOM_MARGIN;
+        ui.horizontal_top(|ui| {
+            ui.vertical(|ui| {
+                ui.set_width(SECTION_LIST_WIDTH);
+                ui.set_height(avail_height);
+
+                let mut new_section_index = None;
+                for (i, section) in self.diff.sections.iter().enumerate() {
+                    let text = format!(
+                        "{} (+{} -{} ~{})",
+                        section.name,
+                        section.count(DiffKind::Added),
+                        section.count(DiffKind::Removed),
+                        section.count(DiffKind::Changed),
+                    );
+                    let text = if section.has_differences() {
+                        RichText::new(text)
+                    } else {
+                        RichText::new(text).weak()
+                    };
+                    if ui.selectable_label(i == self.active_section_index, text).clicked() {
+                        new_section_index = Some(i);
+                    }
+                }
+                if let Some(i) = new_section_index {
+                    self.active_section_index = i;
+                    self.update_display_rows();
+                }
+            });
+            ui.separator();
+
+            ui.vertical(|ui| {
+                ui.set_height(avail_height);
+                let section = &self.diff.sections[self.active_section_index];
+                if let Some(note) = &section.note {
+                    ui.label(RichText::new(note).color(CHANGED_COLOUR));
+                }
+                if self.display_rows.is_empty() {
+                    ui.label("No differences.");
+                    return
+                }
+
+                let value_width = (ui.available_width() - FIELD_NAME_WIDTH) / 2.0;
+                ui.push_id(section.name, |ui| {
+                    TableBuilder::new(ui)
+                    .striped(true)
+                    .column(Column::initial(FIELD_NAME_WIDTH).resizable(true))
+                    .column(Column::initial(value_width).resizable(true))
+                    .column(Column::remainder())
+                    .header(table_constants::HEADER_HEIGHT, |mut row| {
+                        row.col(|ui| { ui.label("Name"); });
+                        row.col(|ui| { ui.label(&self.diff.left_name); });
+                        row.col(|ui| { ui.label(&self.diff.right_name); });
+                    })
+                    .body(|body| {
+                        body.rows(table_constants::ROW_HEIGHT, self.display_rows.len(), |index, mut row| {
+                            let diff_row = &section.rows[self.display_rows[index]];
+                            let colour = Self::kind_colour(diff_row.kind);
+                            let cell = |ui: &mut egui::Ui, text: &str| {
+                                match colour {
+                                    Some(c) => ui.label(RichText::new(text).color(c)),
+                                    None => ui.label(text),
+                                };
+                            };
+
+                            row.col(|ui| cell(ui, &diff_row.name));
+                            row.col(|ui| cell(ui, diff_row.left.as_deref().unwrap_or("-")));
+                            row.col(|ui| cell(ui, diff_row.right.as_deref().unwrap_or("-")));
+                        });
+                    });
+                });
+            });
+        });
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any { self }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any { self }
+}
\ No newline at end of file
@@ -0,0 +1,412 @@
+use std::{ sync::Arc, thread::{ self, JoinHandle } };
+
+use super::{
+    Event,
+    ViewModel,
+    vm_prop_history::PropHistoryViewModel,
+    table_constants,
+};
+use crate::{
+    document::{
+        DemoDocument,
+        entities::{ EntityState, EntityTimeline },
+    },
+    edit::journal::DocumentEdit,
+    export::tick_to_time_string,
+};
+use eframe::egui::{ self, Color32, CursorIcon, RichText, Sense };
+use egui_extras::{ TableBuilder, Column };
+
+const ENTITY_LIST_WIDTH: f32 = 420.0;
+const COL_SERIAL_WIDTH: f32 = 60.0;
+const PROP_NAME_WIDTH: f32 = 260.0;
+const PLOT_BUTTON_WIDTH: f32 = 24.0;
+const ERROR_COLOUR: Color32 = Color32::from_rgb(240, 110, 110);
+
+// the entities at a tick, it follows the tick of the frame selected in the Frames tool.
+// DemoFileViewModel calls update before drawing, the timeline and the state at the tick are
+// decoded on worker threads from a copy of the document taken then.
+pub struct EntitiesToolViewModel {
+    // the document the timeline was built from, None when it needs copying again
+    document: Option<Arc<DemoDocument>>,
+    // None while it's being built
+    timeline: Option<Result<Arc<EntityTimeline>, String>>,
+    timeline_join_handle: Option<JoinHandle<Result<EntityTimeline, String>>>,
+    tick_interval: f32,
+    tick: i32,
+    // the Frames tool's tick when it was last followed
+    followed_tick: Option<i32>,
+    // the entities at tick, None when they need decoding again
+    state: Option<Result<EntityState, String>>,
+    // the tick being decoded
+    state_join_handle: Option<(i32, JoinHandle<Result<EntityState, String>>)>,
+    active_entity: Option<usize>,
+    class_filter: String,
+    prop_filter: String,
+    hide_unset_props: bool,
+    // the entity index, class id and prop index to plot, the history is decoded next update
+    requested_history: Option<(usize, u16, usize)>,
+    // shown instead of the props, None while the history is decoded
+    vm_prop_history: Option<Result<PropHistoryViewModel, String>>,
+    prop_history_join_handle: Option<JoinHandle<Result<PropHistoryViewModel, String>>>,
+    // the document was edited, the timeline is rebuilt next update
+    b_stale: bool,
+}
+
+impl EntitiesToolViewModel {
+    pub fn new(timeline: Result<EntityTimeline, String>, tick_interval: f32) -> Self {
+        Self {
+            // copied on the first update, the timeline was built from the same document
+            document: None,
+            timeline: Some(timeline.map(Arc::new)),
+            timeline_join_handle: None,
+            tick_interval,
+            tick: 0,
+            followed_tick: None,
+            state: None,
+            state_join_handle: None,
+            active_entity: None,
+            class_filter: String::new(),
+            prop_filter: String::new(),
+            hide_unset_props: true,
+            requested_history: None,
+            vm_prop_history: None,
+            prop_history_join_handle: None,
+            b_stale: false,
+        }
+    }
+
+    pub fn update(&mut self, document: &DemoDocument, frames_tick: Option<i32>) {
+        if self.b_stale {
+            // results still being decoded are for the old document, they're dropped
+            self.document = None;
+            self.timeline = None;
+            self.timeline_join_handle = None;
+            self.state = None;
+            self.state_join_handle = None;
+            self.b_stale = false;
+        }
+        let snapshot = self.document
+            .get_or_insert_with(|| Arc::new(document.clone()))
+            .clone();
+
+        if let Some(jh) = self.timeline_join_handle.take() {
+            if jh.is_finished() {
+                self.timeline = Some(jh.join().unwrap_or_else(|_| Err("the decoder panicked".to_owned())).map(Arc::new));
+            } else {
+                self.timeline_join_handle = Some(jh);
+            }
+        } else if self.timeline.is_none() {
+            let snapshot = snapshot.clone();
+            self.timeline_join_handle = Some(thread::spawn(move || EntityTimeline::from_document(&snapshot)));
+        }
+
+        if frames_tick != self.followed_tick {
+            self.followed_tick = frames_tick;
+            if let Some(tick) = frames_tick {
+                self.set_tick(tick);
+            }
+        }
+
+        // one tick is decoded at a time, when it finishes for a tick that's no longer
+        // shown the latest tick is decoded next
+        if let Some((tick, jh)) = self.state_join_handle.take() {
+            if !jh.is_finished() {
+                self.state_join_handle = Some((tick, jh));
+            } else if tick == self.tick {
+                self.state = Some(jh.join().unwrap_or_else(|_| Err("the decoder panicked".to_owned())));
+            }
+        }
+        if let (None, None, Some(Ok(timeline))) = (&self.state, &self.state_join_handle, &self.timeline) {
+            let (timeline, snapshot, tick) = (timeline.clone(), snapshot.clone(), self.tick);
+            self.state_join_handle = Some((tick, thread::spawn(move || timeline.state_at(&snapshot, tick))));
+        }
+
+        if let Some(jh) = self.prop_history_join_handle.take() {
+            if jh.is_finished() {
+                self.vm_prop_history = Some(jh.join().unwrap_or_else(|_| Err("the decoder panicked".to_owned())));
+            } else {
+                self.prop_history_join_handle = Some(jh);
+            }
+        }
+        // replaying every PacketEntities message takes a while on a full match
+        if let (Some((index, class_id, prop_index)), Some(Ok(timeline))) = (self.requested_history.take(), &self.timeline) {
+            let title = match timeline.class(class_id) {
+                Some(class) => format!("{} ({}) {}", class.network_name, index, class.props[prop_index].name),
+                None => format!("Entity {} prop {}", index, prop_index)
+            };
+            let (timeline, tick_interval) = (timeline.clone(), self.tick_interval);
+            self.vm_prop_history = None;
+            self.prop_history_join_handle = Some(thread::spawn(move || {
+                timeline.prop_history(&snapshot, index, class_id, prop_index)
+                    .map(|changes| PropHistoryViewModel::new(title, changes, &snapshot, tick_interval))
+            }));
+        }
+    }
+
+    fn set_tick(&mut self, tick: i32) {
+        if tick != self.tick {
+            self.tick = tick;
+            self.state = None;
+        }
+    }
+}
+
+impl ViewModel for EntitiesToolViewModel {
+    fn draw(&mut self, ui: &mut egui::Ui, events: &mut Vec<Event>) {
+        let timeline = match &self.timeline {
+            Some(Ok(t)) => t,
+            Some(Err(e)) => {
+                ui.label(RichText::new(format!("The entities couldn't be decoded: {}", e)).color(ERROR_COLOUR));
+                return
+            },
+            None => {
+                draw_decoding(ui, "Decoding the entities…");
+                return
+            }
+        };
+
+        ui.horizontal(|ui| {
+            ui.label("Tick");
+            if ui.add(egui::DragValue::new(&mut self.tick).clamp_range(0..=i32::MAX)).changed() {
+                // decoded from the next update
+                self.state = None;
+                ui.ctx().request_repaint();
+            }
+            ui.label(tick_to_time_string(self.tick_interval, self.tick));
+            ui.label(RichText::new("Selecting a frame in the Frames tool moves here too.").weak());
+        });
+        if let Some(e) = &timeline.decode_error {
+            ui.label(RichText::new(format!("Entities after this couldn't be decoded, {}", e)).color(ERROR_COLOUR));
+        }
+        ui.separator();
+
+        let state = match &self.state {
+            Some(Ok(s)) => s,
+            Some(Err(e)) => {
+                ui.label(RichText::new(format!("The entities at tick {} couldn't be decoded: {}", self.tick, e)).color(ERROR_COLOUR));
+                return
+            },
+            None => {
+                draw_decoding(ui, &format!("Decoding the entities at tick {}…", self.tick));
+                return
+            }
+        };
+
+        let avail_height = ui.available_height() - table_constants::BOTTOM_MARGIN;
+        let mut close_history = false;
+        let previous_entity = self.active_entity;
+        egui::Grid::new("entities_tool_grid").show(ui, |ui| {
+            ui.vertical(|ui| {
+                ui.set_width(ENTITY_LIST_WIDTH);
+                ui.set_height(avail_height);
+                ui.horizontal(|ui| {
+                    ui.label("Class");
+                    ui.text_edit_singleline(&mut self.class_filter);
+                    ui.label(format!("{} entities", state.entities.len()));
+                });
+                draw_entity_list(ui, state, timeline, &self.class_filter, &mut self.active_entity);
+            });
+
+            if self.vm_prop_history.is_some() || self.prop_history_join_handle.is_some() {
+                ui.vertical(|ui| {
+                    ui.set_height(avail_height);
+                    if ui.button("⬅ Props").clicked() {
+                        close_history = true;
+                    }
+                    match &mut self.vm_prop_history {
+                        Some(Ok(vm)) => vm.draw(ui, events),
+                        Some(Err(e)) => {
+                            ui.label(RichText::new(format!("The prop's history couldn't be decoded: {}", e)).color(ERROR_COLOUR));
+                        },
+                        None => draw_decoding(ui, "Decoding the prop's history…")
+                    }
+                });
+            } else if let Some(active_entity) = self.active_entity {
+                ui.vertical(|ui| {
+                    ui.set_height(avail_height);
+                    let plot = draw_props(ui, state, timeline, active_entity, &mut self.prop_filter, &mut self.hide_unset_props);
+                    if let Some(request) = plot {
+                        self.requested_history = Some(request);
+                        ui.ctx().request_repaint();
+                    }
+                });
+            }
+        });
+        // picking another entity goes back to the props
+        if close_history || self.active_entity != previous_entity {
+            self.vm_prop_history = None;
+            self.prop_history_join_handle = None;
+        }
+    }
+
+    fn handle_event(&mut self, event: &Event) -> bool {
+        // message data or locations changed, header edits don't touch the entities
+        if let Event::DocumentEdited(edit) = event {
+            if !matches!(edit, DocumentEdit::Header(_)) {
+                self.b_stale = true;
+                self.state = None;
+                self.vm_prop_history = None;
+                self.prop_history_join_handle = None;
+            }
+        }
+        false
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any { self }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any { self }
+}
+
+// the spinner keeps repainting, so update picks up the result as soon as it's done
+fn draw_decoding(ui: &mut egui::Ui, text: &str) {
+    ui.horizontal(|ui| {
+        ui.add(egui::Spinner::new());
+        ui.label(text);
+    });
+}
+
+fn draw_entity_list(
+    ui: &mut egui::Ui,
+    state: &EntityState,
+    timeline: &EntityTimeline,
+    class_filter: &str,
+    active_entity: &mut Option<usize>
+) {
+    let filter = class_filter.to_lowercase();
+    let display_entities: Vec<(usize, &str, u32)> = state.entities
+        .values()
+        .map(|e| (
+            e.index,
+            timeline.class(e.class_id).map(|c| c.network_name.as_str()).unwrap_or("?"),
+            e.serial
+        ))
+        .filter(|(_, class, _)| filter.is_empty() || class.to_lowercase().contains(&filter))
+        .collect();
+
+    TableBuilder::new(ui)
+    .striped(true)
+    .column(Column::exact(table_constants::COL_INDEX_WIDTH))
+    .column(Column::remainder())
+    .column(Column::exact(COL_SERIAL_WIDTH))
+    .header(table_constants::HEADER_HEIGHT, |mut row| {
+        row.col(|ui| {
+            ui.label("Index");
+        });
+        row.col(|ui| {
+            ui.label("Class");
+        });
+        row.col(|ui| {
+            ui.label("Serial");
+        });
+    })
+    .body(|body| {
+        body.rows(
+            table_constants::ROW_HEIGHT,
+            display_entities.len(),
+            |index, mut row| {
+                let (entity_index, class, serial) = display_entities[index];
+                let is_active = *active_entity == Some(entity_index);
+                let texts = [entity_index.to_string(), class.to_owned(), serial.to_string()];
+
+                for text in texts {
+                    let res = row.col(|ui| {
+                        if is_active {
+                            ui.label(RichText::new(text).color(table_constants::SELECTED_ITEM_COLOUR));
+                        } else {
+                            ui.label(text);
+                        }
+                    }).1;
+                    if res
+                    .interact(Sense::click())
+                    .on_hover_cursor(CursorIcon::PointingHand)
+                    .clicked() {
+                        *active_entity = Some(entity_index);
+                    }
+                }
+            });
+    });
+}
+
+fn draw_props(
+    ui: &mut egui::Ui,
+    state: &EntityState,
+    timeline: &EntityTimeline,
+    active_entity: usize,
+    prop_filter: &mut String,
+    hide_unset_props: &mut bool
+) -> Option<(usize, u16, usize)> {
+    let entity = match state.entities.get(&active_entity) {
+        Some(e) => e,
+        None => {
+            ui.label("The entity doesn't exist at this tick.");
+            return None
+        }
+    };
+    let class = match timeline.class(entity.class_id) {
+        Some(c) => c,
+        None => {
+            ui.label(format!("Unknown class {}.", entity.class_id));
+            return None
+        }
+    };
+
+    ui.horizontal(|ui| {
+        ui.label(RichText::new(format!("{} ({})", class.network_name, entity.index)).strong());
+        ui.label(RichText::new(&class.table_name).weak());
+    });
+    ui.horizontal(|ui| {
+        ui.label("Filter");
+        ui.text_edit_singleline(prop_filter);
+        ui.checkbox(hide_unset_props, "Hide unset props");
+    });
+
+    let filter = prop_filter.to_lowercase();
+    let display_props: Vec<(usize, &str, String)> = class.props
+        .iter()
+        .zip(&entity.props)
+        .enumerate()
+        .filter(|(_, (prop, _))| filter.is_empty() || prop.name.to_lowercase().contains(&filter))
+        .filter_map(|(i, (prop, value))| match value {
+            Some(v) => Some((i, prop.name.as_str(), v.to_string())),
+            None if *hide_unset_props => None,
+            None => Some((i, prop.name.as_str(), "None".to_owned()))
+        })
+        .collect();
+
+    let mut plot = None;
+    ui.push_id("entity_props", |ui| {
+        TableBuilder::new(ui)
+        .striped(true)
+        .column(Column::exact(PLOT_BUTTON_WIDTH))
+        .column(Column::initial(PROP_NAME_WIDTH).resizable(true))
+        .column(Column::remainder())
+        .header(table_constants::HEADER_HEIGHT, |mut row| {
+            row.col(|_| {});
+            row.col(|ui| {
+                ui.label("Name");
+            });
+            row.col(|ui| {
+                ui.label("Value");
+            });
+        })
+        .body(|body| {
+            body.rows(
+                table_constants::ROW_HEIGHT,
+                display_props.len(),
+                |index, mut row| {
+                    let (prop_index, name, value) = &display_props[index];
+                    row.col(|ui| {
+                        if ui.small_button("📈").on_hover_text("Plot this prop over the whole demo").clicked() {
+                            plot = Some((entity.index, entity.class_id, *prop_index));
+                        }
+                    });
+                    row.col(|ui| {
+                        ui.label(*name);
+                    });
+                    row.col(|ui| {
+                        ui.label(value);
+                    });
+                });
+        });
+    });    plot
+}
\ No newline at end of file
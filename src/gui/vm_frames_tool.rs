@@ -5,17 +5,30 @@ use super::{
     ViewModel,
     Focusable,
     vm_packet_data::PacketDataViewModel,
-    vm_demo_file::tick_to_time_string,
     table_constants, Filters, vm_data_tables::DataTablesViewModel,
+    wfn_export_csv_button::wfn_export_csv_button,
+    wfn_message_checklist::wfn_message_checklist,
+};
+use crate::{
+    document::{ DemoDocument, MessageLocation },
+    edit::{
+        console_command::InsertConsoleCommand,
+        journal::DocumentEdit,
+        protobuf_field::{ MessageTarget, ProtobufFieldEdit },
+        trim::Trim,
+    },
+    export::{ self, tick_to_time_string },
 };
 use source_demo_tool::{demo_file::{
     frame::{ Command, Frame }, packet::{netmessage::{NetMessage, GameEventListData}, MessageParseReturn},
 }, protobuf_message::ProtobufMessageEnumTraits};
-use eframe::{egui::{ self, CursorIcon, RichText, Sense }, epaint::ColorImage};
+use eframe::{egui::{ self, Color32, CursorIcon, RichText, Sense }, epaint::ColorImage};
 use egui_extras::{ TableBuilder, Column, RetainedImage };
 
 const FRAMES_PLAYER_SLOT_WIDTH: f32 = 80.0;
 const MAX_FRAMES_LIST_WIDTH: f32 = 500.0;
+const INSERTED_COMMAND_COLOUR: Color32 = Color32::from_rgb(120, 220, 140);
+const ERROR_COLOUR: Color32 = Color32::from_rgb(240, 110, 110);
 
 pub struct FramesToolViewModel {
     pub vm_frames_list: FramesListViewModel,
@@ -25,7 +38,10 @@ pub struct FramesToolViewModel {
     frame_data: Vec<FrameData>,
     last_hide_none_values: bool,
     game_event_ld: Option<GameEventListData>,
-    name: &'static str,
+    pub name: &'static str,
+    // the document index of each frame, see DemoDocument::demo_file_frame_indices
+    document_frame_indices: Vec<usize>,
+    field_edits: Vec<ProtobufFieldEdit>,
 }
 
 #[derive(Clone)]
@@ -48,7 +64,8 @@ impl FramesToolViewModel {
         name: &'static str,
         demo_frames: Vec<Frame>,
         tick_interval: f32,
-        game_event_ld: Option<GameEventListData>
+        game_event_ld: Option<GameEventListData>,
+        document_frame_indices: Vec<usize>
     ) -> Self {
         let mut frame_data = Vec::new();
         let mut user_message_it = 0;
@@ -93,9 +110,15 @@ impl FramesToolViewModel {
             vm_data_tables: None,
             last_message_index: None,
             last_hide_none_values: false,
+            document_frame_indices,
+            field_edits: Vec::new(),
         }
     }
 
+    pub fn active_tick(&self) -> Option<i32> {
+        self.vm_frames_list.active_frame.map(|i| self.vm_frames_list.demo_frames[i].tick)
+    }
+
     pub fn select_frame(&mut self, index: usize) -> bool {
         let msg_len = self.vm_frames_list.demo_frames.len();
         if index >= msg_len {
@@ -174,6 +197,15 @@ impl FramesToolViewModel {
                 }
             });
 
+            let sign_on = self.name == "SignOnFrames";
+            if let Some(&frame_index) = self.document_frame_indices.get(index) {
+                packet_data.vm_message_list.set_message_target_callback(move |message_index| Some(MessageTarget {
+                    location: MessageLocation { sign_on, frame_index, message_index },
+                    user_message: false,
+                }));
+            }
+            packet_data.vm_message_list.set_field_edits(self.field_edits.clone());
+
             if let Some(index) = self.last_message_index {
                 if index < packet_data.vm_message_list.messages.len() {
                     packet_data.vm_message_list.set_active_message(index);
@@ -289,6 +321,27 @@ impl ViewModel for FramesToolViewModel {
     }
 
     fn handle_event(&mut self, event: &super::Event) -> bool {
+        if let Event::DocumentEdited(edit) = event {
+            let sign_on = self.name == "SignOnFrames";
+            match edit {
+                DocumentEdit::ProtobufField(field_edit) if field_edit.target.location.sign_on == sign_on => {
+                    self.field_edits.push(field_edit.clone());
+                },
+                edit if edit.moves_messages() => {
+                    // frames DemoFile has are never removed from the gui, keep the rest where they are
+                    for index in &mut self.document_frame_indices {
+                        *index = edit.shift_frame_index(sign_on, *index).unwrap_or(*index);
+                    }
+                    self.field_edits = self.field_edits
+                        .drain(..)
+                        .filter_map(|e| Some(ProtobufFieldEdit { target: edit.shift_target(e.target)?, ..e }))
+                        .collect();
+                    self.vm_frames_list.apply_document_edit(edit);
+                },
+                _ => {}
+            }
+        }
+
         if let Event::SelectFrame(tool_name, index) = event {
             if self.name == *tool_name {
                 return self.select_frame(*index)
@@ -310,15 +363,81 @@ impl ViewModel for FramesToolViewModel {
     fn as_any_mut(&mut self) -> &mut dyn std::any::Any { self }
 }
 
+// a frame listed by the Frames or Sign On Frames tool, see FrameLookup.
+#[derive(Clone, Copy)]
+pub struct FrameRef {
+    tool: &'static str,
+    // the FramesToolViewModel's name, for SelectFrame
+    frame_tool: &'static str,
+    index: usize,
+}
+
+impl FrameRef {
+    // switches to the tool and selects the frame
+    pub fn goto(&self, events: &mut Vec<Event>) {
+        events.push(Event::SetTool(self.tool));
+        events.push(Event::SelectFrame(self.frame_tool, self.index));
+    }
+}
+
+// finds where a document frame is listed in the frames tools.
+pub struct FrameLookup {
+    frame_indices: Vec<usize>,
+    sign_on_frame_indices: Vec<usize>,
+}
+
+impl FrameLookup {
+    pub fn new(document: &DemoDocument) -> Self {
+        Self {
+            frame_indices: document.demo_file_frame_indices(false),
+            sign_on_frame_indices: document.demo_file_frame_indices(true),
+        }
+    }
+
+    // None for frames the tools don't list, e.g. StringTables frames
+    pub fn find(&self, sign_on: bool, frame_index: usize) -> Option<FrameRef> {
+        let (tool, frame_tool, indices) = if sign_on {
+            ("Sign On Frames", "SignOnFrames", &self.sign_on_frame_indices)
+        } else {
+            ("Frames", "Frames", &self.frame_indices)
+        };
+        let index = indices.binary_search(&frame_index).ok()?;
+        Some(FrameRef { tool, frame_tool, index })
+    }
+}
+
+#[derive(Clone, Copy)]
+enum FrameRow {
+    Frame(usize),
+    // index into ConsoleCommandEditor::inserts
+    ConsoleCommand(usize),
+}
+
+// console commands inserted into the demo, they're shown in the list at their tick.
+#[derive(Default)]
+struct ConsoleCommandEditor {
+    // the document index of each inserted frame and its command
+    inserts: Vec<(usize, InsertConsoleCommand)>,
+    tick: i32,
+    player_slot: u8,
+    command: String,
+    error: Option<String>,
+}
+
 pub struct FramesListViewModel {
     tick_interval: f32,
     demo_frames: Vec<Frame>,
     display_frames: Vec<usize>,
+    // display_frames with the console commands to insert merged in, this is what the table shows
+    rows: Vec<FrameRow>,
     active_frame: Option<usize>,
     b_scroll_next: bool,
     frame_tool_name: &'static str,
     filterable_commands: BTreeMap<u8, (String, bool, usize)>,
     filterable_net_messages: BTreeMap<u64, (String, bool, usize)>,
+    // only the main frames can be trimmed or have commands inserted, sign on frames are always kept
+    trim_range: Option<(i32, i32)>,
+    console_commands: Option<ConsoleCommandEditor>,
 }
 
 impl FramesListViewModel {
@@ -366,18 +485,148 @@ impl FramesListViewModel {
             }
         }
 
-        Self {
+        let trim_range = match (frame_tool_name, demo_frames.first(), demo_frames.last()) {
+            ("Frames", Some(first), Some(last)) => Some((first.tick.max(0), last.tick.max(0))),
+            _ => None
+        };
+
+        let console_commands = trim_range.map(|(start_tick, _)| ConsoleCommandEditor {
+            tick: start_tick,
+            ..Default::default()
+        });
+
+        let mut vm = Self {
             tick_interval,
             demo_frames,
+            rows: Vec::new(),
+            trim_range,
+            console_commands,
             frame_tool_name,
             display_frames,
             filterable_commands,
             filterable_net_messages,
             active_frame: None,
             b_scroll_next: true,
+        };
+        vm.update_rows();
+        vm
+    }
+
+    fn update_rows(&mut self) {
+        let inserts = match &self.console_commands {
+            Some(editor) if !editor.inserts.is_empty() => &editor.inserts,
+            _ => {
+                self.rows = self.display_frames.iter().map(|i| FrameRow::Frame(*i)).collect();
+                return
+            }
+        };
+
+        // document order, InsertConsoleCommand::apply puts them after every frame up to their tick
+        let mut order: Vec<usize> = (0..inserts.len()).collect();
+        order.sort_by_key(|i| inserts[*i].0);
+
+        let mut rows = Vec::with_capacity(self.display_frames.len() + inserts.len());
+        let mut next = 0;
+        for frame_index in &self.display_frames {
+            let frame = &self.demo_frames[*frame_index];
+            while next < order.len()
+                && (inserts[order[next]].1.tick < frame.tick || matches!(frame.command, Command::Stop)) {
+                rows.push(FrameRow::ConsoleCommand(order[next]));
+                next += 1;
+            }
+            rows.push(FrameRow::Frame(*frame_index));
+        }
+        rows.extend(order[next..].iter().map(|i| FrameRow::ConsoleCommand(*i)));
+        self.rows = rows;
+    }
+
+    // first and last tick a command can be inserted at.
+    fn tick_bounds(&self) -> (i32, i32) {
+        let ticks = self.demo_frames.iter().map(|f| f.tick);
+        (ticks.clone().min().unwrap_or(0), ticks.max().unwrap_or(0))
+    }
+
+    fn draw_console_command_editor(&mut self, ui: &mut egui::Ui, events: &mut Vec<Event>) {
+        let active_tick = self.active_frame.map(|i| self.demo_frames[i].tick);
+        let (first_tick, last_tick) = self.tick_bounds();
+        let editor = match self.console_commands.as_mut() {
+            Some(editor) => editor,
+            None => return
+        };
+
+        egui::Grid::new("console_command_grid").num_columns(3).show(ui, |ui| {
+            ui.label("Tick");
+            ui.add(egui::DragValue::new(&mut editor.tick).clamp_range(first_tick..=last_tick));
+            if ui.add_enabled(active_tick.is_some(), egui::Button::new("Selected frame")).clicked() {
+                editor.tick = active_tick.unwrap_or(editor.tick);
+            }
+            ui.end_row();
+
+            ui.label("Player Slot");
+            ui.add(egui::DragValue::new(&mut editor.player_slot));
+            ui.end_row();
+
+            ui.label("Command");
+            ui.add(egui::TextEdit::singleline(&mut editor.command).hint_text("spec_player 3"));
+            ui.end_row();
+        });
+        if let Some(e) = &editor.error {
+            ui.label(RichText::new(e).color(ERROR_COLOUR));
+        }
+        if ui.button("Add").clicked() {
+            let insert = InsertConsoleCommand {
+                tick: editor.tick,
+                player_slot: editor.player_slot,
+                command: editor.command.trim().to_owned(),
+            };
+            match insert.validate() {
+                Ok(()) => {
+                    // the demo file view model applies this, the list updates from Event::DocumentEdited
+                    events.push(Event::ApplyEdit(DocumentEdit::ConsoleCommand(insert)));
+                    editor.command.clear();
+                    editor.error = None;
+                },
+                Err(e) => editor.error = Some(e)
+            }
+        }
+
+        if !editor.inserts.is_empty() {
+            ui.separator();
+            for (index, insert) in &editor.inserts {
+                ui.horizontal(|ui| {
+                    if ui.small_button("✖").clicked() {
+                        events.push(Event::ApplyEdit(DocumentEdit::RemoveFrame { sign_on: false, index: *index }));
+                    }
+                    ui.label(RichText::new(format!("{} [{}] {}", insert.tick, insert.player_slot, insert.command))
+                        .color(INSERTED_COMMAND_COLOUR));
+                });
+            }
+            ui.label(RichText::new("Inserted commands are written with Save As.").weak());
         }
     }
 
+    // keeps the inserted console commands in step with the document, main frames only.
+    fn apply_document_edit(&mut self, edit: &DocumentEdit) {
+        let editor = match self.console_commands.as_mut() {
+            Some(editor) => editor,
+            None => return
+        };
+        editor.inserts = editor.inserts
+            .drain(..)
+            .filter_map(|(index, insert)| Some((edit.shift_frame_index(false, index)?, insert)))
+            .collect();
+        if let DocumentEdit::InsertFrame { sign_on: false, index, frame } = edit {
+            if let Some(command) = frame.console_command() {
+                editor.inserts.push((*index, InsertConsoleCommand {
+                    tick: frame.tick,
+                    player_slot: frame.player_slot,
+                    command,
+                }));
+            }
+        }
+        self.update_rows();
+    }
+
     fn set_active_frame(&mut self, index: usize) {
         self.active_frame = Some(index);
         self.b_scroll_next = true;
@@ -468,6 +717,7 @@ impl FramesListViewModel {
             *checked = true;
         }
         self.display_frames = self.update_display_frames();
+        self.update_rows();
     }
 }
 
@@ -478,42 +728,80 @@ impl ViewModel for FramesListViewModel {
             let mut b_update_display_frames = false;
             ui.label("Filters");
             ui.menu_button("Commands", |ui| {
-                for (_, (name, checked, count)) in &mut self.filterable_commands {
-                    let mut ck = *checked;
-                    if ui.checkbox(&mut ck, format!("{} ({})", name, count)).changed() {
-                        b_update_display_frames = true;
-                        *checked = ck;
-                    }
-                }
+                b_update_display_frames |= wfn_message_checklist(ui, &mut self.filterable_commands);
             });
             ui.menu_button("Net Messages", |ui| {
-                for (_, (name, checked, count)) in &mut self.filterable_net_messages {
-                    let mut ck = *checked;
-                    if ui.checkbox(&mut ck, format!("{} ({})", name, count)).changed() {
-                        b_update_display_frames = true;
-                        *checked = ck;
-                    }
-                }
+                b_update_display_frames |= wfn_message_checklist(ui, &mut self.filterable_net_messages);
             });
 
             if b_update_display_frames {
                 self.display_frames = self.update_display_frames();
+                self.update_rows();
+            }
+
+            ui.separator();
+            wfn_export_csv_button(
+                ui,
+                &format!("{}.csv", self.frame_tool_name.to_lowercase()),
+                |w| export::write_frames_csv(
+                    w,
+                    self.display_frames.iter().map(|i| (*i, &self.demo_frames[*i])),
+                    self.tick_interval
+                )
+            );
+
+            let active_tick = self.active_frame.map(|i| self.demo_frames[i].tick);
+            if let Some((start_tick, end_tick)) = &mut self.trim_range {
+                ui.separator();
+                ui.menu_button("Trim", |ui| {
+                    egui::Grid::new("trim_range_grid").num_columns(3).show(ui, |ui| {
+                        ui.label("Start tick");
+                        ui.add(egui::DragValue::new(start_tick).clamp_range(0..=i32::MAX));
+                        if ui.add_enabled(active_tick.is_some(), egui::Button::new("Selected frame")).clicked() {
+                            *start_tick = active_tick.unwrap_or(*start_tick);
+                        }
+                        ui.end_row();
+
+                        ui.label("End tick");
+                        ui.add(egui::DragValue::new(end_tick).clamp_range(0..=i32::MAX));
+                        if ui.add_enabled(active_tick.is_some(), egui::Button::new("Selected frame")).clicked() {
+                            *end_tick = active_tick.unwrap_or(*end_tick);
+                        }
+                        ui.end_row();
+                    });
+                    if ui.button("Trim to Range").clicked() {
+                        events.push(Event::TrimDemo(Trim {
+                            start_tick: *start_tick,
+                            end_tick: *end_tick,
+                        }));
+                        ui.close_menu();
+                    }
+                });
+            }
+
+            if self.console_commands.is_some() {
+                ui.menu_button("Insert Command", |ui| {
+                    self.draw_console_command_editor(ui, events);
+                });
             }
         });
 
         let mut table_builder = TableBuilder::new(ui);
 
         if self.b_scroll_next {
-            let active_index = self.display_frames.binary_search(
-                &self.active_frame
-                .unwrap_or(0)
-            );
-            let active_index = active_index.unwrap_or(0);
+            let active_frame = self.active_frame.unwrap_or(0);
+            let active_index = self.rows.iter()
+                .position(|r| matches!(r, FrameRow::Frame(i) if *i == active_frame))
+                .unwrap_or(0);
 
             table_builder = table_builder.scroll_to_row(active_index, None);
             self.b_scroll_next = false;
         }
 
+        let can_trim = self.trim_range.is_some();
+        let mut trim_start = None;
+        let mut trim_end = None;
+
         table_builder
         .striped(true)
         .column(Column::exact(table_constants::COL_INDEX_WIDTH))
@@ -540,8 +828,28 @@ impl ViewModel for FramesListViewModel {
             })
         .body(|body| {
             body.rows(table_constants::ROW_HEIGHT,
-                self.display_frames.len(), |index, mut row| {
-                    let frame_index = self.display_frames[index];
+                self.rows.len(), |index, mut row| {
+                    let frame_index = match self.rows[index] {
+                        FrameRow::Frame(frame_index) => frame_index,
+                        FrameRow::ConsoleCommand(i) => {
+                            if let Some(editor) = &self.console_commands {
+                                let insert = &editor.inserts[i].1;
+                                let cells = [
+                                    "+".to_owned(),
+                                    insert.tick.to_string(),
+                                    tick_to_time_string(self.tick_interval, insert.tick),
+                                    insert.player_slot.to_string(),
+                                    format!("ConsoleCmd: {}", insert.command),
+                                ];
+                                for cell in cells {
+                                    row.col(|ui| {
+                                        ui.label(RichText::new(cell).color(INSERTED_COMMAND_COLOUR));
+                                    });
+                                }
+                            }
+                            return
+                        }
+                    };
                     let frame = &self.demo_frames[frame_index];
                     let mut responses = Vec::new();
                     let is_active_frame = match self.active_frame {
@@ -592,10 +900,22 @@ impl ViewModel for FramesListViewModel {
 
                     let mut is_any_clicked = false;
                     for res in responses {
-                        let res = res.interact(Sense::click());
+                        let mut res = res.interact(Sense::click());
 
                         is_any_clicked |= res.clicked();
 
+                        if can_trim {
+                            res = res.context_menu(|ui| {
+                                if ui.button("Trim from this tick").clicked() {
+                                    trim_start = Some(frame.tick);
+                                    ui.close_menu();
+                                }
+                                if ui.button("Trim to this tick").clicked() {
+                                    trim_end = Some(frame.tick);
+                                    ui.close_menu();
+                                }
+                            });
+                        }
                         res.on_hover_cursor(CursorIcon::PointingHand);
                     }
 
@@ -607,6 +927,15 @@ impl ViewModel for FramesListViewModel {
                     }
                 });
         });
+
+        if let Some((start_tick, end_tick)) = &mut self.trim_range {
+            if let Some(tick) = trim_start {
+                *start_tick = tick;
+            }
+            if let Some(tick) = trim_end {
+                *end_tick = tick;
+            }
+        }
     }
 
     fn as_any(&self) -> &dyn std::any::Any { self }
@@ -1,6 +1,7 @@
 use std::collections::BTreeMap;
 
-use super::{ Event, ViewModel, Focusable, vm_demo_file::tick_to_time_string, table_constants, Filters };
+use super::{ Event, ViewModel, Focusable, table_constants, Filters, wfn_export_csv_button::wfn_export_csv_button };
+use crate::{ document::player_info::UserIdLookup, export::{ self, tick_to_time_string } };
 use eframe::{egui::{self, Sense, CursorIcon, RichText, Layout}, emath::Align};
 use source_demo_tool::demo_file::{FullGameEvent, FullGameEventKey, FullGameEventKeyType};
 use egui_extras::{ TableBuilder, Column };
@@ -24,16 +25,37 @@ pub struct GameEventsToolViewModel {
     active_filter_index: usize,
     filterable_list: Vec<String>,
     filterable_data: Vec<(String, usize)>,
+    user_ids: UserIdLookup,
+    // event index -> the players its user id keys refer to
+    event_players: BTreeMap<usize, Vec<usize>>,
+    // 0 is no filter, otherwise one more than the player's index
+    active_player_filter_index: usize,
+    player_filter_list: Vec<String>,
 }
 
 impl GameEventsToolViewModel {
-    pub fn new(game_events_vec: Vec<FullGameEvent>, tick_interval: f32) -> Self {
+    pub fn new(game_events_vec: Vec<FullGameEvent>, user_ids: UserIdLookup, tick_interval: f32) -> Self {
         let mut filterable_data = BTreeMap::new();
         let mut game_events = BTreeMap::new();
+        let mut event_players = BTreeMap::new();
+        let mut player_event_counts = vec![0; user_ids.players().len()];
 
         game_events.insert("None".to_owned(), BTreeMap::new());
         for i in 0..game_events_vec.len() {
             let ev = &game_events_vec[i];
+            let mut players: Vec<usize> = ev.event_keys
+                .iter()
+                .filter_map(|k| user_ids.find_key(k, ev.event_tick).map(|(p, _)| p))
+                .collect();
+            players.sort_unstable();
+            players.dedup();
+            for &p in &players {
+                player_event_counts[p] += 1;
+            }
+            if !players.is_empty() {
+                event_players.insert(i, players);
+            }
+
             filterable_data
                 .entry(ev.event_name.clone())
                 .and_modify(|k| *k += 1)
@@ -69,6 +91,11 @@ impl GameEventsToolViewModel {
             );
         }
 
+        let mut player_filter_list = vec!["None".to_owned()];
+        for (player, count) in user_ids.players().iter().zip(player_event_counts) {
+            player_filter_list.push(format!("{} ({})", player.describe_at(player.connect_tick), count));
+        }
+
         let display_events
             = game_events["None".into()].clone().into_iter().collect();
 
@@ -82,9 +109,29 @@ impl GameEventsToolViewModel {
             vm_active_keys: None,
             b_scroll_next: true,
             active_filter_index: 0,
+            user_ids,
+            event_players,
+            active_player_filter_index: 0,
+            player_filter_list,
         }
     }
 
+    // the events of the filtered type that refer to the filtered player
+    fn update_display_events(&mut self) {
+        let ev_str = match self.active_filter_index {
+            0 => "None",
+            i => &self.filterable_data[i - 1].0
+        };
+        let player_filter = self.active_player_filter_index.checked_sub(1);
+        self.display_events = self.game_events[ev_str]
+            .iter()
+            .filter(|(i, _)| player_filter.is_none_or(|p| {
+                self.event_players.get(i).is_some_and(|players| players.contains(&p))
+            }))
+            .map(|(i, ev)| (*i, ev.clone()))
+            .collect();
+    }
+
     fn set_active_index(&mut self, index: usize) -> bool {
         for ev in &self.display_events {
             if ev.0 == index {
@@ -93,8 +140,14 @@ impl GameEventsToolViewModel {
 
                 let active_event = &ev.1;
                 let keys = active_event.event_keys.clone();
+                let players = keys
+                    .iter()
+                    .map(|k| self.user_ids
+                        .find_key(k, active_event.event_tick)
+                        .map(|(i, p)| (i, p.describe_at(active_event.event_tick))))
+                    .collect();
                 self.vm_active_keys = Some(
-                    GameEventKeysViewModel::new(keys)
+                    GameEventKeysViewModel::new(keys, players)
                 );
                 return true
             }
@@ -203,16 +256,39 @@ impl ViewModel for GameEventsToolViewModel {
                             |i| self.filterable_list[i].clone()
                         )
                         .changed() {
-                            self.display_events = {
-                                if self.active_filter_index != 0 {
-                                    let ev_str = &self.filterable_data[self.active_filter_index - 1].0;
-                                    self.game_events[ev_str].clone().into_iter().collect()
-                                } else {
-                                    self.game_events["None".into()].clone().into_iter().collect()
-                                }
-                            };
+                            self.update_display_events();
+                            self.first_message();
+                        }
+
+                        if self.player_filter_list.len() > 1 && egui::ComboBox::new(
+                            ui.next_auto_id(),
+                            "Player"
+                        ).width(200.0)
+                        .show_index(
+                            ui,
+                            &mut self.active_player_filter_index,
+                            self.player_filter_list.len(),
+                            |i| self.player_filter_list[i].clone()
+                        )
+                        .changed() {
+                            self.update_display_events();
                             self.first_message();
                         }
+
+                        // a single event type gets one column per event key
+                        let key_columns = self.active_filter_index != 0;
+                        let file_name = if key_columns {
+                            format!("game_events_{}.csv", self.filterable_data[self.active_filter_index - 1].0)
+                        } else {
+                            "game_events.csv".to_owned()
+                        };
+                        wfn_export_csv_button(ui, &file_name, |w| export::write_game_events_csv(
+                            w,
+                            self.display_events.iter().map(|(i, ev)| (*i, ev)),
+                            self.tick_interval,
+                            key_columns,
+                            &self.user_ids
+                        ));
                     }
                 );
 
@@ -374,7 +450,8 @@ impl ViewModel for GameEventsToolViewModel {
             Event::ClearFilter(filter) => {
                 if let Filters::GameEvents = filter {
                     self.active_filter_index = 0;
-                    self.display_events = self.game_events["None"].clone().into_iter().collect();
+                    self.active_player_filter_index = 0;
+                    self.update_display_events();
                     true
                 } else {
                     false
@@ -390,18 +467,21 @@ impl ViewModel for GameEventsToolViewModel {
 
 struct GameEventKeysViewModel {
     keys: Vec<FullGameEventKey>,
+    // for each key, the player its user id refers to and "name (userid)"
+    players: Vec<Option<(usize, String)>>,
 }
 
 impl GameEventKeysViewModel {
-    pub fn new(keys: Vec<FullGameEventKey>) -> Self {
+    pub fn new(keys: Vec<FullGameEventKey>, players: Vec<Option<(usize, String)>>) -> Self {
         Self {
-            keys
+            keys,
+            players,
         }
     }
 }
 
 impl ViewModel for GameEventKeysViewModel {
-    fn draw(&mut self, ui: &mut egui::Ui, _events: &mut Vec<Event>) {
+    fn draw(&mut self, ui: &mut egui::Ui, events: &mut Vec<Event>) {
         // type
         // name
         // value
@@ -434,6 +514,13 @@ impl ViewModel for GameEventKeysViewModel {
                         ui.label(key_ref.key_name.clone());
                     });
                     row.col(|ui| {
+                        if let Some((player_index, text)) = &self.players[index] {
+                            if ui.link(text).on_hover_text("Show in the Players tool").clicked() {
+                                events.push(Event::SetTool("Players"));
+                                events.push(Event::SelectPlayer(*player_index));
+                            }
+                            return
+                        }
                         let text = match key_ref.key_type {
                             FullGameEventKeyType::Bool => format!("{}", key_ref.val_bool.unwrap()),
                             FullGameEventKeyType::Byte |
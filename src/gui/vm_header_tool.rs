@@ -1,18 +1,185 @@
-use super::{ ViewModel, w_copyable_field::CopyableFieldWidget };
+use super::{ Event, ViewModel, w_copyable_field::CopyableFieldWidget };
+use crate::{
+    document::MAX_HEADER_STRING_LENGTH,
+    edit::{ header::HeaderEdit, journal::DocumentEdit },
+};
 use source_demo_tool::demo_file::header::DemoHeader;
+use eframe::egui::{ self, Color32, RichText };
+
+const WARNING_COLOUR: Color32 = Color32::from_rgb(240, 200, 110);
+const ERROR_COLOUR: Color32 = Color32::from_rgb(240, 110, 110);
+const CHANGED_COLOUR: Color32 = Color32::LIGHT_YELLOW;
+
+// the header fields as typed, they're only parsed when checked or saved.
+#[derive(Clone, PartialEq)]
+struct HeaderEditor {
+    client_name: String,
+    server_name: String,
+    map_name: String,
+    game_directory: String,
+    playback_time: String,
+    ticks: String,
+    frames: String,
+}
+
+impl HeaderEditor {
+    fn from_header(header: &DemoHeader) -> Self {
+        Self {
+            client_name: header.client_name.clone(),
+            server_name: header.server_name.clone(),
+            map_name: header.map_name.clone(),
+            game_directory: header.game_directory.clone(),
+            playback_time: header.playback_time.to_string(),
+            ticks: header.ticks.to_string(),
+            frames: header.frames.to_string(),
+        }
+    }
+
+    fn header_edit(&self) -> Result<HeaderEdit, String> {
+        let playback_time = match self.playback_time.trim().parse::<f32>() {
+            Ok(v) => v,
+            Err(_) => return Err(format!("'{}' is not a valid playback time", self.playback_time))
+        };
+        let ticks = match self.ticks.trim().parse::<i32>() {
+            Ok(v) => v,
+            Err(_) => return Err(format!("'{}' is not a valid tick count", self.ticks))
+        };
+        let frames = match self.frames.trim().parse::<i32>() {
+            Ok(v) => v,
+            Err(_) => return Err(format!("'{}' is not a valid frame count", self.frames))
+        };
+
+        let edit = HeaderEdit {
+            client_name: self.client_name.clone(),
+            server_name: self.server_name.clone(),
+            map_name: self.map_name.clone(),
+            game_directory: self.game_directory.clone(),
+            playback_time,
+            ticks,
+            frames,
+        };
+        edit.validate()?;
+        Ok(edit)
+    }
+}
 
 pub struct HeaderToolViewModel {
     pub demo_header: DemoHeader,
+    // see Repair::problems
+    problems: Vec<String>,
+    // Some while editing, kept until cancelled so the edits survive switching tools
+    editor: Option<HeaderEditor>,
 }
 
 impl HeaderToolViewModel {
-    pub fn new(demo_header: DemoHeader) -> Self {
-        Self { demo_header }
+    pub fn new(demo_header: DemoHeader, problems: Vec<String>) -> Self {
+        Self { demo_header, problems, editor: None }
+    }
+
+    fn draw_editor(&mut self, ui: &mut egui::Ui, events: &mut Vec<Event>) {
+        let original = HeaderEditor::from_header(&self.demo_header);
+        let editor = match &mut self.editor {
+            Some(editor) => editor,
+            None => return
+        };
+        let is_dirty = *editor != original;
+
+        ui.horizontal(|ui| {
+            ui.label(RichText::new("Editing Header").strong());
+            if is_dirty {
+                ui.label(RichText::new("● not applied").color(CHANGED_COLOUR));
+            }
+        });
+        ui.add_space(7.5);
+
+        egui::Grid::new("header_edit_grid").num_columns(3).show(ui, |ui| {
+            let strings = [
+                ("Client Name", &mut editor.client_name, &original.client_name),
+                ("Server Name", &mut editor.server_name, &original.server_name),
+                ("Map Name", &mut editor.map_name, &original.map_name),
+                ("Game Directory", &mut editor.game_directory, &original.game_directory),
+            ];
+            for (label, value, original_value) in strings {
+                Self::field_label(ui, label, value != original_value);
+                ui.text_edit_singleline(value);
+                let length = format!("{}/{} bytes", value.len(), MAX_HEADER_STRING_LENGTH);
+                if value.len() > MAX_HEADER_STRING_LENGTH {
+                    ui.label(RichText::new(length).color(ERROR_COLOUR));
+                } else {
+                    ui.label(RichText::new(length).weak());
+                }
+                ui.end_row();
+            }
+
+            let numbers = [
+                ("Playback Time", &mut editor.playback_time, &original.playback_time),
+                ("Ticks", &mut editor.ticks, &original.ticks),
+                ("Frames", &mut editor.frames, &original.frames),
+            ];
+            for (label, value, original_value) in numbers {
+                Self::field_label(ui, label, value != original_value);
+                ui.text_edit_singleline(value);
+                ui.end_row();
+            }
+        });
+        ui.label(RichText::new("The protocols and sign on length can't be edited, the sign on length is recomputed when saved.").weak());
+        ui.label(RichText::new("Applied changes can be undone with Ctrl+Z and are written with Save As.").weak());
+
+        let edit = editor.header_edit();
+        if let Err(e) = &edit {
+            ui.label(RichText::new(e).color(ERROR_COLOUR));
+        }
+
+        ui.add_space(7.5);
+        ui.horizontal(|ui| {
+            if ui.button("Cancel").clicked() {
+                self.editor = None;
+                return
+            }
+            if ui.add_enabled(is_dirty, egui::Button::new("Revert")).clicked() {
+                self.editor = Some(original.clone());
+            }
+            if ui.add_enabled(is_dirty && edit.is_ok(), egui::Button::new("Apply")).clicked() {
+                if let Ok(edit) = edit {
+                    // the demo file view model handles this
+                    events.push(Event::ApplyEdit(DocumentEdit::Header(edit)));
+                    self.editor = None;
+                }
+            }
+        });
+    }
+
+    fn field_label(ui: &mut egui::Ui, label: &str, is_changed: bool) {
+        if is_changed {
+            ui.label(RichText::new(format!("{} *", label)).color(CHANGED_COLOUR));
+        } else {
+            ui.label(label);
+        }
     }
 }
 
 impl ViewModel for HeaderToolViewModel {
-    fn draw(&mut self, ui: &mut eframe::egui::Ui, _events: &mut Vec<super::Event>) {
+    fn draw(&mut self, ui: &mut eframe::egui::Ui, events: &mut Vec<super::Event>) {
+        if !self.problems.is_empty() {
+            ui.group(|ui| {
+                ui.label(RichText::new("This demo looks unfinished, the recording may have crashed:").color(WARNING_COLOUR));
+                for problem in &self.problems {
+                    ui.label(format!("• {}", problem));
+                }
+                if ui.button("Repair…").clicked() {
+                    // vm_main handles this
+                    events.push(Event::RepairDemo);
+                }
+            });
+            ui.add_space(7.5);
+        }
+        if self.editor.is_some() {
+            self.draw_editor(ui, events);
+            return
+        }
+        if ui.button("Edit…").clicked() {
+            self.editor = Some(HeaderEditor::from_header(&self.demo_header));
+        }
         ui.add(CopyableFieldWidget {
             label: "Client Name".into(),
             value: Box::new(self.demo_header.client_name.clone()),
@@ -61,6 +228,23 @@ impl ViewModel for HeaderToolViewModel {
         });
     }
 
+    fn handle_event(&mut self, event: &Event) -> bool {
+        if let Event::DocumentEdited(DocumentEdit::Header(edit)) = event {
+            let header = &mut self.demo_header;
+            header.client_name = edit.client_name.clone();
+            header.server_name = edit.server_name.clone();
+            header.map_name = edit.map_name.clone();
+            header.game_directory = edit.game_directory.clone();
+            header.playback_time = edit.playback_time;
+            header.ticks = edit.ticks;
+            header.frames = edit.frames;
+            if self.editor.is_some() {
+                self.editor = Some(HeaderEditor::from_header(&self.demo_header));
+            }
+        }
+        false
+    }
+
     fn as_any(&self) -> &dyn std::any::Any { self }
     fn as_any_mut(&mut self) -> &mut dyn std::any::Any { self }
 }
\ No newline at end of file
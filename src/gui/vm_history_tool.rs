@@ -0,0 +1,78 @@
+use eframe::egui::{ self, Color32, RichText };
+
+use super::{ Event, ViewModel };
+use crate::edit::journal::EditJournal;
+
+const UNSAVED_COLOUR: Color32 = Color32::LIGHT_YELLOW;
+
+// the document's edit journal, DemoFileViewModel keeps this up to date with set_journal.
+#[derive(Default)]
+pub struct HistoryToolViewModel {
+    done: Vec<String>,
+    // the next edit to redo is first
+    undone: Vec<String>,
+    unsaved_count: usize,
+}
+
+impl HistoryToolViewModel {
+    pub fn set_journal(&mut self, journal: &EditJournal) {
+        self.done = journal.done().iter().map(|e| e.description.clone()).collect();
+        self.undone = journal.undone().iter().rev().map(|e| e.description.clone()).collect();
+        self.unsaved_count = journal.unsaved_count();
+    }
+}
+
+impl ViewModel for HistoryToolViewModel {
+    fn draw(&mut self, ui: &mut eframe::egui::Ui, events: &mut Vec<Event>) {
+        ui.horizontal(|ui| {
+            if ui.add_enabled(!self.done.is_empty(), egui::Button::new("Undo"))
+                .on_hover_text("Ctrl+Z")
+                .clicked() {
+                // the demo file view model handles this
+                events.push(Event::Undo);
+            }
+            if ui.add_enabled(!self.undone.is_empty(), egui::Button::new("Redo"))
+                .on_hover_text("Ctrl+Shift+Z")
+                .clicked() {
+                events.push(Event::Redo);
+            }
+            if ui.add_enabled(!self.done.is_empty(), egui::Button::new("Export Patch…"))
+                .on_hover_text("Write the edits to a patch file that can be applied to other demos.")
+                .clicked() {
+                // vm_main handles this
+                events.push(Event::ExportPatch);
+            }
+            if self.unsaved_count > 0 {
+                ui.label(RichText::new(format!("● {} unsaved edits", self.unsaved_count)).color(UNSAVED_COLOUR));
+            }
+        });
+        ui.separator();
+
+        if self.done.is_empty() && self.undone.is_empty() {
+            ui.label("No edits yet, edits made in the Header, Server Info, Frames and User Messages tools are listed here.");
+            return
+        }
+
+        egui::ScrollArea::vertical().auto_shrink([false, false]).show(ui, |ui| {
+            // clicking an entry undoes or redoes everything up to it
+            if ui.selectable_label(false, RichText::new("Opened").weak()).clicked() {
+                events.extend(self.done.iter().map(|_| Event::Undo));
+            }
+            for (i, description) in self.done.iter().enumerate() {
+                let is_current = i + 1 == self.done.len();
+                if ui.selectable_label(is_current, format!("{}. {}", i + 1, description)).clicked() {
+                    events.extend((i + 1..self.done.len()).map(|_| Event::Undo));
+                }
+            }
+            for (i, description) in self.undone.iter().enumerate() {
+                let label = RichText::new(format!("{}. {}", self.done.len() + i + 1, description)).weak().italics();
+                if ui.selectable_label(false, label).on_hover_text("Undone").clicked() {
+                    events.extend((0..=i).map(|_| Event::Redo));
+                }
+            }
+        });
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any { self }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any { self }
+}
\ No newline at end of file
@@ -2,22 +2,53 @@ use super::{
     Event, ViewModel, Focusable,
     vm_demo_file::DemoFileViewModel,
     vm_no_files_open::NoFilesOpenViewModel,
-    vm_opening_files::OpeningFileViewModel, vm_frames_tool::FramesToolViewModel, vm_user_messages_tool::UserMessagesToolViewModel, vm_game_events_tool::GameEventsToolViewModel, vm_packet_data::PacketDataViewModel, vm_tasks_tool::TaskRunningViewModel,
+    vm_opening_files::OpeningFileViewModel, vm_archive_entries::ArchiveEntriesViewModel, vm_frames_tool::FramesToolViewModel, vm_user_messages_tool::UserMessagesToolViewModel, vm_game_events_tool::GameEventsToolViewModel, vm_packet_data::PacketDataViewModel, vm_tasks_tool::TaskRunningViewModel,
+    vm_diff::{ DiffTargetViewModel, DiffViewModel },
+    vm_map_switch::MapSwitchViewModel,
+    vm_strip_messages::StripMessagesViewModel,
+};
+use crate::{
+    archive::{ self, Container, DemoSource },
+    diff::DemoDiff,
+    document::DemoDocument,
+    edit::{
+        FieldChange,
+        anonymize::Anonymize,
+        concat::{ self, Concatenate },
+        map_switch::MapSwitch,
+        patch::patch_to_json,
+        repair::Repair,
+        strip::{ MessageCounts, StripMessages },
+        trim::Trim,
+    },
+    export::{ self, json::{ write_demo_json, JsonFormat } },
 };
 use source_demo_tool::demo_file::{ DemoFile, frame::Command };
 use eframe::{egui::{ self, Key, Modifiers, Context, Layout }, emath::Align, epaint::Color32};
-use std::{thread::{ self, JoinHandle }, sync::mpsc, time::SystemTime};
+use std::{thread::{ self, JoinHandle }, sync::mpsc, collections::VecDeque, time::SystemTime, path::PathBuf, fs::File, io::{ BufWriter, Write }};
 
 const SHIFT_JUMP_RANGE: usize = 10;
 const INITIAL_UI_SCALE: f32 = 1.15;
 
+type OpenedDemo = (DemoFile, DemoDocument, DemoSource);
+
 pub struct MainViewModel {
+    // drawn when no tab is open, or while a file is being opened
     inner_view_model: Box<dyn ViewModel>,
-    opening_file_join_handle: Option<JoinHandle<Result<DemoFile, String>>>,
+    tabs: Vec<DemoFileViewModel>,
+    active_tab_index: usize,
+    opening_file_join_handle: Option<JoinHandle<Result<OpenedDemo, String>>>,
+    // files asked for while another was opening, each gets its own tab in turn
+    queued_files: VecDeque<PathBuf>,
     initializing_gui_join_handle: Option<JoinHandle<DemoFileViewModel>>,
     focused_vm: Focusable,
     ui_ppt: f32,
-    task_join_handle: Option<JoinHandle<()>>,
+    // a task can finish with a message for the user
+    task_join_handle: Option<JoinHandle<Result<Option<String>, String>>>,
+    // the tab, the demo it was opened from and its journal's done count when a save began,
+    // the journal is marked saved once the task succeeds
+    pending_save: Option<(usize, PathBuf, usize)>,
+    diff_join_handle: Option<JoinHandle<DemoDiff>>,
     temporary_view_model: Option<Box<dyn ViewModel>>,
 }
 
@@ -25,19 +56,92 @@ impl MainViewModel {
     pub fn new() -> Self {
         MainViewModel {
             inner_view_model: Box::new(NoFilesOpenViewModel{}),
+            tabs: Vec::new(),
+            active_tab_index: 0,
             opening_file_join_handle: None,
+            queued_files: VecDeque::new(),
             initializing_gui_join_handle: None,
             focused_vm: Focusable::None,
             ui_ppt: INITIAL_UI_SCALE,
             task_join_handle: None,
+            pending_save: None,
+            diff_join_handle: None,
             temporary_view_model: None,
         }
     }
 
+    fn active_tab(&self) -> Option<&DemoFileViewModel> {
+        self.tabs.get(self.active_tab_index)
+    }
+
+    fn active_tab_mut(&mut self) -> Option<&mut DemoFileViewModel> {
+        self.tabs.get_mut(self.active_tab_index)
+    }
+
+    pub fn get_active_file_path(&self) -> Option<&PathBuf> {
+        self.active_tab().map(|df_vm| &df_vm.demo_file.path)
+    }
+
+    // also while an archive's entry is being picked, that opens a file once it's chosen
+    fn is_opening_file(&self) -> bool {
+        self.opening_file_join_handle.is_some()
+        || self.initializing_gui_join_handle.is_some()
+        || self.temporary_view_model.as_ref().is_some_and(|vm| vm.as_any().is::<ArchiveEntriesViewModel>())
+    }
+
+    fn set_active_tab(&mut self, index: usize) -> bool {
+        if index >= self.tabs.len() {
+            return false
+        }
+        self.active_tab_index = index;
+        self.focused_vm = self.tabs[index].get_active_focus();
+        true
+    }
+
+    fn close_tab(&mut self, index: usize) -> bool {
+        if index >= self.tabs.len() {
+            return false
+        }
+        // dropping the view model frees the demo file
+        self.tabs.remove(index);
+
+        if self.tabs.is_empty() {
+            self.active_tab_index = 0;
+            self.focused_vm = Focusable::None;
+        } else {
+            if index < self.active_tab_index || self.active_tab_index >= self.tabs.len() {
+                self.active_tab_index -= 1;
+            }
+            self.focused_vm = self.tabs[self.active_tab_index].get_active_focus();
+        }
+        true
+    }
+
+    fn draw_tab_bar(&self, ui: &mut egui::Ui, events: &mut Vec<Event>) {
+        egui::ScrollArea::horizontal()
+        .id_source("main_ui_tab_bar")
+        .show(ui, |ui| {
+            ui.horizontal(|ui| {
+                for (i, tab) in self.tabs.iter().enumerate() {
+                    let path = &tab.demo_file.path;
+                    let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+                    let tab_res = ui.selectable_label(i == self.active_tab_index, name)
+                        .on_hover_text(path.display().to_string());
+                    if tab_res.clicked() {
+                        events.push(Event::SelectTab(i));
+                    }
+                    if tab_res.middle_clicked() || ui.small_button("x").clicked() {
+                        events.push(Event::CloseTab(i));
+                    }
+                    ui.separator();
+                }
+            });
+        });
+    }
+
     fn handle_emit_netmsg_warnerrs(&mut self) -> bool {
-        let df_vm_res = self.inner_view_model
-            .as_any()
-            .downcast_ref::<DemoFileViewModel>();
+        let df_vm_res = self.active_tab();
 
         match df_vm_res {
             Some(df_vm) => {
@@ -71,6 +175,7 @@ impl MainViewModel {
                             }
                         }
                     }
+                    Ok(None)
                 }));
                 self.temporary_view_model = Some(Box::new(
                     TaskRunningViewModel::new(
@@ -86,29 +191,725 @@ impl MainViewModel {
         true
     }
 
-    fn handle_begin_open_file(&mut self) {
-        let file = rfd::FileDialog::new()
+    fn handle_export_json(&mut self) -> bool {
+        let (demo_file, user_ids) = match self.active_tab() {
+            Some(df_vm) => (&df_vm.demo_file, &df_vm.user_ids),
+            None => {
+                eprintln!("Got Event::ExportJson but no DemoFileViewModel present.");
+                return true
+            }
+        };
+
+        let stem = demo_file.path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let path = match rfd::FileDialog::new()
+            .add_filter("JSON", &["json"])
+            .add_filter("Newline Delimited JSON", &["ndjson"])
+            .set_file_name(format!("{}.json", stem).as_str())
+            .set_title("Export Demo as JSON")
+            .save_file() {
+            Some(p) => p,
+            None => return true
+        };
+        let format = JsonFormat::from_extension(
+            &path.extension().unwrap_or_default().to_string_lossy()
+        );
+
+        let demo_file = demo_file.clone();
+        let user_ids = user_ids.clone();
+        let (_tx, rx) = mpsc::channel();
+        self.task_join_handle = Some(thread::spawn(move || {
+            let res = File::create(&path).and_then(|file| {
+                let mut writer = BufWriter::new(file);
+                write_demo_json(&mut writer, &demo_file, &user_ids, format)?;
+                writer.flush()
+            });
+            match res {
+                Ok(()) => Ok(None),
+                Err(e) => Err(format!("Failed to export {}: {}", path.display(), e))
+            }
+        }));
+        self.temporary_view_model = Some(Box::new(
+            TaskRunningViewModel::new(
+                "Exporting demo as JSON.",
+                rx
+            )
+        ));
+        true
+    }
+
+    fn handle_save_demo_as(&mut self) -> bool {
+        let df_vm = match self.active_tab() {
+            Some(df_vm) => df_vm,
+            None => {
+                eprintln!("Got Event::SaveDemoAs but no DemoFileViewModel present.");
+                return true
+            }
+        };
+
+        let stem = df_vm.demo_file.path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let document = df_vm.document.clone();
+        let source_path = df_vm.source.path.clone();
+        let edit_count = df_vm.journal.unsaved_count();
+        let done_count = df_vm.journal.done().len();
+        let done_message = match edit_count {
+            0 => None,
+            n => Some(format!("Saved {} edits.", n))
+        };
+        if self.begin_save_document(document, &source_path, format!("{}_edited.dem", stem), done_message, None) {
+            self.pending_save = Some((self.active_tab_index, source_path, done_count));
+        }
+        true
+    }
+
+    // writes the active document's edits to a patch file, see edit::patch.
+    fn handle_export_patch(&mut self) -> bool {
+        let df_vm = match self.active_tab() {
+            Some(df_vm) => df_vm,
+            None => {
+                eprintln!("Got Event::ExportPatch but no DemoFileViewModel present.");
+                return true
+            }
+        };
+
+        let patch = match patch_to_json(&df_vm.journal.edits()) {
+            Ok(patch) => patch,
+            Err(e) => {
+                rfd::MessageDialog::new()
+                    .set_level(rfd::MessageLevel::Error)
+                    .set_description(format!("Failed to export the patch: {}", e).as_str())
+                    .set_title("Export Patch")
+                    .show();
+                return true
+            }
+        };
+        let stem = df_vm.demo_file.path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let path = match rfd::FileDialog::new()
+            .add_filter("Patch", &["json"])
+            .set_file_name(format!("{}_patch.json", stem).as_str())
+            .set_title("Export Patch")
+            .save_file() {
+            Some(p) => p,
+            None => return true
+        };
+
+        let res = File::create(&path).and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            serde_json::to_writer_pretty(&mut writer, &patch)?;
+            writer.flush()
+        });
+        if let Err(e) = res {
+            rfd::MessageDialog::new()
+                .set_level(rfd::MessageLevel::Error)
+                .set_description(format!("Failed to write {}: {}", path.display(), e).as_str())
+                .set_title("Export Patch")
+                .show();
+        }
+        true
+    }
+
+    // asks where to save and writes the document on a task thread, the demo the
+    // document was opened from is never overwritten. a report of the changes is written
+    // next to the demo as <name>_report.csv. returns false if the user cancelled.
+    fn begin_save_document(
+        &mut self,
+        document: DemoDocument,
+        source_path: &PathBuf,
+        default_file_name: String,
+        done_message: Option<String>,
+        report: Option<Vec<FieldChange>>
+    ) -> bool {
+        let path = match Self::pick_save_path(&[source_path], default_file_name) {
+            Some(p) => p,
+            None => return false
+        };
+
+        let (_tx, rx) = mpsc::channel();
+        self.task_join_handle = Some(thread::spawn(move || {
+            let res = File::create(&path).and_then(|file| {
+                let mut writer = BufWriter::new(file);
+                document.write(&mut writer)?;
+                writer.flush()
+            });
+            if let Err(e) = res {
+                return Err(format!("Failed to save {}: {}", path.display(), e))
+            }
+
+            let mut done_message = done_message.map(|m| format!("{}\n\nSaved to {}", m, path.display()));
+            if let Some(report) = report {
+                let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+                let report_path = path.with_file_name(format!("{}_report.csv", stem));
+                let res = File::create(&report_path).and_then(|file| {
+                    let mut writer = BufWriter::new(file);
+                    export::write_field_changes_csv(&mut writer, &report)?;
+                    writer.flush()
+                });
+                if let Err(e) = res {
+                    return Err(format!("Saved {} but failed to write the report {}: {}", path.display(), report_path.display(), e))
+                }
+                done_message = done_message.map(|m| format!("{}\nReport written to {}", m, report_path.display()));
+            }
+            Ok(done_message)
+        }));
+        self.temporary_view_model = Some(Box::new(
+            TaskRunningViewModel::new(
+                "Saving demo.",
+                rx
+            )
+        ));
+        true
+    }
+
+    // asks where to save a demo, refusing any of the demos it was made from.
+    fn pick_save_path(source_paths: &[&PathBuf], default_file_name: String) -> Option<PathBuf> {
+        let path = rfd::FileDialog::new()
             .add_filter("Source Demo File", &["dem"])
-            .pick_file();
+            .set_file_name(default_file_name.as_str())
+            .set_title("Save Demo As")
+            .save_file()?;
+        if source_paths.iter().any(|source| archive::same_file(source, &path)) {
+            rfd::MessageDialog::new()
+                .set_level(rfd::MessageLevel::Error)
+                .set_description("The original demo can't be overwritten, pick another file name.")
+                .set_title("Save Demo As")
+                .show();
+            return None
+        }
+        Some(path)
+    }
 
-        if let Some(path) = file {
-            if path.exists() {
-                let name = path.file_stem().unwrap().to_str().unwrap().to_string();
-                self.inner_view_model = Box::new(OpeningFileViewModel::new(name));
-
-                self.opening_file_join_handle = Some(thread::spawn(move || {
-                        DemoFile::open(&path)
-                    })
-                );
-            } else {
+    fn handle_concatenate_demos(&mut self) -> bool {
+        let df_vm = match self.active_tab() {
+            Some(df_vm) => df_vm,
+            None => {
+                eprintln!("Got Event::ConcatenateDemos but no DemoFileViewModel present.");
+                return true
+            }
+        };
+
+        let mut parts = match rfd::FileDialog::new()
+            .add_filter("Source Demo File", &["dem", "gz", "bz2"])
+            .set_title("Pick the Demos That Follow This One")
+            .pick_files() {
+            Some(parts) => parts,
+            None => return true
+        };
+        // split recordings are numbered, so name order is recording order
+        parts.sort_by(|a, b| concat::part_order(a, b));
+
+        let tick_interval = match df_vm.demo_file.get_server_info() {
+            Some(si) => si.tick_interval.unwrap_or(0.0),
+            None => 0.0
+        };
+        let stem = df_vm.demo_file.path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let mut document = df_vm.document.clone();
+        let source_path = df_vm.source.path.clone();
+
+        let mut source_paths: Vec<&PathBuf> = parts.iter().collect();
+        source_paths.push(&source_path);
+        let path = match Self::pick_save_path(&source_paths, format!("{}_combined.dem", stem)) {
+            Some(p) => p,
+            None => return true
+        };
+
+        let (tx, rx) = mpsc::channel();
+        self.task_join_handle = Some(thread::spawn(move || {
+            let mut summary = Vec::new();
+            for (i, part) in parts.iter().enumerate() {
+                let _ = tx.send(format!("Appending part {} of {}.", i + 2, parts.len() + 1));
+                let next = DemoSource { path: part.clone(), zip_entry: None }.open_document()
+                    .map_err(|e| format!("Failed to read {}: {}", part.display(), e))?;
+                let appended = Concatenate.apply(&mut document, next, tick_interval)
+                    .map_err(|e| format!("Can't append {}: {}", part.display(), e))?;
+                summary.push(format!(
+                    "{}: {} frames, ticks shifted by {}",
+                    part.file_name().unwrap_or_default().to_string_lossy(),
+                    appended.frames_appended,
+                    appended.tick_offset
+                ));
+            }
+
+            let _ = tx.send("Saving demo.".into());
+            let res = File::create(&path).and_then(|file| {
+                let mut writer = BufWriter::new(file);
+                document.write(&mut writer)?;
+                writer.flush()
+            });
+            if let Err(e) = res {
+                return Err(format!("Failed to save {}: {}", path.display(), e))
+            }
+            Ok(Some(format!(
+                "Appended {} demos, {} ticks in total.\n{}\n\nSaved to {}",
+                parts.len(),
+                document.header.ticks,
+                summary.join("\n"),
+                path.display()
+            )))
+        }));
+        self.temporary_view_model = Some(Box::new(
+            TaskRunningViewModel::new(
+                "Concatenating demos.",
+                rx
+            )
+        ));
+        true
+    }
+
+    fn handle_begin_map_switch(&mut self) -> bool {
+        let map_name = match self.active_tab() {
+            Some(df_vm) => df_vm.document.header.map_name.clone(),
+            None => {
+                eprintln!("Got Event::BeginMapSwitch but no DemoFileViewModel present.");
+                return true
+            }
+        };
+        self.temporary_view_model = Some(Box::new(MapSwitchViewModel::new(map_name)));
+        true
+    }
+
+    fn handle_preview_map_switch(&mut self, map_switch: &MapSwitch) -> bool {
+        let changes = match self.active_tab() {
+            Some(df_vm) => map_switch.changes(&df_vm.document),
+            None => return false
+        };
+        let wizard = self.temporary_view_model
+            .as_mut()
+            .and_then(|vm| vm.as_any_mut().downcast_mut::<MapSwitchViewModel>());
+        match wizard {
+            Some(wizard) => {
+                wizard.set_preview(changes);
+                true
+            },
+            None => false
+        }
+    }
+
+    fn handle_save_map_switch(&mut self, map_switch: &MapSwitch) -> bool {
+        let df_vm = match self.active_tab() {
+            Some(df_vm) => df_vm,
+            None => return false
+        };
+
+        let mut document = df_vm.document.clone();
+        let changes = match map_switch.apply(&mut document) {
+            Ok(changes) => changes,
+            Err(e) => {
+                rfd::MessageDialog::new()
+                    .set_level(rfd::MessageLevel::Error)
+                    .set_description(format!("Failed to switch map: {}", e).as_str())
+                    .set_title("Map Switch")
+                    .show();
+                return true
+            }
+        };
+        let changed = changes.iter().filter(|c| c.old != c.new).count();
+        let stem = df_vm.demo_file.path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let source_path = df_vm.source.path.clone();
+        // the wizard stays open if saving is cancelled
+        self.begin_save_document(
+            document,
+            &source_path,
+            format!("{}_{}.dem", stem, map_switch.map_name),
+            Some(format!("Switched map to {}, {} values changed.", map_switch.map_name, changed)),
+            None
+        );
+        true
+    }
+
+    fn handle_trim_demo(&mut self, trim: &Trim) -> bool {
+        let df_vm = match self.active_tab() {
+            Some(df_vm) => df_vm,
+            None => {
+                eprintln!("Got Event::TrimDemo but no DemoFileViewModel present.");
+                return true
+            }
+        };
+
+        let tick_interval = match df_vm.demo_file.get_server_info() {
+            Some(si) => si.tick_interval.unwrap_or(0.0),
+            None => 0.0
+        };
+        let mut document = df_vm.document.clone();
+        let report = match trim.apply(&mut document, tick_interval) {
+            Ok(report) => report,
+            Err(e) => {
+                rfd::MessageDialog::new()
+                    .set_level(rfd::MessageLevel::Error)
+                    .set_description(format!("Failed to trim demo: {}", e).as_str())
+                    .set_title("Trim")
+                    .show();
+                return true
+            }
+        };
+        let stem = df_vm.demo_file.path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let source_path = df_vm.source.path.clone();
+        self.begin_save_document(
+            document,
+            &source_path,
+            format!("{}_{}-{}.dem", stem, trim.start_tick, trim.end_tick),
+            Some(format!(
+                "Kept {} frames between tick {} and {}, removed {}.\nCarried over {} state frames and {} string table messages from before the range.{}",
+                report.frames_kept,
+                report.start_tick,
+                trim.end_tick,
+                report.frames_removed,
+                report.state_frames_kept,
+                report.state_messages_kept,
+                if report.start_tick < trim.start_tick {
+                    format!("\nThe clip starts at tick {} instead of {}, the last full entity update.", report.start_tick, trim.start_tick)
+                } else {
+                    String::new()
+                }
+            )),
+            None
+        );
+        true
+    }
+
+    fn handle_anonymize_players(&mut self) -> bool {
+        let df_vm = match self.active_tab() {
+            Some(df_vm) => df_vm,
+            None => {
+                eprintln!("Got Event::AnonymizePlayers but no DemoFileViewModel present.");
+                return true
+            }
+        };
+
+        let mut document = df_vm.document.clone();
+        let changes = match Anonymize.apply(&mut document) {
+            Ok(changes) => changes,
+            Err(e) => {
+                rfd::MessageDialog::new()
+                    .set_level(rfd::MessageLevel::Error)
+                    .set_description(format!("Failed to anonymize players: {}", e).as_str())
+                    .set_title("Anonymize Players")
+                    .show();
+                return true
+            }
+        };
+        let stem = df_vm.demo_file.path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let source_path = df_vm.source.path.clone();
+        self.begin_save_document(
+            document,
+            &source_path,
+            format!("{}_anonymized.dem", stem),
+            Some(format!("Anonymized players, {} values replaced.", changes.len())),
+            Some(changes)
+        );
+        true
+    }
+
+    fn handle_repair_demo(&mut self) -> bool {
+        let df_vm = match self.active_tab() {
+            Some(df_vm) => df_vm,
+            None => {
+                eprintln!("Got Event::RepairDemo but no DemoFileViewModel present.");
+                return true
+            }
+        };
+
+        if Repair::problems(&df_vm.document).is_empty() {
+            rfd::MessageDialog::new()
+                .set_level(rfd::MessageLevel::Info)
+                .set_description("The demo is complete, it doesn't need repairing.")
+                .set_title("Repair Demo")
+                .show();
+            return true
+        }
+
+        let tick_interval = match df_vm.demo_file.get_server_info() {
+            Some(si) => si.tick_interval.unwrap_or(0.0),
+            None => 0.0
+        };
+        let mut document = df_vm.document.clone();
+        let report = match Repair.apply(&mut document, tick_interval) {
+            Ok(report) => report,
+            Err(e) => {
                 rfd::MessageDialog::new()
                     .set_level(rfd::MessageLevel::Error)
-                    .set_description("file does not exist")
-                    .set_title("Error")
+                    .set_description(format!("Failed to repair demo: {}", e).as_str())
+                    .set_title("Repair Demo")
                     .show();
+                return true
+            }
+        };
+
+        let mut message = format!("Discarded {} bytes that couldn't be read.", report.bytes_discarded);
+        if report.sign_on_frames_recovered > 0 {
+            message += &format!("\nRecovered {} sign on frames.", report.sign_on_frames_recovered);
+        }
+        if report.stop_appended {
+            message += "\nAppended a Stop frame.";
+        }
+        for change in &report.changes {
+            message += &format!("\nHeader {}: {} -> {}", change.field, change.old, change.new);
+        }
+        let stem = df_vm.demo_file.path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let source_path = df_vm.source.path.clone();
+        self.begin_save_document(
+            document,
+            &source_path,
+            format!("{}_repaired.dem", stem),
+            Some(message),
+            None
+        );
+        true
+    }
+
+    fn handle_begin_strip_messages(&mut self) -> bool {
+        let counts = match self.active_tab() {
+            Some(df_vm) => MessageCounts::from_document(&df_vm.document),
+            None => {
+                eprintln!("Got Event::BeginStripMessages but no DemoFileViewModel present.");
+                return true
+            }
+        };
+        self.temporary_view_model = Some(Box::new(StripMessagesViewModel::new(counts)));
+        true
+    }
+
+    fn handle_save_stripped_demo(&mut self, strip: &StripMessages) -> bool {
+        let df_vm = match self.active_tab() {
+            Some(df_vm) => df_vm,
+            None => return false
+        };
+
+        let mut document = df_vm.document.clone();
+        let report = match strip.apply(&mut document) {
+            Ok(report) => report,
+            Err(e) => {
+                rfd::MessageDialog::new()
+                    .set_level(rfd::MessageLevel::Error)
+                    .set_description(format!("Failed to remove messages: {}", e).as_str())
+                    .set_title("Export Without")
+                    .show();
+                return true
+            }
+        };
+        let stem = df_vm.demo_file.path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let source_path = df_vm.source.path.clone();
+        // the dialog stays open if saving is cancelled
+        self.begin_save_document(
+            document,
+            &source_path,
+            format!("{}_stripped.dem", stem),
+            Some(format!(
+                "Removed {} messages ({} bytes) from {} packets.",
+                report.messages_removed,
+                report.bytes_removed,
+                report.packets_changed
+            )),
+            None
+        );
+        true
+    }
+
+    fn handle_round_trip_check(&mut self) -> bool {
+        let source = match self.active_tab() {
+            Some(df_vm) => df_vm.source.clone(),
+            None => {
+                eprintln!("Got Event::RoundTripCheck but no DemoFileViewModel present.");
+                return true
+            }
+        };
+
+        let (_tx, rx) = mpsc::channel();
+        self.task_join_handle = Some(thread::spawn(move || {
+            let data = source.read_bytes()?;
+            let report = DemoDocument::round_trip(&data)?;
+            let summary = format!(
+                "{} bytes read, {} bytes written, {} netmessages re-encoded, {} copied as read.",
+                report.original_length,
+                report.written_length,
+                report.messages_reencoded,
+                report.messages_copied
+            );
+            match report.first_difference {
+                None => Ok(Some(format!("The re-saved demo is byte-identical to the original.\n\n{}", summary))),
+                Some(offset) => Err(format!(
+                    "The re-saved demo differs from the original, starting at byte {} (0x{:x}).\n\n{}",
+                    offset, offset, summary
+                ))
+            }
+        }));
+        self.temporary_view_model = Some(Box::new(
+            TaskRunningViewModel::new(
+                "Checking demo round-trip.",
+                rx
+            )
+        ));
+        true
+    }
+
+    fn handle_begin_diff(&mut self, target_tab_index: Option<usize>) -> bool {
+        if self.tabs.is_empty() {
+            eprintln!("Got Event::BeginDiff but no DemoFileViewModel present.");
+            return true
+        }
+
+        let targets: Vec<(usize, String)> = self.tabs
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != self.active_tab_index)
+            .map(|(i, tab)| (i, tab.demo_file.path.file_name().unwrap_or_default().to_string_lossy().to_string()))
+            .collect();
+
+        let target_tab_index = match target_tab_index {
+            Some(i) => i,
+            None => match targets.len() {
+                0 => {
+                    rfd::MessageDialog::new()
+                        .set_level(rfd::MessageLevel::Info)
+                        .set_description("Open another demo in a new tab to compare it with this one.")
+                        .set_title("Compare Demos")
+                        .show();
+                    return true
+                },
+                1 => targets[0].0,
+                _ => {
+                    self.temporary_view_model = Some(Box::new(DiffTargetViewModel::new(targets)));
+                    return true
+                }
+            }
+        };
+
+        let left = self.tabs[self.active_tab_index].demo_file.clone();
+        let right = match self.tabs.get(target_tab_index) {
+            Some(tab) => tab.demo_file.clone(),
+            None => return false
+        };
+
+        let (_tx, rx) = mpsc::channel();
+        self.diff_join_handle = Some(thread::spawn(move || {
+            DemoDiff::new(&left, &right)
+        }));
+        self.temporary_view_model = Some(Box::new(
+            TaskRunningViewModel::new(
+                "Comparing demos.",
+                rx
+            )
+        ));
+        true
+    }
+
+    fn handle_diffing(&mut self) {
+        if let Some(jh) = self.diff_join_handle.take() {
+            if jh.is_finished() {
+                match jh.join() {
+                    Ok(diff) => self.temporary_view_model = Some(Box::new(DiffViewModel::new(diff))),
+                    Err(_) => {
+                        self.temporary_view_model = None;
+                        rfd::MessageDialog::new()
+                            .set_level(rfd::MessageLevel::Error)
+                            .set_description("Failed to compare the demos, one of them may be corrupt.")
+                            .set_title("Error")
+                            .show();
+                    }
                 }
+            } else {
+                self.diff_join_handle = Some(jh);
+            }
+        }
+    }
+
+    fn handle_begin_open_file(&mut self) {
+        let file = rfd::FileDialog::new()
+            .add_filter("Source Demo File", &["dem", "gz", "bz2", "zip"])
+            .pick_file();
+
+        if let Some(path) = file {
+            self.open_file(path);
+        }
+    }
+
+    fn open_file(&mut self, path: PathBuf) {
+        if self.is_opening_file() {
+            self.queued_files.push_back(path);
+            return
+        }
+
+        if !path.is_file() {
+            Self::show_open_error(format!("file does not exist: {}", path.display()));
+            return
+        }
+
+        let container = match Container::detect(&path) {
+            Ok(c) => c,
+            Err(e) => {
+                Self::show_open_error(format!("Failed to open file: {}", e));
+                return
             }
+        };
+
+        if container == Container::Zip {
+            match archive::list_zip_demos(&path) {
+                Ok(mut entries) => match entries.len() {
+                    0 => Self::show_open_error(format!("Failed to open file: '{}' contains no .dem files", path.display())),
+                    1 => self.begin_opening(path, Some(entries.remove(0))),
+                    _ => self.temporary_view_model = Some(Box::new(
+                        ArchiveEntriesViewModel::new(path, entries)
+                    )),
+                },
+                Err(e) => Self::show_open_error(format!("Failed to open file: {}", e))
+            }
+        } else {
+            self.begin_opening(path, None);
         }
+    }
+
+    fn begin_opening(&mut self, path: PathBuf, zip_entry: Option<String>) {
+        let name = match &zip_entry {
+            Some(entry) => entry.clone(),
+            None => path.file_name().unwrap_or_default().to_string_lossy().to_string()
+        };
+        self.inner_view_model = Box::new(OpeningFileViewModel::new(name));
+
+        let source = DemoSource { path, zip_entry };
+        self.opening_file_join_handle = Some(thread::spawn(move || {
+                let document = source.open_document()?;
+                let demo_file = document.to_demo_file(source.display_path())?;
+                Ok((demo_file, document, source))
+            })
+        );
+    }
+
+    fn show_open_error(description: String) {
+        rfd::MessageDialog::new()
+            .set_level(rfd::MessageLevel::Error)
+            .set_description(description.as_str())
+            .set_title("Error")
+            .show();
+    }
+
+    fn handle_dropped_files(&mut self, ctx: &Context, events: &mut Vec<Event>) {
+        let dropped_files = ctx.input(|i| i.raw.dropped_files.clone());
+        for dropped in dropped_files {
+            match dropped.path {
+                Some(path) => events.push(Event::OpenFile(path)),
+                None => Self::show_open_error(format!("dropped file has no path: {}", dropped.name))
+            }
+        }
+    }
+
+    fn draw_file_hover_overlay(ctx: &Context) {
+        let hovering = ctx.input(|i| !i.raw.hovered_files.is_empty());
+        if !hovering {
+            return
+        }
+
+        let painter = ctx.layer_painter(egui::LayerId::new(
+            egui::Order::Foreground,
+            egui::Id::new("file_hover_overlay")
+        ));
+        let screen_rect = ctx.screen_rect();
+        painter.rect_filled(screen_rect, 0.0, Color32::from_black_alpha(192));
+        painter.text(
+            screen_rect.center(),
+            egui::Align2::CENTER_CENTER,
+            "Drop to open demo file",
+            egui::FontId::proportional(24.0),
+            Color32::WHITE
+        );
+    }
 
     fn handle_keyboard_events(&mut self, ctx: &Context, events: &mut Vec<Event>) {
         let b_shift = ctx.input(|i| i.modifiers.shift_only());
@@ -119,9 +920,7 @@ impl MainViewModel {
         if b_pressed_arrow_dn || b_pressed_arrow_up {
             match &self.focused_vm {
                 Focusable::FramesListViewModel => {
-                    let df_vm_res = self.inner_view_model
-                        .as_any_mut()
-                        .downcast_mut::<DemoFileViewModel>();
+                    let df_vm_res = self.active_tab_mut();
 
                     if let Some(df_vm) = df_vm_res {
                         let frames_vm_res = df_vm.get_active_tool()
@@ -159,9 +958,7 @@ impl MainViewModel {
                     }
                 },
                 Focusable::ProtobufMessageListViewModel("packet_data_messages") => {
-                    let df_vm_res = self.inner_view_model
-                        .as_any_mut()
-                        .downcast_mut::<DemoFileViewModel>();
+                    let df_vm_res = self.active_tab_mut();
 
                     if let Some(df_vm) = df_vm_res {
                         let frames_vm_res = df_vm.get_active_tool()
@@ -195,9 +992,7 @@ impl MainViewModel {
                     }
                 },
                 Focusable::ProtobufMessageListViewModel("user_messages") => {
-                    let df_vm_res = self.inner_view_model
-                        .as_any_mut()
-                        .downcast_mut::<DemoFileViewModel>();
+                    let df_vm_res = self.active_tab_mut();
 
                     if let Some(df_vm) = df_vm_res {
                         let um_vm_res = df_vm.get_active_tool()
@@ -238,9 +1033,7 @@ impl MainViewModel {
                     eprintln!("Unknown ProtobufMessageListViewModel focusable id: {}", s);
                 },
                 Focusable::GameEventsList => {
-                    let df_vm_res = self.inner_view_model
-                    .as_any_mut()
-                    .downcast_mut::<DemoFileViewModel>();
+                    let df_vm_res = self.active_tab_mut();
 
                     if let Some(df_vm) = df_vm_res {
                         let ge_vm_res = df_vm.get_active_tool()
@@ -278,9 +1071,7 @@ impl MainViewModel {
                     }
                 },
                 Focusable::SendTables => {
-                    let df_vm_res = self.inner_view_model
-                        .as_any_mut()
-                        .downcast_mut::<DemoFileViewModel>();
+                    let df_vm_res = self.active_tab_mut();
 
                     if let Some(df_vm) = df_vm_res {
                         let frames_vm_res = df_vm.get_active_tool()
@@ -331,9 +1122,7 @@ impl MainViewModel {
         let b_pressed_arrow_left = ctx.input(|i| i.key_pressed(Key::ArrowLeft));
         let b_pressed_arrow_right = ctx.input(|i| i.key_pressed(Key::ArrowRight));
         if b_pressed_arrow_left || b_pressed_arrow_right {
-            let df_vm_res = self.inner_view_model
-                .as_any_mut()
-                .downcast_mut::<DemoFileViewModel>();
+            let df_vm_res = self.active_tab_mut();
 
             if let Some(df_vm) = df_vm_res {
                 if b_pressed_arrow_left {
@@ -353,20 +1142,42 @@ impl MainViewModel {
             }
         }
 
+        // Ctrl+Z / Ctrl+Shift+Z: Undo and redo edits, text fields keep their own undo
+        if !ctx.wants_keyboard_input() && !self.tabs.is_empty() {
+            if ctx.input_mut(|i| i.consume_key(Modifiers::CTRL | Modifiers::SHIFT, Key::Z)) {
+                events.push(Event::Redo);
+            }
+            if ctx.input_mut(|i| i.consume_key(Modifiers::CTRL, Key::Z)) {
+                events.push(Event::Undo);
+            }
+        }
+
         // Ctrl+O: Open file anywhere in program
         if ctx.input_mut(|i| i.consume_key(Modifiers::CTRL, Key::O)) {
             events.push(Event::BeginOpenFile);
         }
+
+        // Ctrl+W: Close the active tab
+        if ctx.input_mut(|i| i.consume_key(Modifiers::CTRL, Key::W)) && !self.tabs.is_empty() {
+            events.push(Event::CloseTab(self.active_tab_index));
+        }
+
+        // Ctrl+Tab / Ctrl+Shift+Tab: Switch tabs
+        if ctx.input_mut(|i| i.consume_key(Modifiers::CTRL, Key::Tab)) && !self.tabs.is_empty() {
+            events.push(Event::SelectTab((self.active_tab_index + 1) % self.tabs.len()));
+        }
+        if ctx.input_mut(|i| i.consume_key(Modifiers::CTRL | Modifiers::SHIFT, Key::Tab)) && !self.tabs.is_empty() {
+            events.push(Event::SelectTab((self.active_tab_index + self.tabs.len() - 1) % self.tabs.len()));
+        }
     }
 
-    fn handle_opening_file(&mut self, events: &mut Vec<Event>) {
+    fn handle_opening_file(&mut self) {
         if let Some(jh) = self.opening_file_join_handle.take() {
             if jh.is_finished() {
                 match jh.join().unwrap() {
-                    Ok(df) => {
-                        events.push(Event::NewFile(df.path.clone()));
+                    Ok((df, document, source)) => {
                         self.initializing_gui_join_handle = Some(thread::spawn(move || {
-                            DemoFileViewModel::new(df)
+                            DemoFileViewModel::new(df, document, source)
                         }));
                     },
                     Err(e) => {
@@ -388,14 +1199,25 @@ impl MainViewModel {
         if let Some(jh) = self.initializing_gui_join_handle.take() {
             if jh.is_finished() {
                 if let Ok(df_vm) = jh.join() {
-                    self.inner_view_model = Box::new(df_vm);
+                    self.tabs.push(df_vm);
+                    self.set_active_tab(self.tabs.len() - 1);
                 }
+                self.inner_view_model = Box::new(NoFilesOpenViewModel{});
             } else {
                 self.initializing_gui_join_handle = Some(jh);
             }
         }
     }
 
+    fn handle_queued_files(&mut self) {
+        if self.is_opening_file() {
+            return
+        }
+        if let Some(path) = self.queued_files.pop_front() {
+            self.open_file(path);
+        }
+    }
+
     fn set_styles(ui: &mut egui::Ui) {
         ui.style_mut()
             .visuals
@@ -405,16 +1227,43 @@ impl MainViewModel {
 
 impl ViewModel for MainViewModel {
     fn draw(&mut self, ui: &mut egui::Ui, events: &mut Vec<Event>) {
-        self.handle_opening_file(events);
+        self.handle_opening_file();
         self.handle_initializing_gui();
+        self.handle_queued_files();
+        self.handle_diffing();
         self.handle_keyboard_events(ui.ctx(), events);
+        self.handle_dropped_files(ui.ctx(), events);
+        Self::draw_file_hover_overlay(ui.ctx());
         Self::set_styles(ui);
 
         if let Some(task_jh) = self.task_join_handle.take() {
             if task_jh.is_finished() {
-                task_jh.join().unwrap();
+                let res = task_jh.join().unwrap();
                 self.temporary_view_model = None;
-                eprintln!("Task Complete");
+                // the tab may have been closed while saving, the index then points at another demo
+                if let (Ok(_), Some((index, source_path, done_count))) = (&res, self.pending_save.take()) {
+                    if let Some(df_vm) = self.tabs.get_mut(index).filter(|df_vm| df_vm.source.path == source_path) {
+                        df_vm.journal.mark_saved(done_count);
+                        df_vm.update_history();
+                    }
+                }
+                match res {
+                    Ok(None) => eprintln!("Task Complete"),
+                    Ok(Some(message)) => {
+                        rfd::MessageDialog::new()
+                            .set_level(rfd::MessageLevel::Info)
+                            .set_description(message.as_str())
+                            .set_title("Task Complete")
+                            .show();
+                    },
+                    Err(e) => {
+                        rfd::MessageDialog::new()
+                            .set_level(rfd::MessageLevel::Error)
+                            .set_description(e.as_str())
+                            .set_title("Error")
+                            .show();
+                    }
+                }
             } else {
                 self.task_join_handle = Some(task_jh);
             }
@@ -428,7 +1277,7 @@ impl ViewModel for MainViewModel {
             .show(ui, |ui| {
                 ui.horizontal(|ui| {
                     ui.set_width(avail_width / 2.0);
-                    // nothing for now
+                    self.draw_tab_bar(ui, events);
                 });
                 ui.with_layout(
                     Layout::right_to_left(Align::Center),
@@ -446,8 +1295,11 @@ impl ViewModel for MainViewModel {
 
             if let Some(temp_vm) = self.temporary_view_model.as_mut() {
                 temp_vm.draw(ui, events);
-            } else {
+            } else if self.tabs.is_empty() || self.inner_view_model.as_any().is::<OpeningFileViewModel>() {
                 self.inner_view_model.draw(ui, events);
+            } else {
+                let active_tab_index = self.active_tab_index;
+                self.tabs[active_tab_index].draw(ui, events);
             }
         });
 
@@ -460,16 +1312,63 @@ impl ViewModel for MainViewModel {
                 self.handle_begin_open_file();
                 return true
             },
+            Event::OpenFile(path) => {
+                self.open_file(path.clone());
+                return true
+            },
+            Event::OpenArchiveEntry(path, entry) => {
+                self.temporary_view_model = None;
+                self.begin_opening(path.clone(), Some(entry.clone()));
+                return true
+            },
+            Event::CancelOpenArchive => {
+                self.temporary_view_model = None;
+                return true
+            },
+            Event::SelectTab(index) => return self.set_active_tab(*index),
+            Event::CloseTab(index) => return self.close_tab(*index),
             Event::SetFocus(focusable) => {
                 self.focused_vm = focusable.clone();
-                // let inner grab this event as well
-                return self.inner_view_model.handle_event(event)
+                // let the active tab grab this event as well
+                return match self.active_tab_mut() {
+                    Some(df_vm) => df_vm.handle_event(event),
+                    None => false
+                }
             },
             Event::EmitNetMsgWarnErrs => return self.handle_emit_netmsg_warnerrs(),
+            Event::BeginDiff(target_tab_index) => return self.handle_begin_diff(*target_tab_index),
+            Event::ExportJson => return self.handle_export_json(),
+            Event::SaveDemoAs => return self.handle_save_demo_as(),
+            Event::RoundTripCheck => return self.handle_round_trip_check(),
+            Event::BeginMapSwitch => return self.handle_begin_map_switch(),
+            Event::PreviewMapSwitch(map_switch) => return self.handle_preview_map_switch(map_switch),
+            Event::SaveMapSwitch(map_switch) => return self.handle_save_map_switch(map_switch),
+            Event::TrimDemo(trim) => return self.handle_trim_demo(trim),
+            Event::AnonymizePlayers => return self.handle_anonymize_players(),
+            Event::RepairDemo => return self.handle_repair_demo(),
+            Event::ConcatenateDemos => return self.handle_concatenate_demos(),
+            Event::ExportPatch => return self.handle_export_patch(),
+            Event::BeginStripMessages => return self.handle_begin_strip_messages(),
+            Event::SaveStrippedDemo(strip) => return self.handle_save_stripped_demo(strip),
+            Event::CloseStripMessages => {
+                self.temporary_view_model = None;
+                return true
+            },
+            Event::CloseMapSwitch => {
+                self.temporary_view_model = None;
+                return true
+            },
+            Event::CloseDiff => {
+                self.temporary_view_model = None;
+                return true
+            },
             _ => {}
         }
 
-        self.inner_view_model.handle_event(event)
+        match self.active_tab_mut() {
+            Some(df_vm) => df_vm.handle_event(event),
+            None => self.inner_view_model.handle_event(event)
+        }
     }
 
     fn as_any(&self) -> &dyn std::any::Any { self }
@@ -0,0 +1,173 @@
+use super::{ Event, ViewModel, table_constants };
+use crate::edit::{ FieldChange, map_switch::MapSwitch };
+use eframe::egui::{ self, Color32, RichText };
+use egui_extras::{ TableBuilder, Column };
+
+const ERROR_COLOUR: Color32 = Color32::from_rgb(240, 110, 110);
+const CHANGED_COLOUR: Color32 = Color32::LIGHT_YELLOW;
+const LOCATION_WIDTH: f32 = 320.0;
+const FIELD_WIDTH: f32 = 100.0;
+
+enum MapSwitchStep {
+    Edit,
+    Preview(Vec<FieldChange>),
+}
+
+// map switch wizard: pick the new map, preview every change, then save as a new demo.
+pub struct MapSwitchViewModel {
+    current_map_name: String,
+    map_name: String,
+    replace_crc: bool,
+    crc_text: String,
+    error: Option<String>,
+    step: MapSwitchStep,
+}
+
+impl MapSwitchViewModel {
+    pub fn new(current_map_name: String) -> Self {
+        Self {
+            map_name: current_map_name.clone(),
+            current_map_name,
+            replace_crc: false,
+            crc_text: String::new(),
+            error: None,
+            step: MapSwitchStep::Edit,
+        }
+    }
+
+    pub fn set_preview(&mut self, changes: Vec<FieldChange>) {
+        self.step = MapSwitchStep::Preview(changes);
+    }
+
+    fn map_switch(&self) -> Result<MapSwitch, String> {
+        let map_crc = if self.replace_crc {
+            let text = self.crc_text.trim();
+            let text = text.strip_prefix("0x").unwrap_or(text);
+            match u32::from_str_radix(text, 16) {
+                Ok(crc) => Some(crc),
+                Err(_) => return Err(format!("'{}' is not a valid hexadecimal CRC", self.crc_text))
+            }
+        } else {
+            None
+        };
+
+        let map_switch = MapSwitch {
+            map_name: self.map_name.trim().to_owned(),
+            map_crc,
+        };
+        map_switch.validate()?;
+        Ok(map_switch)
+    }
+
+    fn draw_edit(&mut self, ui: &mut egui::Ui, events: &mut Vec<Event>) {
+        ui.label(format!("Current map: {}", self.current_map_name));
+        ui.add_space(7.5);
+
+        egui::Grid::new("map_switch_edit_grid").num_columns(2).show(ui, |ui| {
+            ui.label("New map name");
+            ui.text_edit_singleline(&mut self.map_name);
+            ui.end_row();
+
+            ui.checkbox(&mut self.replace_crc, "Replace map CRC");
+            ui.add_enabled(self.replace_crc, egui::TextEdit::singleline(&mut self.crc_text).hint_text("0x1a2b3c4d"));
+            ui.end_row();
+        });
+        ui.label(RichText::new("The map CRC is kept when not replaced, the game may refuse a map whose CRC doesn't match.").weak());
+
+        if let Some(e) = &self.error {
+            ui.label(RichText::new(e).color(ERROR_COLOUR));
+        }
+
+        ui.add_space(7.5);
+        ui.horizontal(|ui| {
+            if ui.button("Cancel").clicked() {
+                events.push(Event::CloseMapSwitch);
+            }
+            if ui.button("Preview Changes").clicked() {
+                match self.map_switch() {
+                    Ok(map_switch) => {
+                        self.error = None;
+                        // vm_main fills in the preview
+                        events.push(Event::PreviewMapSwitch(map_switch));
+                    },
+                    Err(e) => self.error = Some(e)
+                }
+            }
+        });
+    }
+
+    fn draw_preview(ui: &mut egui::Ui, changes: &[FieldChange]) {
+        let height = ui.available_height() - 48.0;
+        ui.push_id("map_switch_preview", |ui| {
+            TableBuilder::new(ui)
+            .striped(true)
+            .max_scroll_height(height)
+            .column(Column::initial(LOCATION_WIDTH).resizable(true))
+            .column(Column::initial(FIELD_WIDTH).resizable(true))
+            .column(Column::initial(200.0).resizable(true))
+            .column(Column::remainder())
+            .header(table_constants::HEADER_HEIGHT, |mut row| {
+                row.col(|ui| { ui.label("Location"); });
+                row.col(|ui| { ui.label("Field"); });
+                row.col(|ui| { ui.label("Current"); });
+                row.col(|ui| { ui.label("New"); });
+            })
+            .body(|body| {
+                body.rows(table_constants::ROW_HEIGHT, changes.len(), |index, mut row| {
+                    let change = &changes[index];
+                    let unchanged = change.old == change.new;
+                    row.col(|ui| { ui.label(&change.location); });
+                    row.col(|ui| { ui.label(&change.field); });
+                    row.col(|ui| { ui.label(&change.old); });
+                    row.col(|ui| {
+                        if unchanged {
+                            ui.label(RichText::new(&change.new).weak());
+                        } else {
+                            ui.label(RichText::new(&change.new).color(CHANGED_COLOUR));
+                        }
+                    });
+                });
+            });
+        });
+    }
+}
+
+impl ViewModel for MapSwitchViewModel {
+    fn draw(&mut self, ui: &mut egui::Ui, events: &mut Vec<Event>) {
+        ui.vertical_centered(|ui| {
+            ui.add_space(15.0);
+            ui.label(RichText::new("Map Switch").size(32.0).italics());
+            ui.add_space(7.5);
+            ui.separator();
+            ui.add_space(7.5);
+        });
+
+        match &self.step {
+            MapSwitchStep::Edit => self.draw_edit(ui, events),
+            MapSwitchStep::Preview(changes) => {
+                let changed = changes.iter().filter(|c| c.old != c.new).count();
+                ui.label(format!("{} of {} values will change.", changed, changes.len()));
+                Self::draw_preview(ui, changes);
+
+                ui.add_space(7.5);
+                let mut back = false;
+                let mut save = false;
+                ui.horizontal(|ui| {
+                    back = ui.button("Back").clicked();
+                    save = ui.button("Save As").clicked();
+                });
+                if save {
+                    if let Ok(map_switch) = self.map_switch() {
+                        events.push(Event::SaveMapSwitch(map_switch));
+                    }
+                }
+                if back {
+                    self.step = MapSwitchStep::Edit;
+                }
+            }
+        }
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any { self }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any { self }
+}
\ No newline at end of file
@@ -16,7 +16,7 @@ impl ViewModel for NoFilesOpenViewModel {
             ui.style_mut().wrap = Some(false);
             ui.add_space(vertical_spacing);
             ui.heading("No files opened");
-            ui.label("Double click or press Ctrl+O to open a new file.");
+            ui.label("Double click, press Ctrl+O or drop a demo file here to open it.");
             ui.add_space(vertical_spacing);
         }).response.interact(Sense::click());
         
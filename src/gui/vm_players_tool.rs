@@ -0,0 +1,348 @@
+use std::collections::HashMap;
+
+use super::{
+    Event,
+    ViewModel,
+    Filters,
+    w_copyable_field::CopyableFieldWidget,
+    table_constants,
+};
+use crate::{ document::player_info::{ Player, UserIdLookup }, export::tick_to_time_string };
+use source_demo_tool::demo_file::{
+    FullGameEvent,
+    ParsedUserMessage,
+    packet::usermessage::UserMessage,
+};
+use eframe::{ egui::{ self, Color32, CursorIcon, RichText, Sense }, epaint::ColorImage };
+use egui_extras::{ TableBuilder, Column, RetainedImage };
+
+const PLAYER_LIST_WIDTH: f32 = 620.0;
+const COL_USER_ID_WIDTH: f32 = 60.0;
+const COL_STEAM_ID_WIDTH: f32 = 150.0;
+const AVATAR_SIZE: usize = 64;
+const ERROR_COLOUR: Color32 = Color32::from_rgb(240, 110, 110);
+
+// a game event whose user id keys refer to the player
+struct PlayerGameEvent {
+    // index into the Game Events tool's events
+    index: usize,
+    tick: i32,
+    name: String,
+    key: String,
+}
+
+// a chat message the player sent
+struct PlayerChatMessage {
+    // index into the User Messages tool's messages
+    index: usize,
+    tick: i32,
+    text: String,
+}
+
+// every player in the userinfo table with their ids, and the game events, chat and avatar
+// that belong to them.
+pub struct PlayersToolViewModel {
+    players: Result<UserIdLookup, String>,
+    game_events: Vec<Vec<PlayerGameEvent>>,
+    chat_messages: Vec<Vec<PlayerChatMessage>>,
+    // avatar rgb data by steam account id, from AvatarData netmessages
+    avatars: HashMap<u32, Vec<u8>>,
+    active_player: Option<usize>,
+    avatar: Option<RetainedImage>,
+    // scroll the list to the active player next draw
+    b_scroll_next: bool,
+    tick_interval: f32,
+}
+
+impl PlayersToolViewModel {
+    pub fn new(
+        players: Result<UserIdLookup, String>,
+        game_events: &[FullGameEvent],
+        user_messages: &[ParsedUserMessage],
+        avatars: HashMap<u32, Vec<u8>>,
+        tick_interval: f32
+    ) -> Self {
+        let empty = UserIdLookup::default();
+        let lookup = players.as_ref().unwrap_or(&empty);
+        let player_list = lookup.players();
+
+        let mut player_game_events: Vec<Vec<PlayerGameEvent>> = player_list.iter().map(|_| Vec::new()).collect();
+        for (index, ev) in game_events.iter().enumerate() {
+            for key in &ev.event_keys {
+                if let Some((player_index, _)) = lookup.find_key(key, ev.event_tick) {
+                    player_game_events[player_index].push(PlayerGameEvent {
+                        index,
+                        tick: ev.event_tick,
+                        name: ev.event_name.clone(),
+                        key: key.key_name.clone(),
+                    });
+                }
+            }
+        }
+
+        let chat_messages = player_list
+            .iter()
+            .map(|player| user_messages
+                .iter()
+                .enumerate()
+                .filter(|(_, msg)| player.is_connected_at(msg.tick))
+                .filter_map(|(index, msg)| {
+                    let entity_index = player.slot as u64 + 1;
+                    let text = match msg.message_return.message.as_ref()? {
+                        UserMessage::SayText2(m) if m.ent_sidx == Some(entity_index) => {
+                            // the sender's name then the text
+                            m.params.get(1).cloned().unwrap_or_default()
+                        },
+                        UserMessage::SayText(m) if m.entity_idx == Some(entity_index) => {
+                            m.text.clone().unwrap_or_default()
+                        },
+                        _ => return None
+                    };
+                    Some(PlayerChatMessage { index, tick: msg.tick, text })
+                })
+                .collect())
+            .collect();
+
+        Self {
+            players,
+            game_events: player_game_events,
+            chat_messages,
+            avatars,
+            active_player: None,
+            avatar: None,
+            b_scroll_next: false,
+            tick_interval,
+        }
+    }
+
+    fn set_active_player(&mut self, index: usize) {
+        let player = match &self.players {
+            Ok(lookup) if index < lookup.players().len() => &lookup.players()[index],
+            _ => return
+        };
+        self.active_player = Some(index);
+        self.avatar = player.info.account_id()
+            .and_then(|account_id| self.avatars.get(&account_id))
+            .filter(|rgb| rgb.len() == AVATAR_SIZE * AVATAR_SIZE * 3)
+            .map(|rgb| RetainedImage::from_color_image(
+                "PlayerAvatar",
+                ColorImage::from_rgb([AVATAR_SIZE, AVATAR_SIZE], rgb)
+            ));
+    }
+
+    // returns the player that was clicked
+    fn draw_player_list(&mut self, ui: &mut egui::Ui, players: &[Player]) -> Option<usize> {
+        let mut clicked = None;
+        let mut table_builder = TableBuilder::new(ui);
+        if self.b_scroll_next {
+            if let Some(index) = self.active_player {
+                table_builder = table_builder.scroll_to_row(index, None);
+            }
+            self.b_scroll_next = false;
+        }
+        table_builder
+        .striped(true)
+        .column(Column::exact(table_constants::COL_INDEX_WIDTH))
+        .column(Column::exact(COL_USER_ID_WIDTH))
+        .column(Column::remainder())
+        .column(Column::exact(COL_STEAM_ID_WIDTH))
+        .column(Column::exact(table_constants::COL_TICK_WIDTH))
+        .column(Column::exact(table_constants::COL_TICK_WIDTH))
+        .header(table_constants::HEADER_HEIGHT, |mut row| {
+            row.col(|ui| {
+                ui.label("Entity");
+            });
+            row.col(|ui| {
+                ui.label("User ID");
+            });
+            row.col(|ui| {
+                ui.label("Name");
+            });
+            row.col(|ui| {
+                ui.label("SteamID");
+            });
+            row.col(|ui| {
+                ui.label("Connected");
+            });
+            row.col(|ui| {
+                ui.label("Disconnected");
+            });
+        })
+        .body(|body| {
+            body.rows(
+                table_constants::ROW_HEIGHT,
+                players.len(),
+                |index, mut row| {
+                    let player = &players[index];
+                    let is_active = self.active_player == Some(index);
+                    let texts = [
+                        (player.slot + 1).to_string(),
+                        player.info.user_id.to_string(),
+                        player.info.name.clone(),
+                        describe_steam_id(player),
+                        player.connect_tick.to_string(),
+                        player.disconnect_tick.map(|t| t.to_string()).unwrap_or_default(),
+                    ];
+
+                    for text in texts {
+                        let res = row.col(|ui| {
+                            if is_active {
+                                ui.label(RichText::new(text).color(table_constants::SELECTED_ITEM_COLOUR));
+                            } else {
+                                ui.label(text);
+                            }
+                        }).1;
+                        if res
+                        .interact(Sense::click())
+                        .on_hover_cursor(CursorIcon::PointingHand)
+                        .clicked() {
+                            clicked = Some(index);
+                        }
+                    }
+                });
+        });
+        clicked
+    }
+
+    fn draw_player(&self, ui: &mut egui::Ui, index: usize, player: &Player, events: &mut Vec<Event>) {
+        ui.horizontal(|ui| {
+            if let Some(avatar) = &self.avatar {
+                avatar.show(ui);
+            }
+            ui.vertical(|ui| {
+                ui.heading(&player.info.name);
+                let mut flags = Vec::new();
+                if player.info.fake_player {
+                    flags.push("Bot");
+                }
+                if player.info.is_hltv {
+                    flags.push("GOTV");
+                }
+                if !flags.is_empty() {
+                    ui.label(RichText::new(flags.join(", ")).weak());
+                }
+            });
+        });
+
+        ui.add(CopyableFieldWidget { label: "User ID".into(), value: Box::new(player.info.user_id) });
+        ui.add(CopyableFieldWidget { label: "Entity Index".into(), value: Box::new(player.slot + 1) });
+        ui.add(CopyableFieldWidget { label: "XUID / SteamID64".into(), value: Box::new(player.info.xuid) });
+        if let (Some(steam_id_2), Some(steam_id_3), Some(account_id)) =
+            (player.info.steam_id_2(), player.info.steam_id_3(), player.info.account_id()) {
+            ui.add(CopyableFieldWidget { label: "SteamID".into(), value: Box::new(steam_id_2) });
+            ui.add(CopyableFieldWidget { label: "SteamID3".into(), value: Box::new(steam_id_3) });
+            ui.add(CopyableFieldWidget { label: "Account ID".into(), value: Box::new(account_id) });
+        }
+        ui.add(CopyableFieldWidget { label: "GUID".into(), value: Box::new(player.info.guid.clone()) });
+        if !player.info.friends_name.is_empty() {
+            ui.add(CopyableFieldWidget { label: "Friends Name".into(), value: Box::new(player.info.friends_name.clone()) });
+        }
+        ui.label(format!(
+            "Connected at tick {} ({}), {}",
+            player.connect_tick,
+            tick_to_time_string(self.tick_interval, player.connect_tick),
+            match player.disconnect_tick {
+                Some(tick) => format!("disconnected at tick {} ({})", tick, tick_to_time_string(self.tick_interval, tick)),
+                None => "still connected at the end".to_owned()
+            }
+        ));
+        if player.names.len() > 1 {
+            let earlier_names: Vec<&str> = player.names[..player.names.len() - 1]
+                .iter()
+                .map(|(_, name)| name.as_str())
+                .collect();
+            ui.label(format!("Also known as {}", earlier_names.join(", ")));
+        }
+        ui.separator();
+
+        let game_events = &self.game_events[index];
+        let chat_messages = &self.chat_messages[index];
+        egui::ScrollArea::vertical().auto_shrink([false, false]).show(ui, |ui| {
+            egui::CollapsingHeader::new(format!("Game Events ({})", game_events.len()))
+                .id_source("player_game_events")
+                .show(ui, |ui| {
+                    for ev in game_events {
+                        let text = format!("{}  {} ({})", ev.tick, ev.name, ev.key);
+                        if ui.selectable_label(false, text).on_hover_cursor(CursorIcon::PointingHand).clicked() {
+                            events.push(Event::SetTool("Game Events"));
+                            events.push(Event::ClearFilter(Filters::GameEvents));
+                            events.push(Event::SelectGameEvent(ev.index));
+                        }
+                    }
+                });
+            egui::CollapsingHeader::new(format!("Chat ({})", chat_messages.len()))
+                .id_source("player_chat_messages")
+                .show(ui, |ui| {
+                    for msg in chat_messages {
+                        let text = format!("{}  {}", msg.tick, msg.text);
+                        if ui.selectable_label(false, text).on_hover_cursor(CursorIcon::PointingHand).clicked() {
+                            events.push(Event::SetTool("User Messages"));
+                            events.push(Event::ClearFilter(Filters::UserMessages));
+                            events.push(Event::SelectMessage("user_messages", msg.index));
+                        }
+                    }
+                });
+        });
+    }
+}
+
+impl ViewModel for PlayersToolViewModel {
+    fn draw(&mut self, ui: &mut egui::Ui, events: &mut Vec<Event>) {
+        // taken out while drawing so the list can borrow self
+        let lookup = match std::mem::replace(&mut self.players, Ok(UserIdLookup::default())) {
+            Ok(l) => l,
+            Err(e) => {
+                ui.label(RichText::new(format!("The players couldn't be read from the userinfo table: {}", e)).color(ERROR_COLOUR));
+                self.players = Err(e);
+                return
+            }
+        };
+        let players = lookup.players();
+        let mut clicked = None;
+        if players.is_empty() {
+            ui.label("The userinfo table has no players.");
+        } else {
+            let avail_height = ui.available_height() - table_constants::BOTTOM_MARGIN;
+            egui::Grid::new("players_tool_grid").show(ui, |ui| {
+                ui.vertical(|ui| {
+                    ui.set_width(PLAYER_LIST_WIDTH);
+                    ui.set_height(avail_height);
+                    clicked = self.draw_player_list(ui, players);
+                });
+
+                if let Some(index) = self.active_player {
+                    ui.vertical(|ui| {
+                        ui.set_height(avail_height);
+                        self.draw_player(ui, index, &players[index], events);
+                    });
+                }
+            });
+        }
+        self.players = Ok(lookup);
+        if let Some(index) = clicked {
+            self.set_active_player(index);
+        }
+    }
+
+    fn handle_event(&mut self, event: &Event) -> bool {
+        if let Event::SelectPlayer(index) = event {
+            self.set_active_player(*index);
+            self.b_scroll_next = true;
+            return true
+        }
+        false
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any { self }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any { self }
+}
+
+fn describe_steam_id(player: &Player) -> String {
+    if player.info.is_hltv {
+        return "GOTV".to_owned()
+    }
+    match player.info.steam_id_2() {
+        Some(steam_id) => steam_id,
+        None => player.info.guid.clone()
+    }
+}
\ No newline at end of file
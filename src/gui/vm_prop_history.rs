@@ -0,0 +1,247 @@
+use super::{
+    Event,
+    ViewModel,
+    vm_frames_tool::{ FrameLookup, FrameRef },
+    table_constants,
+};
+use crate::{
+    document::{
+        DemoDocument,
+        entities::{ PropChange, PropValue },
+    },
+    export::tick_to_time_string,
+};
+use eframe::egui::{
+    self,
+    Color32,
+    CursorIcon,
+    RichText,
+    Sense,
+    plot::{ Line, MarkerShape, Plot, PlotPoint, Points },
+};
+use egui_extras::{ TableBuilder, Column };
+
+const PLOT_HEIGHT: f32 = 260.0;
+const MARKER_RADIUS: f32 = 3.0;
+// how close to a marker a click has to be, in points
+const MARKER_CLICK_DISTANCE: f32 = 8.0;
+const COMPONENT_COLOURS: [Color32; 3] = [
+    Color32::from_rgb(240, 110, 110),
+    Color32::from_rgb(120, 220, 140),
+    Color32::from_rgb(110, 160, 240),
+];
+
+// the values one entity prop had over the whole demo, plotted with a marker at every change.
+// clicking a change goes to its frame in the Frames (or Sign On Frames) tool.
+pub struct PropHistoryViewModel {
+    title: String,
+    changes: Vec<PropChange>,
+    // where each change's frame is in the frames tools
+    frames: Vec<Option<FrameRef>>,
+    // each plotted component's name and its value at each change, None when it can't be plotted
+    components: Vec<(&'static str, Vec<Option<f64>>)>,
+    last_tick: i32,
+    tick_interval: f32,
+}
+
+impl PropHistoryViewModel {
+    pub fn new(title: String, changes: Vec<PropChange>, document: &DemoDocument, tick_interval: f32) -> Self {
+        let frame_lookup = FrameLookup::new(document);
+        let frames = changes
+            .iter()
+            .map(|c| frame_lookup.find(c.location.sign_on, c.location.frame_index))
+            .collect();
+
+        let component_names: &[&'static str] = match changes.iter().find_map(|c| c.value.as_ref()) {
+            Some(PropValue::Vector(_)) => &["x", "y", "z"],
+            Some(PropValue::VectorXY(_)) => &["x", "y"],
+            Some(PropValue::Int(_) | PropValue::Float(_)) => &["value"],
+            _ => &[]
+        };
+        let components = component_names
+            .iter()
+            .enumerate()
+            .map(|(i, &name)| {
+                let values = changes.iter().map(|c| match &c.value {
+                    Some(PropValue::Int(v)) => Some(*v as f64),
+                    Some(PropValue::Float(v)) => Some(*v as f64),
+                    Some(PropValue::Vector(v)) => Some(v[i] as f64),
+                    Some(PropValue::VectorXY(v)) => v.get(i).map(|v| *v as f64),
+                    _ => None
+                }).collect();
+                (name, values)
+            })
+            .collect();
+
+        let last_tick = document.frames.last().map(|f| f.tick).unwrap_or(0);
+
+        Self {
+            title,
+            changes,
+            frames,
+            components,
+            last_tick,
+            tick_interval,
+        }
+    }
+
+    fn goto_change(&self, events: &mut Vec<Event>, index: usize) {
+        if let Some(frame) = self.frames[index] {
+            frame.goto(events);
+        }
+    }
+
+    fn draw_plot(&self, ui: &mut egui::Ui, events: &mut Vec<Event>) {
+        let tick_interval = self.tick_interval;
+        let plot = Plot::new("prop_history_plot")
+            .height(PLOT_HEIGHT)
+            .legend(Default::default())
+            .allow_boxed_zoom(true)
+            .label_formatter(move |name, point| format!(
+                "{}\ntick {} ({})\n{}",
+                name,
+                point.x.round() as i32,
+                tick_to_time_string(tick_interval, point.x.round() as i32),
+                point.y
+            ));
+
+        let response = plot.show(ui, |plot_ui| {
+            // a step line per component, broken where the value is unset
+            for (c, (name, values)) in self.components.iter().enumerate() {
+                let colour = COMPONENT_COLOURS[c % COMPONENT_COLOURS.len()];
+                let mut segment: Vec<[f64; 2]> = Vec::new();
+                for (i, value) in values.iter().enumerate() {
+                    let tick = self.changes[i].tick as f64;
+                    let next_tick = self.changes.get(i + 1).map(|c| c.tick).unwrap_or(self.last_tick) as f64;
+                    match value {
+                        Some(v) => {
+                            segment.push([tick, *v]);
+                            segment.push([next_tick, *v]);
+                        },
+                        None if !segment.is_empty() => {
+                            plot_ui.line(Line::new(std::mem::take(&mut segment)).color(colour).name(name));
+                        },
+                        None => {}
+                    }
+                }
+                if !segment.is_empty() {
+                    plot_ui.line(Line::new(segment).color(colour).name(name));
+                }
+
+                let markers: Vec<[f64; 2]> = values
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, v)| Some([self.changes[i].tick as f64, (*v)?]))
+                    .collect();
+                plot_ui.points(Points::new(markers).radius(MARKER_RADIUS).color(colour).name(name));
+            }
+            // values that can't be plotted still get their change markers
+            if self.components.is_empty() {
+                let markers: Vec<[f64; 2]> = self.changes.iter().map(|c| [c.tick as f64, 0.0]).collect();
+                plot_ui.points(Points::new(markers).radius(MARKER_RADIUS).shape(MarkerShape::Diamond).name("changed"));
+            }
+
+            if !plot_ui.plot_clicked() {
+                return None
+            }
+            let pointer = plot_ui.screen_from_plot(plot_ui.pointer_coordinate()?);
+            let marker_ys: Vec<Vec<Option<f64>>> = if self.components.is_empty() {
+                vec![vec![Some(0.0); self.changes.len()]]
+            } else {
+                self.components.iter().map(|(_, values)| values.clone()).collect()
+            };
+            let mut closest: Option<(usize, f32)> = None;
+            for values in &marker_ys {
+                for (i, y) in values.iter().enumerate() {
+                    let y = match y {
+                        Some(y) => *y,
+                        None => continue
+                    };
+                    let marker = plot_ui.screen_from_plot(PlotPoint::new(self.changes[i].tick as f64, y));
+                    let distance = marker.distance(pointer);
+                    if distance <= MARKER_CLICK_DISTANCE && closest.is_none_or(|(_, d)| distance < d) {
+                        closest = Some((i, distance));
+                    }
+                }
+            }
+            closest.map(|(i, _)| i)
+        });
+
+        if let Some(index) = response.inner {
+            self.goto_change(events, index);
+        }
+    }
+
+    fn draw_change_list(&self, ui: &mut egui::Ui, events: &mut Vec<Event>) {
+        TableBuilder::new(ui)
+        .striped(true)
+        .column(Column::exact(table_constants::COL_TICK_WIDTH))
+        .column(Column::exact(table_constants::COL_TIME_WIDTH))
+        .column(Column::remainder())
+        .header(table_constants::HEADER_HEIGHT, |mut row| {
+            row.col(|ui| {
+                ui.label("Tick");
+            });
+            row.col(|ui| {
+                ui.label("Time");
+            });
+            row.col(|ui| {
+                ui.label("Value");
+            });
+        })
+        .body(|body| {
+            body.rows(
+                table_constants::ROW_HEIGHT,
+                self.changes.len(),
+                |index, mut row| {
+                    let change = &self.changes[index];
+                    let value = match &change.value {
+                        Some(v) => RichText::new(v.to_string()),
+                        None => RichText::new("None").weak()
+                    };
+                    let responses = [
+                        row.col(|ui| {
+                            ui.label(change.tick.to_string());
+                        }).1,
+                        row.col(|ui| {
+                            ui.label(tick_to_time_string(self.tick_interval, change.tick));
+                        }).1,
+                        row.col(|ui| {
+                            ui.label(value);
+                        }).1,
+                    ];
+                    for res in responses {
+                        if res
+                        .interact(Sense::click())
+                        .on_hover_cursor(CursorIcon::PointingHand)
+                        .clicked() {
+                            self.goto_change(events, index);
+                        }
+                    }
+                });
+        });
+    }
+}
+
+impl ViewModel for PropHistoryViewModel {
+    fn draw(&mut self, ui: &mut egui::Ui, events: &mut Vec<Event>) {
+        ui.horizontal(|ui| {
+            ui.label(RichText::new(&self.title).strong());
+            ui.label(format!("{} changes", self.changes.len()));
+        });
+        if self.changes.is_empty() {
+            ui.label("The prop is never set.");
+            return
+        }
+        ui.label(RichText::new("Click a marker or a row to go to the frame of the change.").weak());
+
+        self.draw_plot(ui, events);
+        ui.separator();
+        ui.push_id("prop_history_changes", |ui| {
+            self.draw_change_list(ui, events);
+        });
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any { self }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any { self }
+}
\ No newline at end of file
@@ -1,7 +1,15 @@
-use std::{fs::File, io::Write};
+use std::{collections::BTreeMap, fs::File, io::Write};
 
-use super::{ViewModel, wfn_text_edit_singleline::wfn_text_edit_singleline};
+use super::{Event, ViewModel, wfn_text_edit_singleline::wfn_text_edit_singleline};
+use crate::{
+    edit::{
+        journal::DocumentEdit,
+        protobuf_field::{ FieldValue, MessageTarget, ProtobufFieldEdit },
+    },
+    export::{ flatten_protobuf_fields, protobuf_value_to_string },
+};
 
+use eframe::egui::{ Color32, RichText };
 use rfd::MessageLevel;
 use source_demo_tool::protobuf_message::ProtobufMessageEnumTraits;
 
@@ -10,11 +18,20 @@ use egui_extras::{ TableBuilder, Column };
 use source_demo_tool::demo_file::packet::protobuf_value::ProtobufValue;
 
 const FIELD_NAME_WIDTH: f32 = 200.0;
+const CHANGED_COLOUR: Color32 = Color32::LIGHT_YELLOW;
+const ERROR_COLOUR: Color32 = Color32::from_rgb(240, 110, 110);
 
 pub struct ProtobufMessageViewModel {
     pub message: Box<dyn ProtobufMessageEnumTraits>,
     pub field_data: Vec<(String, ProtobufValue)>,
     hide_none_values: bool,
+    // where the message is in the demo, fields can only be edited when this is known
+    target: Option<MessageTarget>,
+    // values edited since the demo was opened, shown over the parsed ones
+    edited_values: BTreeMap<String, FieldValue>,
+    // the field being edited and its text
+    editing: Option<(String, String)>,
+    edit_error: Option<String>,
 }
 
 impl ProtobufMessageViewModel {
@@ -23,12 +40,60 @@ impl ProtobufMessageViewModel {
             message,
             field_data: Vec::new(),
             hide_none_values: false,
+            target: None,
+            edited_values: BTreeMap::new(),
+            editing: None,
+            edit_error: None,
         };
         rval.update_field_data();
 
         rval
     }
 
+    // makes the fields editable, edits already made to the message are shown.
+    pub fn set_edit_target(&mut self, target: MessageTarget, edits: &[ProtobufFieldEdit]) {
+        self.target = Some(target);
+        for edit in edits {
+            self.apply_field_edit(edit);
+        }
+    }
+
+    // shows an edit that was applied to the demo, if it's for this message.
+    pub fn apply_field_edit(&mut self, edit: &ProtobufFieldEdit) {
+        if self.target != Some(edit.target) {
+            return
+        }
+        self.edited_values.insert(edit.field.clone(), edit.value.clone());
+        self.update_field_data();
+    }
+
+    fn push_edit(&mut self, events: &mut Vec<Event>, field: String, value: FieldValue) {
+        if let Some(target) = self.target {
+            // DemoFileViewModel applies this
+            events.push(Event::ApplyEdit(DocumentEdit::ProtobufField(ProtobufFieldEdit { target, field, value })));
+        }
+    }
+
+    fn replace_from_file(&mut self, events: &mut Vec<Event>, field: String) {
+        let path = match rfd::FileDialog::new()
+            .add_filter("Binary Data", &["bin"])
+            .set_title("Replace Binary Data")
+            .pick_file() {
+            Some(p) => p,
+            None => return
+        };
+        match std::fs::read(&path) {
+            Ok(data) => self.push_edit(events, field, FieldValue::Length(data)),
+            Err(e) => {
+                rfd::MessageDialog::new()
+                .set_title("File Error")
+                .set_description(format!("Failed to read {}: {}", path.display(), e).as_str())
+                .set_level(MessageLevel::Error)
+                .show();
+            }
+        }
+    }
+
     pub fn hide_none_values_set(&mut self, value: bool) {
         self.hide_none_values = value;
         self.update_field_data();
@@ -39,69 +104,44 @@ impl ProtobufMessageViewModel {
     }
 
     fn update_field_data(&mut self) {
-        let mut int_field_data: Vec<(String, ProtobufValue)> = self.message
-            .to_vec()
-            .into_iter()
-            .map(|(name, val)| {
-              (name.to_string(), val)
-            })
-            .collect();
-
-        // flatten the field_data and skip None values if requested
-        let mut field_data;
-        loop {
-            let mut did_work = false;
-            field_data = Vec::new();
-
-            for (field_name, field_val) in int_field_data {
-                match field_val {
-                    ProtobufValue::Proto(vec_proto_fields) => {
-                        for (sub_field_name, sub_field_value) in vec_proto_fields {
-                            let sub_name = format!("{}.{}", field_name, sub_field_name);
-                            field_data.push((sub_name, sub_field_value));
-                        }
-                        did_work = true;
-                    },
-                    ProtobufValue::Repeated(vec_proto_values) => {
-                        let mut it = 0;
-                        for sub_value in vec_proto_values {
-                            let sub_name = format!("{}[{}]", field_name, it);
-                            field_data.push((sub_name, sub_value));
-                            it += 1;
-                        }
-                        did_work = true;
-                    },
-                    ProtobufValue::None => {
-                        if !self.hide_none_values {
-                            field_data.push((field_name, ProtobufValue::None));
-                        }
-                    },
-                    val => field_data.push((field_name, val))
-                }
-            }
-
-            if !did_work {
-                break
+        self.field_data = flatten_protobuf_fields(
+            self.message.to_vec(),
+            self.hide_none_values
+        );
+        for (name, value) in &mut self.field_data {
+            if let Some(edited) = self.edited_values.get(name) {
+                *value = edited.to_protobuf_value();
             }
-
-            // did work, re-run the loop
-            int_field_data = field_data;
         }
-
-        self.field_data = field_data;
     }
 }
 
+enum FieldAction {
+    BeginEdit(String, String),
+    CancelEdit,
+    Commit(String, Result<FieldValue, String>),
+    ReplaceFromFile(String),
+}
+
 impl ViewModel for ProtobufMessageViewModel {
-    fn draw(&mut self, ui: &mut eframe::egui::Ui, _events: &mut Vec<super::Event>) {
+    fn draw(&mut self, ui: &mut eframe::egui::Ui, events: &mut Vec<super::Event>) {
+        let mut action = None;
         ui.push_id(3, |ui| {
             ui.set_width(ui.available_width());
             ui.set_height(ui.available_height());
 
-            if ui.checkbox(&mut self.hide_none_values, "Hide None Values").changed() {
-                self.update_field_data();
-            }
+            ui.horizontal(|ui| {
+                if ui.checkbox(&mut self.hide_none_values, "Hide None Values").changed() {
+                    self.update_field_data();
+                }
+                if let Some(e) = &self.edit_error {
+                    ui.label(RichText::new(e).color(ERROR_COLOUR));
+                }
+            });
 
+            let is_editable = self.target.is_some();
+            let editing = &mut self.editing;
+            let edited_values = &self.edited_values;
             TableBuilder::new(ui)
             .column(Column::initial(FIELD_NAME_WIDTH).resizable(true))
             .column(Column::remainder())
@@ -109,19 +149,14 @@ impl ViewModel for ProtobufMessageViewModel {
                 body.rows(super::table_constants::ROW_HEIGHT, self.field_data.len(), |index, mut row| {
                     let field = &self.field_data[index];
                     row.col(|ui| {
-                        ui.label(&field.0);
+                        if edited_values.contains_key(&field.0) {
+                            ui.label(RichText::new(format!("{} *", field.0)).color(CHANGED_COLOUR));
+                        } else {
+                            ui.label(&field.0);
+                        }
                     });
                     row.col(|ui| {
-                        let mut val_str: String = match &field.1 {
-                            ProtobufValue::None => "None".to_string(),
-                            ProtobufValue::VarInt(v) => v.to_string(),
-                            ProtobufValue::Length(v) => format!("Data ({} Bytes)", v.len()),
-                            ProtobufValue::String(v) => v.clone(),
-                            ProtobufValue::Fixed32(v) => v.to_string(),
-                            ProtobufValue::Float32(v) => v.to_string(),
-                            ProtobufValue::Proto(_v) => panic!("Proto should be flattened out"),
-                            ProtobufValue::Repeated(_v) => panic!("Repeated should be flattened out"),
-                        };
+                        let mut val_str = protobuf_value_to_string(&field.1);
 
                         match &field.1 {
                             ProtobufValue::Length(d) => {
@@ -156,14 +191,70 @@ impl ViewModel for ProtobufMessageViewModel {
                                             None => {}
                                         }
                                     }
+                                    if is_editable && ui.button("📂").on_hover_text("Replace from file").clicked() {
+                                        action = Some(FieldAction::ReplaceFromFile(field.0.clone()));
+                                    }
                                 });
                             },
-                            _ => { wfn_text_edit_singleline(ui, &mut val_str, None, true); }
+                            ProtobufValue::None => { wfn_text_edit_singleline(ui, &mut val_str, None, true); },
+                            value => match editing {
+                                Some((name, text)) if *name == field.0 => {
+                                    ui.horizontal(|ui| {
+                                        ui.text_edit_singleline(text);
+                                        if ui.button("✔").clicked() {
+                                            action = Some(FieldAction::Commit(name.clone(), FieldValue::parse_like(value, text)));
+                                        }
+                                        if ui.button("✖").clicked() {
+                                            action = Some(FieldAction::CancelEdit);
+                                        }
+                                    });
+                                },
+                                _ => {
+                                    ui.horizontal(|ui| {
+                                        if is_editable && ui.button("✏").on_hover_text("Edit").clicked() {
+                                            action = Some(FieldAction::BeginEdit(field.0.clone(), val_str.clone()));
+                                        }
+                                        wfn_text_edit_singleline(ui, &mut val_str, None, true);
+                                    });
+                                }
+                            }
                         }
                     });
                 });
             });
         });
+
+        match action {
+            Some(FieldAction::BeginEdit(field, text)) => {
+                self.editing = Some((field, text));
+                self.edit_error = None;
+            },
+            Some(FieldAction::CancelEdit) => {
+                self.editing = None;
+                self.edit_error = None;
+            },
+            Some(FieldAction::Commit(field, value)) => match value {
+                Ok(value) => {
+                    self.editing = None;
+                    self.edit_error = None;
+                    self.push_edit(events, field, value);
+                },
+                Err(e) => self.edit_error = Some(e)
+            },
+            Some(FieldAction::ReplaceFromFile(field)) => self.replace_from_file(events, field),
+            None => {}
+        }
+    }
+
+    fn handle_event(&mut self, event: &Event) -> bool {
+        if let Event::DocumentEdited(edit) = event {
+            match edit {
+                DocumentEdit::ProtobufField(field_edit) => self.apply_field_edit(field_edit),
+                // the message may have moved, or been removed
+                edit => self.target = self.target.and_then(|t| edit.shift_target(t)),
+            }
+        }
+        false
     }
 
     fn as_any(&self) -> &dyn std::any::Any { self }
@@ -1,7 +1,10 @@
 use std::collections::BTreeMap;
 
-use super::vm_demo_file::tick_to_time_string;
-use super::{ Event, ViewModel, Focusable, table_constants };
+use super::{ Event, ViewModel, Focusable, table_constants, wfn_export_csv_button::wfn_export_csv_button };
+use crate::{
+    edit::{ journal::DocumentEdit, protobuf_field::{ MessageTarget, ProtobufFieldEdit } },
+    export::{ self, tick_to_time_string },
+};
 use eframe::egui::{ self, RichText, Sense, CursorIcon, Layout };
 use eframe::emath::Align;
 use egui_extras::{ Column, TableBuilder };
@@ -49,6 +52,14 @@ pub struct ProtobufMessageListViewModel<MessageType: ProtobufMessageEnumTraits>
         dyn Fn(&MessageType) -> String
         + Send
     >>,
+    message_target_callback: Option<Box<
+        dyn Fn(usize) -> Option<MessageTarget>
+        + Send
+    >>,
+    // edits applied to the demo, passed on to the message view when its message is selected
+    field_edits: Vec<ProtobufFieldEdit>,
+    // edits that moved messages since the target callback was set, its targets are moved by these
+    layout_edits: Vec<DocumentEdit>,
 }
 
 impl<MessageType: ProtobufMessageEnumTraits + Clone + 'static> ProtobufMessageListViewModel<MessageType> {
@@ -137,6 +148,9 @@ impl<MessageType: ProtobufMessageEnumTraits + Clone + 'static> ProtobufMessageLi
             filterable: false,
             active_filter_index: 0,
             message_name_callback: None,
+            message_target_callback: None,
+            field_edits: Vec::new(),
+            layout_edits: Vec::new(),
         }
     }
 
@@ -197,6 +211,18 @@ impl<MessageType: ProtobufMessageEnumTraits + Clone + 'static> ProtobufMessageLi
         self.message_name_callback = Some(Box::new(callback));
     }
 
+    // where each message is in the demo, messages with a target can be edited.
+    pub fn set_message_target_callback<F>(&mut self, callback: F)
+    where F: Fn(usize) -> Option<MessageTarget>
+    + Send + 'static {
+        self.message_target_callback = Some(Box::new(callback));
+        self.layout_edits.clear();
+    }
+
+    pub fn set_field_edits(&mut self, field_edits: Vec<ProtobufFieldEdit>) {
+        self.field_edits = field_edits;
+    }
+
     pub fn get_active_message(&self) -> &Option<usize> {
         &self.active_message
     }
@@ -218,6 +244,13 @@ impl<MessageType: ProtobufMessageEnumTraits + Clone + 'static> ProtobufMessageLi
                     };
 
                     let mut pbm_vm = ProtobufMessageViewModel::new(Box::new(msg.clone()));
+                    let target = self.message_target_callback
+                        .as_ref()
+                        .and_then(|f| f(index))
+                        .and_then(|t| self.layout_edits.iter().try_fold(t, |t, edit| edit.shift_target(t)));
+                    if let Some(target) = target {
+                        pbm_vm.set_edit_target(target, &self.field_edits);
+                    }
                     pbm_vm.hide_none_values_set(hide_none_values);
 
                     self.vm_protobuf_message = Some(
@@ -282,6 +315,29 @@ impl<MessageType: ProtobufMessageEnumTraits + Clone + 'static> ProtobufMessageLi
         self.set_active_message(self.display_messages.last().unwrap().0)
     }
 
+    fn message_name(&self, message_return: &MessageParseReturn<MessageType>) -> String {
+        if let Some(msg) = &message_return.message {
+            if let Some(f) = &self.message_name_callback {
+                f(&msg)
+            } else {
+                msg.to_str().to_owned()
+            }
+        } else {
+            "????".to_owned()
+        }
+    }
+
+    fn write_display_messages_csv(&self, writer: &mut impl std::io::Write) -> std::io::Result<()> {
+        let rows = self.display_messages
+            .iter()
+            .map(|(i, msg_return)| (
+                *i,
+                self.message_ticks.as_ref().map(|ticks| ticks[*i]),
+                self.message_name(msg_return)
+            ));
+        export::write_messages_csv(writer, rows, self.tick_interval)
+    }
+
     pub fn clear_filter(&mut self) {
         self.active_filter_index = 0;
         self.display_messages = self.messages["None"].clone().into_iter().collect();
@@ -325,38 +381,42 @@ impl<MessageType: ProtobufMessageEnumTraits + Clone + 'static> ViewModel for Pro
                     ui.set_width(message_list_width);
                     ui.set_height(avail_space.y - table_constants::BOTTOM_MARGIN);
 
-                    if self.filterable {
-                        ui.with_layout(
-                            Layout::right_to_left(Align::TOP),
-                            |ui| {
-                                ui.set_width(message_list_width);
-                                ui.add_space(20.0);
-
-                                if egui::ComboBox::new(
-                                    ui.next_auto_id(),
-                                    "Filter"
-                                ).width(200.0)
-                                .show_index(
-                                    ui,
-                                    &mut self.active_filter_index,
-                                    self.filterable_list.len(),
-                                    |i| self.filterable_list[i].clone()
-                                )
-                                .changed() {
-                                    self.display_messages = {
-                                        if self.active_filter_index != 0 {
-                                            let msg_str = self.filterable_data[self.active_filter_index - 1].0;
-                                            self.messages[msg_str].clone().into_iter().collect()
-                                        } else {
-                                            self.messages["None"].clone().into_iter().collect()
-                                        }
-                                    };
-                                    self.first_message();
-                                }
+                    ui.with_layout(
+                        Layout::right_to_left(Align::TOP),
+                        |ui| {
+                            ui.set_width(message_list_width);
+                            ui.add_space(20.0);
+
+                            if self.filterable && egui::ComboBox::new(
+                                ui.next_auto_id(),
+                                "Filter"
+                            ).width(200.0)
+                            .show_index(
+                                ui,
+                                &mut self.active_filter_index,
+                                self.filterable_list.len(),
+                                |i| self.filterable_list[i].clone()
+                            )
+                            .changed() {
+                                self.display_messages = {
+                                    if self.active_filter_index != 0 {
+                                        let msg_str = self.filterable_data[self.active_filter_index - 1].0;
+                                        self.messages[msg_str].clone().into_iter().collect()
+                                    } else {
+                                        self.messages["None"].clone().into_iter().collect()
+                                    }
+                                };
+                                self.first_message();
                             }
-                        );
-                        ui.end_row();
-                    }
+
+                            wfn_export_csv_button(
+                                ui,
+                                &format!("{}.csv", self.name),
+                                |w| self.write_display_messages_csv(w)
+                            );
+                        }
+                    );
+                    ui.end_row();
 
                     let mut table_builder = TableBuilder::new(ui);
 
@@ -411,17 +471,7 @@ impl<MessageType: ProtobufMessageEnumTraits + Clone + 'static> ViewModel for Pro
                             |index, mut row| {
 
                             let message_return_pair = &self.display_messages[index];
-                            let name = {
-                                if let Some(msg) = &message_return_pair.1.message {
-                                    if let Some(f) = &self.message_name_callback {
-                                        f(&msg)
-                                    } else {
-                                        msg.to_str().to_owned()
-                                    }
-                                } else {
-                                    "????".to_owned()
-                                }
-                            };
+                            let name = self.message_name(&message_return_pair.1);
 
                             let real_index = self.display_messages[index].0;
                             let is_active = {
@@ -534,6 +584,20 @@ impl<MessageType: ProtobufMessageEnumTraits + Clone + 'static> ViewModel for Pro
     }
 
     fn handle_event(&mut self, event: &Event) -> bool {
+        if let Event::DocumentEdited(edit) = event {
+            match edit {
+                DocumentEdit::ProtobufField(field_edit) => self.field_edits.push(field_edit.clone()),
+                edit if edit.moves_messages() => {
+                    self.field_edits = self.field_edits
+                        .drain(..)
+                        .filter_map(|e| Some(ProtobufFieldEdit { target: edit.shift_target(e.target)?, ..e }))
+                        .collect();
+                    self.layout_edits.push(edit.clone());
+                },
+                _ => {}
+            }
+        }
+
         if let Event::SelectMessage(id, index) = event {
             if *id == self.name {
                 return self.set_active_message(*index)
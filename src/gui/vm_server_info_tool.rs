@@ -1,4 +1,5 @@
 use super::{ Event, ViewModel, vm_protobuf_message::ProtobufMessageViewModel };
+use crate::edit::protobuf_field::MessageTarget;
 use eframe::egui;
 use source_demo_tool::demo_file::packet::netmessage::{ServerInfoData, NetMessage};
 
@@ -7,11 +8,16 @@ pub struct ServerInfoViewModel {
 }
 
 impl ServerInfoViewModel {
-    pub fn new(server_info: Option<ServerInfoData>) -> Self {
+    // target is where the ServerInfo is in the demo, it can be edited when known.
+    pub fn new(server_info: Option<ServerInfoData>, target: Option<MessageTarget>) -> Self {
         let vm_protobuf_message = match server_info {
             Some(si) => {
                 let nmsg = NetMessage::ServerInfo(si);
-                Some(ProtobufMessageViewModel::new(Box::new(nmsg)))
+                let mut vm = ProtobufMessageViewModel::new(Box::new(nmsg));
+                if let Some(target) = target {
+                    vm.set_edit_target(target, &[]);
+                }
+                Some(vm)
             },
             None => None
         };
@@ -34,6 +40,13 @@ impl ViewModel for ServerInfoViewModel {
         }
     }
 
+    fn handle_event(&mut self, event: &Event) -> bool {
+        match &mut self.vm_protobuf_message {
+            Some(vm) => vm.handle_event(event),
+            None => false
+        }
+    }
+
     fn as_any(&self) -> &dyn std::any::Any { self }
     fn as_any_mut(&mut self) -> &mut dyn std::any::Any { self }
 }
\ No newline at end of file
@@ -0,0 +1,406 @@
+use super::{
+    Event,
+    ViewModel,
+    vm_frames_tool::{ FrameLookup, FrameRef },
+    table_constants,
+};
+use crate::{
+    document::{
+        DemoDocument,
+        player_info::{ PlayerInfo, USERINFO_TABLE_NAME },
+        string_table::{ self, StringTable, StringTableLocation, StringTableUpdate },
+    },
+    export::tick_to_time_string,
+};
+use eframe::egui::{ self, Color32, CursorIcon, RichText, Sense };
+use egui_extras::{ TableBuilder, Column };
+
+const TABLE_LIST_WIDTH: f32 = 220.0;
+const HISTORY_WIDTH: f32 = 420.0;
+const COL_USER_DATA_WIDTH: f32 = 220.0;
+const COL_FRAME_WIDTH: f32 = 180.0;
+// user data longer than this is cut short in the hover text
+const MAX_HEX_BYTES: usize = 256;
+const ERROR_COLOUR: Color32 = Color32::from_rgb(240, 110, 110);
+
+// a change to the selected entry, from a CreateStringTable/UpdateStringTable message or a StringTables frame.
+struct EntryChange {
+    tick: i32,
+    location: StringTableLocation,
+    frame: Option<FrameRef>,
+    string: String,
+    user_data: Option<Vec<u8>>,
+}
+
+// every string table and its entries at a tick, it follows the tick of the frame selected
+// in the Frames tool. DemoFileViewModel calls update before drawing.
+pub struct StringTablesToolViewModel {
+    updates: Result<Vec<StringTableUpdate>, String>,
+    frame_lookup: FrameLookup,
+    tick_interval: f32,
+    tick: i32,
+    // the Frames tool's tick when it was last followed
+    followed_tick: Option<i32>,
+    // the tables at tick and the tick each entry of the active table last changed at,
+    // None when they need building again, draw builds them
+    tables: Option<(Vec<StringTable>, Vec<Option<i32>>)>,
+    active_table: Option<String>,
+    active_entry: Option<usize>,
+    entry_history: Vec<EntryChange>,
+    entry_filter: String,
+    // the document was edited, the updates are read again next update
+    b_stale: bool,
+}
+
+impl StringTablesToolViewModel {
+    pub fn new(document: &DemoDocument, tick_interval: f32) -> Self {
+        Self {
+            updates: string_table::string_table_updates(document),
+            frame_lookup: FrameLookup::new(document),
+            tick_interval,
+            tick: 0,
+            followed_tick: None,
+            tables: None,
+            active_table: None,
+            active_entry: None,
+            entry_history: Vec::new(),
+            entry_filter: String::new(),
+            b_stale: false,
+        }
+    }
+
+    pub fn update(&mut self, document: &DemoDocument, frames_tick: Option<i32>) {
+        if self.b_stale {
+            self.updates = string_table::string_table_updates(document);
+            self.frame_lookup = FrameLookup::new(document);
+            self.tables = None;
+            self.update_entry_history();
+            self.b_stale = false;
+        }
+        if frames_tick != self.followed_tick {
+            self.followed_tick = frames_tick;
+            if let Some(tick) = frames_tick {
+                if tick != self.tick {
+                    self.tick = tick;
+                    self.tables = None;
+                }
+            }
+        }
+    }
+
+    fn update_tables(&mut self) {
+        let updates = match &self.updates {
+            Ok(u) => u,
+            Err(_) => return
+        };
+        let tables = string_table::string_tables_at(updates, self.tick);
+
+        let mut changed_ticks = Vec::new();
+        if let Some(table) = tables.iter().find(|t| Some(&t.name) == self.active_table.as_ref()) {
+            changed_ticks = vec![None; table.entries.len()];
+            for update in updates.iter().filter(|u| u.table_name == table.name && u.applies_at(self.tick)) {
+                for entry in &update.entries {
+                    if let Some(changed) = changed_ticks.get_mut(entry.index) {
+                        *changed = Some(update.tick);
+                    }
+                }
+            }
+        }
+        self.tables = Some((tables, changed_ticks));
+    }
+
+    fn update_entry_history(&mut self) {
+        self.entry_history.clear();
+        let (updates, table_name, index) = match (&self.updates, &self.active_table, self.active_entry) {
+            (Ok(u), Some(t), Some(i)) => (u, t, i),
+            _ => return
+        };
+        for update in updates.iter().filter(|u| u.table_name == *table_name) {
+            for entry in update.entries.iter().filter(|e| e.index == index) {
+                let frame = match update.location {
+                    StringTableLocation::Message(l) => self.frame_lookup.find(l.sign_on, l.frame_index),
+                    StringTableLocation::Frame { .. } => None,
+                };
+                self.entry_history.push(EntryChange {
+                    tick: update.tick,
+                    location: update.location,
+                    frame,
+                    string: entry.string.clone(),
+                    user_data: entry.user_data.clone(),
+                });
+            }
+        }
+    }
+
+    fn set_active_table(&mut self, name: String) {
+        self.active_table = Some(name);
+        self.active_entry = None;
+        self.tables = None;
+        self.update_entry_history();
+    }
+
+    fn set_active_entry(&mut self, index: usize) {
+        self.active_entry = Some(index);
+        self.update_entry_history();
+    }
+
+    fn draw_table_list(&self, ui: &mut egui::Ui, tables: &[StringTable]) -> Option<String> {
+        let mut clicked = None;
+        egui::ScrollArea::vertical().id_source("string_table_list").show(ui, |ui| {
+            for table in tables {
+                let is_active = self.active_table.as_ref() == Some(&table.name);
+                let label = format!("{} ({})", table.name, table.entries.len());
+                if ui.selectable_label(is_active, label).clicked() {
+                    clicked = Some(table.name.clone());
+                }
+            }
+        });
+        clicked
+    }
+
+    fn draw_entries(&self, ui: &mut egui::Ui, table: &StringTable, changed_ticks: &[Option<i32>]) -> Option<usize> {
+        let filter = self.entry_filter.to_lowercase();
+        let display_entries: Vec<usize> = (0..table.entries.len())
+            .filter(|&i| filter.is_empty() || table.entries[i].0.to_lowercase().contains(&filter))
+            .collect();
+
+        let mut clicked = None;
+        TableBuilder::new(ui)
+        .striped(true)
+        .column(Column::exact(table_constants::COL_INDEX_WIDTH))
+        .column(Column::remainder())
+        .column(Column::exact(COL_USER_DATA_WIDTH))
+        .column(Column::exact(table_constants::COL_TICK_WIDTH))
+        .header(table_constants::HEADER_HEIGHT, |mut row| {
+            row.col(|ui| {
+                ui.label("Index");
+            });
+            row.col(|ui| {
+                ui.label("String");
+            });
+            row.col(|ui| {
+                ui.label("User Data");
+            });
+            row.col(|ui| {
+                ui.label("Changed");
+            }).1.on_hover_text("The tick the entry last changed at");
+        })
+        .body(|body| {
+            body.rows(
+                table_constants::ROW_HEIGHT,
+                display_entries.len(),
+                |index, mut row| {
+                    let entry_index = display_entries[index];
+                    let (string, user_data) = &table.entries[entry_index];
+                    let is_active = self.active_entry == Some(entry_index);
+                    let changed = match changed_ticks.get(entry_index) {
+                        Some(Some(tick)) => tick.to_string(),
+                        _ => String::new()
+                    };
+                    let texts = [
+                        entry_index.to_string(),
+                        string.clone(),
+                        describe_user_data(&table.name, user_data.as_deref()),
+                        changed,
+                    ];
+
+                    for text in texts {
+                        let res = row.col(|ui| {
+                            if is_active {
+                                ui.label(RichText::new(text).color(table_constants::SELECTED_ITEM_COLOUR));
+                            } else {
+                                ui.label(text);
+                            }
+                        }).1;
+                        let res = res
+                            .interact(Sense::click())
+                            .on_hover_cursor(CursorIcon::PointingHand);
+                        let res = match user_data {
+                            Some(data) => res.on_hover_text(hex_string(data)),
+                            None => res
+                        };
+                        if res.clicked() {
+                            clicked = Some(entry_index);
+                        }
+                    }
+                });
+        });
+        clicked
+    }
+
+    fn draw_entry_history(&self, ui: &mut egui::Ui, table_name: &str, events: &mut Vec<Event>) {
+        ui.label(RichText::new(format!(
+            "{} entry {} changes",
+            table_name,
+            self.active_entry.unwrap_or_default()
+        )).strong());
+        ui.label(RichText::new("Click a change to go to its frame.").weak());
+
+        TableBuilder::new(ui)
+        .striped(true)
+        .column(Column::exact(table_constants::COL_TICK_WIDTH))
+        .column(Column::exact(COL_FRAME_WIDTH))
+        .column(Column::remainder())
+        .header(table_constants::HEADER_HEIGHT, |mut row| {
+            row.col(|ui| {
+                ui.label("Tick");
+            });
+            row.col(|ui| {
+                ui.label("Sent In");
+            });
+            row.col(|ui| {
+                ui.label("Value");
+            });
+        })
+        .body(|body| {
+            body.rows(
+                table_constants::ROW_HEIGHT,
+                self.entry_history.len(),
+                |index, mut row| {
+                    let change = &self.entry_history[index];
+                    let value = match &change.user_data {
+                        Some(data) => format!("{}, {}", change.string, describe_user_data(table_name, Some(data))),
+                        None => change.string.clone()
+                    };
+                    let responses = [
+                        row.col(|ui| {
+                            ui.label(change.tick.to_string())
+                                .on_hover_text(tick_to_time_string(self.tick_interval, change.tick));
+                        }).1,
+                        row.col(|ui| {
+                            ui.label(change.location.describe());
+                        }).1,
+                        row.col(|ui| {
+                            ui.label(value);
+                        }).1,
+                    ];
+                    if let Some(frame) = change.frame {
+                        for res in responses {
+                            if res
+                            .interact(Sense::click())
+                            .on_hover_cursor(CursorIcon::PointingHand)
+                            .clicked() {
+                                frame.goto(events);
+                            }
+                        }
+                    }
+                });
+        });
+    }
+}
+
+impl ViewModel for StringTablesToolViewModel {
+    fn draw(&mut self, ui: &mut egui::Ui, events: &mut Vec<Event>) {
+        if let Err(e) = &self.updates {
+            ui.label(RichText::new(format!("The string tables couldn't be decoded: {}", e)).color(ERROR_COLOUR));
+            return
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Tick");
+            if ui.add(egui::DragValue::new(&mut self.tick).clamp_range(0..=i32::MAX)).changed() {
+                self.tables = None;
+            }
+            ui.label(tick_to_time_string(self.tick_interval, self.tick));
+            ui.label(RichText::new("Selecting a frame in the Frames tool moves here too.").weak());
+        });
+        ui.separator();
+
+        if self.tables.is_none() {
+            self.update_tables();
+        }
+        // taken out while drawing so the lists can borrow self
+        let (tables, changed_ticks) = match self.tables.take() {
+            Some(t) => t,
+            None => return
+        };
+
+        let avail_width = ui.available_width();
+        let avail_height = ui.available_height() - table_constants::BOTTOM_MARGIN;
+        let active_table = tables.iter().find(|t| Some(&t.name) == self.active_table.as_ref());
+        let mut clicked_table = None;
+        let mut clicked_entry = None;
+        egui::Grid::new("string_tables_tool_grid").show(ui, |ui| {
+            ui.vertical(|ui| {
+                ui.set_width(TABLE_LIST_WIDTH);
+                ui.set_height(avail_height);
+                clicked_table = self.draw_table_list(ui, &tables);
+            });
+
+            let table = match active_table {
+                Some(t) => t,
+                None => {
+                    ui.label("Select a table.");
+                    return
+                }
+            };
+            ui.vertical(|ui| {
+                let mut width = avail_width - TABLE_LIST_WIDTH;
+                if self.active_entry.is_some() {
+                    width -= HISTORY_WIDTH;
+                }
+                ui.set_width(width);
+                ui.set_height(avail_height);
+                ui.horizontal(|ui| {
+                    ui.label("Filter");
+                    ui.text_edit_singleline(&mut self.entry_filter);
+                });
+                ui.push_id("string_table_entries", |ui| {
+                    clicked_entry = self.draw_entries(ui, table, &changed_ticks);
+                });
+            });
+
+            if self.active_entry.is_some() {
+                ui.vertical(|ui| {
+                    ui.set_height(avail_height);
+                    ui.push_id("string_table_entry_history", |ui| {
+                        self.draw_entry_history(ui, &table.name, events);
+                    });
+                });
+            }
+        });
+
+        self.tables = Some((tables, changed_ticks));
+        if let Some(name) = clicked_table {
+            self.set_active_table(name);
+        }
+        if let Some(index) = clicked_entry {
+            self.set_active_entry(index);
+        }
+    }
+
+    fn handle_event(&mut self, event: &Event) -> bool {
+        if let Event::DocumentEdited(_) = event {
+            self.b_stale = true;
+        }
+        false
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any { self }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any { self }
+}
+
+fn describe_user_data(table_name: &str, user_data: Option<&[u8]>) -> String {
+    let data = match user_data {
+        Some(d) => d,
+        None => return String::new()
+    };
+    if table_name == USERINFO_TABLE_NAME {
+        if let Some(info) = PlayerInfo::from_bytes(data) {
+            return format!("{} (userid {})", info.name, info.user_id)
+        }
+    }
+    format!("{} Bytes", data.len())
+}
+
+fn hex_string(data: &[u8]) -> String {
+    let mut hex: Vec<String> = data
+        .iter()
+        .take(MAX_HEX_BYTES)
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    if data.len() > MAX_HEX_BYTES {
+        hex.push("…".to_owned());
+    }
+    hex.chunks(16).map(|line| line.join(" ")).collect::<Vec<String>>().join("\n")
+}
\ No newline at end of file
@@ -0,0 +1,126 @@
+use std::collections::{ BTreeMap, BTreeSet };
+
+use super::{ Event, ViewModel, wfn_message_checklist::wfn_message_checklist };
+use crate::{
+    document::net_message_id,
+    edit::strip::{ MessageCounts, StripMessages },
+};
+use source_demo_tool::{
+    demo_file::packet::{ netmessage::NetMessage, usermessage::UserMessage },
+    protobuf_message::ProtobufMessageEnumTraits,
+};
+use eframe::egui::{ self, RichText };
+
+const CHECKLIST_WIDTH: f32 = 280.0;
+
+// "export without" dialog: uncheck message types to leave them out of the saved demo.
+pub struct StripMessagesViewModel {
+    net_messages: BTreeMap<u64, (String, bool, usize)>,
+    user_messages: BTreeMap<u64, (String, bool, usize)>,
+}
+
+impl StripMessagesViewModel {
+    pub fn new(counts: MessageCounts) -> Self {
+        Self {
+            net_messages: checklist(counts.net_messages, NetMessage::get_id_map()),
+            user_messages: checklist(counts.user_messages, UserMessage::get_id_map()),
+        }
+    }
+
+    fn strip_messages(&self) -> StripMessages {
+        StripMessages {
+            net_message_ids: unchecked(&self.net_messages),
+            user_message_ids: unchecked(&self.user_messages),
+        }
+    }
+
+    fn removed_count(&self) -> usize {
+        let mut count: usize = self.net_messages.values()
+            .filter(|(_, checked, _)| !checked)
+            .map(|(_, _, count)| count)
+            .sum();
+        if self.keeps_user_messages() {
+            count += self.user_messages.values()
+                .filter(|(_, checked, _)| !checked)
+                .map(|(_, _, count)| count)
+                .sum::<usize>();
+        }
+        count
+    }
+
+    fn keeps_user_messages(&self) -> bool {
+        self.net_messages.get(&net_message_id::USER_MESSAGE).is_none_or(|(_, checked, _)| *checked)
+    }
+}
+
+impl ViewModel for StripMessagesViewModel {
+    fn draw(&mut self, ui: &mut egui::Ui, events: &mut Vec<Event>) {
+        ui.vertical_centered(|ui| {
+            ui.add_space(15.0);
+            ui.label(RichText::new("Export Without…").size(32.0).italics());
+            ui.add_space(7.5);
+            ui.separator();
+            ui.add_space(7.5);
+        });
+        ui.label("Uncheck the message types to leave out of the saved demo.");
+        ui.label(RichText::new("Removing Tick, ServerInfo or string table messages will stop the demo from playing.").weak());
+        ui.add_space(7.5);
+
+        let height = ui.available_height() - 64.0;
+        ui.horizontal_top(|ui| {
+            ui.vertical(|ui| {
+                ui.set_width(CHECKLIST_WIDTH);
+                ui.label(RichText::new("Net Messages").strong());
+                egui::ScrollArea::vertical()
+                    .id_source("strip_net_messages")
+                    .max_height(height)
+                    .show(ui, |ui| wfn_message_checklist(ui, &mut self.net_messages));
+            });
+            ui.separator();
+            let keeps_user_messages = self.keeps_user_messages();
+            ui.vertical(|ui| {
+                ui.set_width(CHECKLIST_WIDTH);
+                ui.label(RichText::new("User Messages").strong());
+                ui.add_enabled_ui(keeps_user_messages, |ui| {
+                    egui::ScrollArea::vertical()
+                        .id_source("strip_user_messages")
+                        .max_height(height)
+                        .show(ui, |ui| wfn_message_checklist(ui, &mut self.user_messages));
+                });
+            });
+        });
+
+        ui.add_space(7.5);
+        let removed_count = self.removed_count();
+        ui.label(format!("{} messages will be removed.", removed_count));
+        ui.horizontal(|ui| {
+            if ui.button("Cancel").clicked() {
+                events.push(Event::CloseStripMessages);
+            }
+            if ui.add_enabled(removed_count > 0, egui::Button::new("Save As")).clicked() {
+                // vm_main handles this
+                events.push(Event::SaveStrippedDemo(self.strip_messages()));
+            }
+        });
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any { self }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any { self }
+}
+
+fn checklist(counts: BTreeMap<u64, usize>, names: BTreeMap<usize, &'static str>) -> BTreeMap<u64, (String, bool, usize)> {
+    counts.into_iter().map(|(id, count)| {
+        let name = match names.get(&(id as usize)) {
+            Some(name) => name.to_string(),
+            None => format!("Unknown ({})", id)
+        };
+        (id, (name, true, count))
+    }).collect()
+}
+
+fn unchecked(types: &BTreeMap<u64, (String, bool, usize)>) -> BTreeSet<u64> {
+    types.iter()
+        .filter(|(_, (_, checked, _))| !checked)
+        .map(|(id, _)| *id)
+        .collect()
+}
\ No newline at end of file
@@ -33,6 +33,78 @@ impl ViewModel for TasksToolViewModel {
                     // vm_main handles this
                     events.push(Event::EmitNetMsgWarnErrs);
                 }
+
+                if ui.add(
+                    egui::Button::new("Compare with another open demo")
+                    .min_size(button_size)
+                ).clicked() {
+                    // vm_main handles this
+                    events.push(Event::BeginDiff(None));
+                }
+
+                if ui.add(
+                    egui::Button::new("Export demo as JSON / NDJSON")
+                    .min_size(button_size)
+                ).clicked() {
+                    // vm_main handles this
+                    events.push(Event::ExportJson);
+                }
+
+                if ui.add(
+                    egui::Button::new("Switch map")
+                    .min_size(button_size)
+                ).clicked() {
+                    // vm_main handles this
+                    events.push(Event::BeginMapSwitch);
+                }
+
+                if ui.add(
+                    egui::Button::new("Anonymize players")
+                    .min_size(button_size)
+                ).clicked() {
+                    // vm_main handles this
+                    events.push(Event::AnonymizePlayers);
+                }
+
+                if ui.add(
+                    egui::Button::new("Export without…")
+                    .min_size(button_size)
+                ).clicked() {
+                    // vm_main handles this
+                    events.push(Event::BeginStripMessages);
+                }
+
+                if ui.add(
+                    egui::Button::new("Repair unfinished demo")
+                    .min_size(button_size)
+                ).clicked() {
+                    // vm_main handles this
+                    events.push(Event::RepairDemo);
+                }
+
+                if ui.add(
+                    egui::Button::new("Concatenate split recordings")
+                    .min_size(button_size)
+                ).clicked() {
+                    // vm_main handles this
+                    events.push(Event::ConcatenateDemos);
+                }
+
+                if ui.add(
+                    egui::Button::new("Save demo as")
+                    .min_size(button_size)
+                ).clicked() {
+                    // vm_main handles this
+                    events.push(Event::SaveDemoAs);
+                }
+
+                if ui.add(
+                    egui::Button::new("Round-trip check (re-save and compare)")
+                    .min_size(button_size)
+                ).clicked() {
+                    // vm_main handles this
+                    events.push(Event::RoundTripCheck);
+                }
             }
         );
     }
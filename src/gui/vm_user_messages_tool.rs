@@ -1,5 +1,6 @@
 use super::Filters;
 use super::{ Event, ViewModel, vm_protobuf_message_list::ProtobufMessageListViewModel };
+use crate::{ document::MessageLocation, edit::protobuf_field::MessageTarget };
 
 use source_demo_tool::demo_file::packet::{
     usermessage::UserMessage,
@@ -12,7 +13,8 @@ pub struct UserMessagesToolViewModel {
 }
 
 impl UserMessagesToolViewModel {
-    pub fn new(user_messages: Vec<ParsedUserMessage>, tick_interval: f32) -> Self {
+    // document_frame_indices maps the messages' frames to the document, see DemoDocument::demo_file_frame_indices.
+    pub fn new(user_messages: Vec<ParsedUserMessage>, tick_interval: f32, document_frame_indices: Vec<usize>) -> Self {
         let mut messages = Vec::new();
         let mut frame_indices = Vec::new();
         let mut message_indices = Vec::new();
@@ -24,8 +26,18 @@ impl UserMessagesToolViewModel {
             ticks.push(msg.tick);
         }
 
+        let targets: Vec<Option<MessageTarget>> = frame_indices
+            .iter()
+            .zip(&message_indices)
+            .map(|(frame_index, message_index)| document_frame_indices.get(*frame_index).map(|&frame_index| MessageTarget {
+                location: MessageLocation { sign_on: false, frame_index, message_index: *message_index },
+                user_message: true,
+            }))
+            .collect();
+
         let mut vm_messages
             = ProtobufMessageListViewModel::new("user_messages", messages);
+        vm_messages.set_message_target_callback(move |index| targets[index]);
         vm_messages.set_message_header_callback(move |index, ui, events, _| {
             ui.horizontal(|ui| {
                 let frame_index = frame_indices[index];
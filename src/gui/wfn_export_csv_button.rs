@@ -0,0 +1,35 @@
+use std::{ fs::File, io::{ self, BufWriter, Write } };
+
+use eframe::egui::Ui;
+use rfd::MessageLevel;
+
+// draws an "Export CSV" button, when clicked asks for a file and writes to it with write_fn.
+pub fn wfn_export_csv_button<F>(ui: &mut Ui, default_file_name: &str, write_fn: F)
+where F: FnOnce(&mut BufWriter<File>) -> io::Result<()> {
+    if !ui.button("Export CSV").clicked() {
+        return
+    }
+
+    let path = match rfd::FileDialog::new()
+        .add_filter("CSV", &["csv"])
+        .set_title("Export CSV")
+        .set_file_name(default_file_name)
+        .save_file() {
+        Some(p) => p,
+        None => return
+    };
+
+    let res = File::create(&path).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        write_fn(&mut writer)?;
+        writer.flush()
+    });
+
+    if let Err(e) = res {
+        rfd::MessageDialog::new()
+            .set_title("File Error")
+            .set_description(format!("Failed to export '{}': {}", path.display(), e).as_str())
+            .set_level(MessageLevel::Error)
+            .show();
+    }
+}
\ No newline at end of file
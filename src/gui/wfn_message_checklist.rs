@@ -0,0 +1,16 @@
+use std::collections::BTreeMap;
+
+use eframe::egui::Ui;
+
+// a checkbox per type with its count, as in "SayText2 (12)". returns true if any box changed.
+pub fn wfn_message_checklist<K>(ui: &mut Ui, types: &mut BTreeMap<K, (String, bool, usize)>) -> bool {
+    let mut b_changed = false;
+    for (_, (name, checked, count)) in types.iter_mut() {
+        let mut ck = *checked;
+        if ui.checkbox(&mut ck, format!("{} ({})", name, count)).changed() {
+            b_changed = true;
+            *checked = ck;
+        }
+    }
+    b_changed
+}
\ No newline at end of file
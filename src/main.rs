@@ -1,6 +1,19 @@
+mod archive;
+mod cli;
+mod diff;
+mod document;
+mod edit;
+mod export;
 mod gui;
 
+use clap::Parser;
+
 fn main() {
+    let args = cli::Args::parse();
+    if let Some(command) = args.command {
+        std::process::exit(cli::run(command));
+    }
+
     let native_options = eframe::NativeOptions {
         initial_window_size: Some([1280.0, 720.0].into()),
         initial_window_pos: Some([20.0, 20.0].into()),
@@ -12,7 +25,7 @@ fn main() {
         native_options,
         Box::new(|cc| {
             Box::new(
-                gui::NewCrawlerApp::new(cc)
+                gui::NewCrawlerApp::new(cc, args.demo)
             )
         })
     ) {